@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+// altitudes [ft] and vertical speeds [fpm]
+
+const ALTITUDE_ALERT_APPROACHING: f64 = 1000.0;
+const ALTITUDE_ALERT_DEVIATION: f64 = 200.0;
+
+// capture is complete when we are this close to the selected altitude and (almost) level
+const ALTITUDE_HOLD_BAND: f64 = 20.0;
+const ALTITUDE_HOLD_VERTICAL_SPEED: f64 = 100.0;
+
+// Inactive: climbing or descending towards the selected altitude
+// Capture (ALT*): levelling off onto the selected altitude
+// Hold (ALT): holding the selected altitude
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum AltitudeCaptureState {
+    #[default]
+    Inactive,
+    #[serde(rename = "ALT*")]
+    Capture,
+    #[serde(rename = "ALT")]
+    Hold,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum AltitudeAlert {
+    #[default]
+    None,
+    // within 1000 ft of the selected altitude, before it is captured
+    Approaching,
+    // more than 200 ft away from the selected altitude after it was captured
+    Deviation,
+}
+
+pub(super) struct AltitudeHoldInput {
+    pub altitude_error: f64,
+    pub vertical_speed: f64,
+    pub capture_state: AltitudeCaptureState,
+    pub vertical_speed_command: f64,
+    pub capture_time: f64,
+    pub minimum_capture_distance: f64,
+    pub climb_vertical_speed: f64,
+    pub vertical_acceleration: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct AltitudeHoldCommand {
+    pub capture_state: AltitudeCaptureState,
+    pub vertical_speed_command: f64,
+}
+
+// the level off starts when we would reach the selected altitude within the capture time [s] at the current vertical speed
+pub(super) fn capture_distance(vertical_speed: f64, capture_time: f64, minimum: f64) -> f64 {
+    (vertical_speed.abs() * capture_time / 60.0).max(minimum)
+}
+
+// vertical speed to fly towards or on the selected altitude
+//
+// during the capture the commanded vertical speed is proportional to the altitude error, so the plane
+// approaches the selected altitude exponentially with the capture time as time constant.
+// changes in the command are rate limited for a smooth level off.
+pub(super) fn altitude_hold(input: &AltitudeHoldInput, dt: f64) -> AltitudeHoldCommand {
+    let capture_distance: f64 = capture_distance(
+        input.vertical_speed,
+        input.capture_time,
+        input.minimum_capture_distance,
+    );
+
+    let capture_state: AltitudeCaptureState = match input.capture_state {
+        AltitudeCaptureState::Inactive if input.altitude_error.abs() <= capture_distance => {
+            AltitudeCaptureState::Capture
+        }
+        AltitudeCaptureState::Capture
+            if input.altitude_error.abs() <= ALTITUDE_HOLD_BAND
+                && input.vertical_speed.abs() <= ALTITUDE_HOLD_VERTICAL_SPEED =>
+        {
+            AltitudeCaptureState::Hold
+        }
+        state => state,
+    };
+
+    let (target, previous): (f64, f64) = match capture_state {
+        AltitudeCaptureState::Inactive => (
+            input.climb_vertical_speed.abs().copysign(input.altitude_error),
+            input.vertical_speed_command,
+        ),
+        AltitudeCaptureState::Capture | AltitudeCaptureState::Hold => {
+            let target: f64 = (input.altitude_error * 60.0 / input.capture_time.max(1.0))
+                .clamp(-input.climb_vertical_speed.abs(), input.climb_vertical_speed.abs());
+
+            // start the capture from the vertical speed we actually have, so there is no bump
+            let previous: f64 = match input.capture_state {
+                AltitudeCaptureState::Inactive => input.vertical_speed,
+                _ => input.vertical_speed_command,
+            };
+
+            (target, previous)
+        }
+    };
+
+    let max_change: f64 = input.vertical_acceleration.abs() * dt;
+
+    AltitudeHoldCommand {
+        capture_state,
+        vertical_speed_command: previous + (target - previous).clamp(-max_change, max_change),
+    }
+}
+
+pub(super) fn altitude_alert(
+    altitude_error: f64,
+    capture_state: AltitudeCaptureState,
+) -> AltitudeAlert {
+    match capture_state {
+        AltitudeCaptureState::Hold if altitude_error.abs() > ALTITUDE_ALERT_DEVIATION => {
+            AltitudeAlert::Deviation
+        }
+        AltitudeCaptureState::Inactive
+            if altitude_error.abs() <= ALTITUDE_ALERT_APPROACHING
+                && altitude_error.abs() > ALTITUDE_ALERT_DEVIATION =>
+        {
+            AltitudeAlert::Approaching
+        }
+        _ => AltitudeAlert::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(altitude_error: f64, vertical_speed: f64) -> AltitudeHoldInput {
+        AltitudeHoldInput {
+            altitude_error,
+            vertical_speed,
+            capture_state: AltitudeCaptureState::Inactive,
+            vertical_speed_command: vertical_speed,
+            capture_time: 12.0,
+            minimum_capture_distance: 50.0,
+            climb_vertical_speed: 1000.0,
+            vertical_acceleration: 200.0,
+        }
+    }
+
+    #[test]
+    fn test_capture_starts_proportional_to_vertical_speed() {
+        // 1000 fpm with a 12 s capture time starts the level off 200 ft before the selected altitude
+        assert_eq!(capture_distance(1000.0, 12.0, 50.0), 200.0);
+        assert_eq!(capture_distance(-500.0, 12.0, 50.0), 100.0);
+        assert_eq!(capture_distance(0.0, 12.0, 50.0), 50.0);
+
+        let command = altitude_hold(&input(250.0, 1000.0), 0.2);
+        assert_eq!(command.capture_state, AltitudeCaptureState::Inactive);
+
+        let command = altitude_hold(&input(190.0, 1000.0), 0.2);
+        assert_eq!(command.capture_state, AltitudeCaptureState::Capture);
+    }
+
+    #[test]
+    fn test_level_off_is_rate_limited() {
+        let mut hold_input = input(100.0, 1000.0);
+        hold_input.capture_state = AltitudeCaptureState::Capture;
+
+        // the target is 500 fpm, but the command may only change 40 fpm in 0.2 s
+        let command = altitude_hold(&hold_input, 0.2);
+        assert!((command.vertical_speed_command - 960.0).abs() < 1e-9);
+
+        hold_input.altitude_error = 10.0;
+        hold_input.vertical_speed = 50.0;
+        let command = altitude_hold(&hold_input, 0.2);
+        assert_eq!(command.capture_state, AltitudeCaptureState::Hold);
+    }
+
+    #[test]
+    fn test_altitude_alert() {
+        assert_eq!(
+            altitude_alert(900.0, AltitudeCaptureState::Inactive),
+            AltitudeAlert::Approaching
+        );
+        assert_eq!(
+            altitude_alert(1500.0, AltitudeCaptureState::Inactive),
+            AltitudeAlert::None
+        );
+        assert_eq!(
+            altitude_alert(-250.0, AltitudeCaptureState::Hold),
+            AltitudeAlert::Deviation
+        );
+        assert_eq!(
+            altitude_alert(150.0, AltitudeCaptureState::Capture),
+            AltitudeAlert::None
+        );
+    }
+}
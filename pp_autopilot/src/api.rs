@@ -0,0 +1,215 @@
+use serde::Deserialize;
+
+use crate::autothrottle::ThrustLimit;
+use crate::navigation::{BankLimit, HeadingReference, TurnDirection};
+use crate::types::TecsPriority;
+
+// the api as served on /api/v1/openapi.json
+pub(super) const OPENAPI: &str = include_str!("../openapi.json");
+
+// [fpm] the most the VS mode climbs or descends with
+pub(super) const VERTICAL_SPEED_LIMIT: f64 = 6_000.0;
+
+// the ranges a standby value has to be in, a negative heading is a heading left of north. the speed
+// goes up to the vne of the active profile [kt]
+fn standby_ranges(speed_max: f64) -> [(&'static str, f64, f64); 6] {
+    [
+        ("heading", -360.0, 360.0),
+        // [ft]
+        ("altitude", -1000.0, 50000.0),
+        // [kt]
+        ("velocity", 0.0, speed_max),
+        // [fpm]
+        ("vertical_speed", -VERTICAL_SPEED_LIMIT, VERTICAL_SPEED_LIMIT),
+        ("flch_climb_throttle", 0.0, 1.0),
+        ("flch_descent_throttle", 0.0, 1.0),
+    ]
+}
+
+// the standby values to set, the ones left out stay as they are
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct StandbyValues {
+    pub heading: Option<f64>,
+    pub altitude: Option<f64>,
+    pub velocity: Option<f64>,
+    pub vertical_speed: Option<f64>,
+    pub flch_climb_throttle: Option<f64>,
+    pub flch_descent_throttle: Option<f64>,
+}
+
+impl StandbyValues {
+    // one value, as the deprecated /set/{key}/{value} route gives it
+    pub fn from_key(key: &str, value: f64) -> Result<Self, String> {
+        let mut values: StandbyValues = StandbyValues::default();
+
+        *match key {
+            "heading" => &mut values.heading,
+            "altitude" => &mut values.altitude,
+            "velocity" => &mut values.velocity,
+            "vertical_speed" => &mut values.vertical_speed,
+            "flch_climb_throttle" => &mut values.flch_climb_throttle,
+            "flch_descent_throttle" => &mut values.flch_descent_throttle,
+            _ => return Err(format!("there is no standby value named {}", key)),
+        } = Some(value);
+
+        Ok(values)
+    }
+
+    fn as_list(&self) -> [Option<f64>; 6] {
+        [
+            self.heading,
+            self.altitude,
+            self.velocity,
+            self.vertical_speed,
+            self.flch_climb_throttle,
+            self.flch_descent_throttle,
+        ]
+    }
+
+    // all problems at once, nothing is set unless every value is good
+    pub fn validate(&self, speed_max: f64) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        for ((name, min, max), value) in standby_ranges(speed_max).iter().zip(self.as_list()) {
+            if let Some(value) = value {
+                // NaN is not in any range
+                if !(*min <= value && value <= *max) {
+                    errors.push(format!("{} must be from {} to {}, is {}", name, min, max, value));
+                }
+            }
+        }
+
+        if self.as_list().iter().all(|value| value.is_none()) {
+            errors.push("there are no standby values given".to_string());
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("; ")),
+        }
+    }
+}
+
+// a mode of one of the channels, e.g. {"channel": "horizontal", "mode": "lnav"}
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ModeRequest {
+    pub channel: String,
+    pub mode: String,
+}
+
+// the settings to change, the ones left out stay as they are. the values are written the way the
+// autopilot state shows them
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Settings {
+    pub turn_direction: Option<TurnDirection>,
+    pub heading_reference: Option<HeadingReference>,
+    pub bank_limit: Option<BankLimit>,
+    pub tecs_priority: Option<TecsPriority>,
+    pub thrust_limit: Option<ThrustLimit>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standby_ranges() {
+        let values = StandbyValues {
+            heading: Some(-20.5),
+            vertical_speed: Some(-500.0),
+            flch_climb_throttle: Some(0.85),
+            ..StandbyValues::default()
+        };
+        assert!(values.validate(163.0).is_ok());
+
+        let values = StandbyValues {
+            heading: Some(400.0),
+            velocity: Some(f64::NAN),
+            altitude: Some(3000.0),
+            ..StandbyValues::default()
+        };
+        let error: String = values.validate(163.0).unwrap_err();
+        assert!(error.contains("heading"));
+        assert!(error.contains("velocity"));
+        assert!(!error.contains("altitude"));
+
+        assert!(StandbyValues::default().validate(163.0).is_err());
+
+        // the speed is limited by the aircraft that is flown
+        let values = StandbyValues {
+            velocity: Some(200.0),
+            ..StandbyValues::default()
+        };
+        assert!(values.validate(163.0).unwrap_err().contains("velocity must be from 0 to 163"));
+        assert!(values.validate(226.0).is_ok());
+
+        let values = StandbyValues {
+            vertical_speed: Some(-7000.0),
+            ..StandbyValues::default()
+        };
+        assert!(values.validate(163.0).is_err());
+    }
+
+    #[test]
+    fn test_standby_from_key() {
+        assert_eq!(
+            StandbyValues::from_key("vertical_speed", -700.0),
+            Ok(StandbyValues {
+                vertical_speed: Some(-700.0),
+                ..StandbyValues::default()
+            })
+        );
+        assert!(StandbyValues::from_key("speed", 100.0).is_err());
+    }
+
+    #[test]
+    fn test_request_bodies() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"bank_limit": "25", "thrust_limit": "TOGA"}"#).unwrap();
+        assert_eq!(settings.bank_limit, Some(BankLimit::TwentyFive));
+        assert_eq!(settings.thrust_limit, Some(ThrustLimit::TakeOffGoAround));
+        assert_eq!(settings.turn_direction, None);
+
+        // misspelled fields are an error, not ignored
+        assert!(serde_json::from_str::<Settings>(r#"{"bank": "25"}"#).is_err());
+        assert!(serde_json::from_str::<StandbyValues>(r#"{"headng": 90}"#).is_err());
+    }
+
+    #[test]
+    fn test_openapi_document() {
+        let document: serde_json::Value = serde_json::from_str(OPENAPI).unwrap();
+        let paths = document["paths"].as_object().unwrap();
+
+        for path in [
+            "/api/v1/autopilot_state/stream",
+            "/api/v1/standby",
+            "/api/v1/standby/{key}/activate",
+            "/api/v1/modes/activate",
+            "/api/v1/modes/arm",
+            "/api/v1/modes/disarm",
+            "/api/v1/settings",
+            "/api/v1/hold",
+            "/api/v1/orbit",
+            "/api/v1/flight_director",
+        ] {
+            assert!(paths.contains_key(path), "{}", path);
+        }
+
+        assert_eq!(paths["/api/v1/set/{key}/{value}"]["get"]["deprecated"], true);
+
+        // the documented ranges are the ones that are checked, the speed has no fixed maximum
+        let properties = &document["components"]["schemas"]["StandbyValues"]["properties"];
+        for (name, min, max) in standby_ranges(f64::INFINITY) {
+            assert_eq!(properties[name]["minimum"].as_f64(), Some(min), "{}", name);
+            assert_eq!(
+                properties[name]["maximum"].as_f64(),
+                Some(max).filter(|max| max.is_finite()),
+                "{}",
+                name
+            );
+        }
+    }
+}
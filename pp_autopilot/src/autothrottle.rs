@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    pid::{Pid, PidGains, PidInput},
+    send_command,
+    types::{AutoPilotConstants, AutoThrottleModes, CommandType, PidLoop},
+};
+
+// the autothrottle owns the throttle when it is engaged, the vertical modes then only use the elevator
+pub(super) fn is_engaged(auto_pilot_state: &super::types::AutoPilotState) -> bool {
+    !matches!(
+        auto_pilot_state.autothrottle.autothrottle_mode,
+        AutoThrottleModes::Standby
+    )
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum ThrustLimit {
+    // maximum continuous thrust, used in flight
+    #[default]
+    #[serde(rename = "MCT")]
+    MaximumContinuous,
+    // take off / go around thrust
+    #[serde(rename = "TOGA")]
+    TakeOffGoAround,
+}
+
+// engaged on the runway means a take off, the throttle is held until we are airborne
+pub(super) fn engage_mode(on_runway: bool) -> AutoThrottleModes {
+    match on_runway {
+        true => AutoThrottleModes::ThrHold,
+        false => AutoThrottleModes::Speed,
+    }
+}
+
+// the take off is over when we are off the runway and safely airborne
+fn release_thr_hold(on_runway: bool, altitude_agl: f64, release_altitude: f64) -> bool {
+    !on_runway && altitude_agl >= release_altitude
+}
+
+// the throttle stays between idle and the thrust limit, and moves at most the rate limit from the last
+// command. the rate limit is part of the output limits so the integral does not wind up against it
+fn throttle_limits(
+    constants: &AutoPilotConstants,
+    thrust_limit: ThrustLimit,
+    throttle_command: f64,
+    dt: f64,
+) -> (f64, f64) {
+    let max_throttle: f64 = match thrust_limit {
+        ThrustLimit::MaximumContinuous => constants.throttle_max_continuous,
+        ThrustLimit::TakeOffGoAround => constants.throttle_toga,
+    }
+    .min(constants.throttle_toga);
+
+    let max_change: f64 = constants.autothrottle_max_rate * dt;
+
+    (
+        (throttle_command - max_change).clamp(constants.throttle_idle, max_throttle),
+        (throttle_command + max_change).clamp(constants.throttle_idle, max_throttle),
+    )
+}
+
+pub(super) async fn execute_autothrottle(
+    dt: f64,
+    client: &reqwest::Client,
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+) -> anyhow::Result<()> {
+    let autothrottle = &auto_pilot_state.autothrottle;
+    let constants = &auto_pilot_state.control_constants;
+
+    match autothrottle.autothrottle_mode {
+        AutoThrottleModes::Standby => {}
+        AutoThrottleModes::ThrHold => {
+            // during the take off the throttle is left where the pilot set it, until we are safely airborne
+            if release_thr_hold(
+                plane_state_struct.on_runway,
+                plane_state_struct.altitude_agl,
+                constants.autothrottle_thr_hold_altitude,
+            ) {
+                tracing::event!(
+                    tracing::Level::INFO,
+                    "Autothrottle THR HOLD released at {:.0} ft AGL, SPEED mode active",
+                    plane_state_struct.altitude_agl
+                );
+                app_state_proxy
+                    .release_autothrottle_thr_hold(plane_state_struct.throttle)
+                    .await?;
+            }
+        }
+        AutoThrottleModes::Speed => {
+            let velocity_error: f64 =
+                auto_pilot_state.vertical_guidance.velocity_setpoint - plane_state_struct.v_ind;
+
+            let (output_min, output_max): (f64, f64) = throttle_limits(
+                constants,
+                autothrottle.thrust_limit,
+                autothrottle.throttle_command,
+                dt,
+            );
+            let gains: PidGains = PidGains {
+                kp: constants.autothrottle_p,
+                ki: constants.autothrottle_i,
+                output_min,
+                output_max,
+                ..Default::default()
+            };
+
+            let mut autothrottle_pid: Pid = autothrottle.autothrottle_pid;
+            let throttle: f64 = autothrottle_pid.update(
+                &gains,
+                &PidInput {
+                    error: velocity_error,
+                    measurement: plane_state_struct.v_ind,
+                    feedforward: constants.tecs_cruise_throttle_base,
+                    ..Default::default()
+                },
+                dt,
+            );
+
+            tracing::event!(tracing::Level::TRACE,
+                "Autothrottle SPEED - Vind [kt]: {:.4}, velocity error [kt]: {:.4}, integral: {:.4}, thrust limit: {:?}, throttle: {:.4}",
+                plane_state_struct.v_ind, velocity_error, autothrottle_pid.integral, autothrottle.thrust_limit, throttle
+            );
+
+            app_state_proxy
+                .update_pid(PidLoop::AutoThrottle, autothrottle_pid)
+                .await?;
+            app_state_proxy.update_autothrottle(throttle).await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Throttle,
+                throttle,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thr_hold() {
+        // engaged for the take off the throttle is the pilot's, in the air the autothrottle flies the speed
+        assert_eq!(engage_mode(true), AutoThrottleModes::ThrHold);
+        assert_eq!(engage_mode(false), AutoThrottleModes::Speed);
+
+        // held on the runway, also when the radio altimeter jumps, and in the air until high enough
+        assert!(!release_thr_hold(true, 500.0, 400.0));
+        assert!(!release_thr_hold(false, 250.0, 400.0));
+        assert!(release_thr_hold(false, 400.0, 400.0));
+        assert!(release_thr_hold(false, 1200.0, 400.0));
+    }
+
+    #[test]
+    fn test_throttle_limits() {
+        let constants = AutoPilotConstants {
+            throttle_idle: 0.1,
+            throttle_max_continuous: 0.9,
+            throttle_toga: 1.0,
+            autothrottle_max_rate: 0.2,
+            ..AutoPilotConstants::new()
+        };
+
+        // the rate limit around the last command
+        let (min, max) = throttle_limits(&constants, ThrustLimit::MaximumContinuous, 0.5, 0.5);
+        assert!((min - 0.4).abs() < 1e-12);
+        assert!((max - 0.6).abs() < 1e-12);
+
+        // never below idle
+        let (min, _) = throttle_limits(&constants, ThrustLimit::MaximumContinuous, 0.15, 0.5);
+        assert_eq!(min, 0.1);
+
+        // never above the thrust limit
+        let (_, max) = throttle_limits(&constants, ThrustLimit::MaximumContinuous, 0.85, 0.5);
+        assert_eq!(max, 0.9);
+        let (_, max) = throttle_limits(&constants, ThrustLimit::TakeOffGoAround, 0.95, 0.5);
+        assert_eq!(max, 1.0);
+
+        // a maximum continuous thrust above TOGA is held at TOGA
+        let constants = AutoPilotConstants {
+            throttle_max_continuous: 1.2,
+            ..constants
+        };
+        let (_, max) = throttle_limits(&constants, ThrustLimit::MaximumContinuous, 0.95, 0.5);
+        assert_eq!(max, 1.0);
+    }
+
+    #[test]
+    fn test_speed_against_the_thrust_limit() {
+        let constants = AutoPilotConstants::new();
+        let mut pid = Pid::default();
+        pid.initialize(0.8);
+
+        // far too slow: the throttle runs up to the thrust limit at the rate limit and stays there
+        let mut throttle: f64 = 0.8;
+        for _ in 0..50 {
+            let (output_min, output_max) =
+                throttle_limits(&constants, ThrustLimit::MaximumContinuous, throttle, 0.1);
+            let gains = PidGains {
+                kp: constants.autothrottle_p,
+                ki: constants.autothrottle_i,
+                output_min,
+                output_max,
+                ..Default::default()
+            };
+
+            let next: f64 = pid.update(
+                &gains,
+                &PidInput {
+                    error: 40.0,
+                    measurement: 80.0,
+                    feedforward: constants.tecs_cruise_throttle_base,
+                    ..Default::default()
+                },
+                0.1,
+            );
+            assert!(next - throttle <= constants.autothrottle_max_rate * 0.1 + 1e-12);
+            throttle = next;
+        }
+
+        assert_eq!(throttle, constants.throttle_max_continuous);
+        assert!(pid.saturated);
+    }
+}
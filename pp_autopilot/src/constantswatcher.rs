@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{sync::mpsc, time::Duration};
+use tracing::{event, Level};
+
+use crate::profiles::{self, AircraftProfile};
+use crate::types::{AppStateProxy, AutoPilotConstants};
+
+// used when CONSTANTS_PATH is not set
+const DEFAULT_CONSTANTS_PATH: &str = "./constants.json";
+
+// number of sets of constants kept to roll back to
+pub(super) const CONSTANTS_HISTORY_LENGTH: usize = 20;
+
+// editors write a file in a few steps, so the file is read when the changes have settled
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+// when the directory cannot be watched, e.g. it does not exist or there are no inotify watches left,
+// the modification time of the file is checked this often instead
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// which constants are in effect, and why the last file was not applied (empty when it was)
+#[derive(Debug, Default, Serialize, Clone)]
+pub(super) struct ConstantsStatus {
+    pub constants_source: String,
+    // counts the sets of constants that took effect, the built in defaults are version 0
+    pub constants_version: u64,
+    // [ms]
+    pub constants_timestamp: i64,
+    pub constants_changed: Vec<String>,
+    pub constants_error: String,
+}
+
+// a set of constants that took effect
+#[derive(Debug, Serialize, Clone)]
+pub(super) struct ConstantsVersion {
+    pub version: u64,
+    // [ms]
+    pub timestamp: i64,
+    pub source: String,
+    pub changed: Vec<String>,
+    pub constants: AutoPilotConstants,
+}
+
+pub(super) fn constants_path() -> PathBuf {
+    PathBuf::from(
+        std::env::var("CONSTANTS_PATH").unwrap_or_else(|_| DEFAULT_CONSTANTS_PATH.to_string()),
+    )
+}
+
+// the names of the constants that differ, sorted
+pub(super) fn changed_constants(
+    previous: &AutoPilotConstants,
+    next: &AutoPilotConstants,
+) -> Vec<String> {
+    let (Ok(Value::Object(previous)), Ok(Value::Object(next))) =
+        (serde_json::to_value(previous), serde_json::to_value(next))
+    else {
+        return Vec::new();
+    };
+
+    next.iter()
+        .filter(|(name, value)| previous.get(*name) != Some(*value))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+// loads the constants at start up, and again every time the file changes. the constants are not
+// worth stopping the autopilot for, so this never ends: when the file cannot be watched it is polled,
+// and the last good constants stay in effect
+pub(super) async fn watch_constants(app_state_proxy: AppStateProxy, path: PathBuf) {
+    reload_constants(&app_state_proxy, &path).await;
+
+    let (tx, mut rx) = mpsc::channel::<notify::Result<Event>>(16);
+
+    match start_watcher(&path, tx) {
+        // the watcher stops when it is dropped, so it is kept for as long as its events are read
+        Ok(_watcher) => {
+            event!(Level::INFO, "Watching {} for new constants", path.display());
+
+            while let Some(res) = rx.recv().await {
+                match res {
+                    Ok(event) if is_written(&event, &path) => {
+                        tokio::time::sleep(SETTLE_TIME).await;
+                        while rx.try_recv().is_ok() {}
+
+                        reload_constants(&app_state_proxy, &path).await;
+                    }
+                    Ok(_) => {}
+                    Err(e) => event!(Level::WARN, "Error watching the constants: {:?}", e),
+                }
+            }
+
+            event!(
+                Level::WARN,
+                "Stopped watching {}, polling it every {:?} instead",
+                path.display(),
+                POLL_INTERVAL
+            );
+        }
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "Cannot watch {}, polling it every {:?} instead: {:#}",
+                path.display(),
+                POLL_INTERVAL,
+                e
+            );
+        }
+    }
+
+    poll_constants(&app_state_proxy, &path).await;
+}
+
+fn start_watcher(
+    path: &Path,
+    tx: mpsc::Sender<notify::Result<Event>>,
+) -> anyhow::Result<RecommendedWatcher> {
+    // the watcher calls back from its own thread
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res| {
+        let _ = tx.blocking_send(res);
+    })?;
+
+    // the directory is watched, because editors often replace the file instead of writing to it
+    let directory: &Path = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+// loads the constants when the modification time of the file changes, also when the file only
+// shows up later
+async fn poll_constants(app_state_proxy: &AppStateProxy, path: &Path) {
+    let mut last_modified: Option<SystemTime> = modified(path);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let modified: Option<SystemTime> = modified(path);
+        if modified.is_some() && modified != last_modified {
+            tokio::time::sleep(SETTLE_TIME).await;
+            reload_constants(app_state_proxy, path).await;
+        }
+        last_modified = modified;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// the constants in effect stay when nothing could be loaded
+async fn reload_constants(app_state_proxy: &AppStateProxy, path: &Path) {
+    if let Err(e) = load_constants(app_state_proxy, path).await {
+        event!(
+            Level::ERROR,
+            "Constants not loaded, keeping the last good constants: {:#}",
+            e
+        );
+    }
+}
+
+fn is_written(event: &Event, path: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == path.file_name())
+}
+
+// constants that do not pass are reported, and the last good constants stay in effect.
+// the profile in effect is applied on top again
+async fn load_constants(app_state_proxy: &AppStateProxy, path: &Path) -> anyhow::Result<()> {
+    let profile: String = app_state_proxy
+        .get_auto_pilot_state()
+        .await?
+        .profile_status
+        .aircraft_profile;
+
+    let (constants, source) = match profile.is_empty() {
+        true => (
+            AutoPilotConstants::from_file(path),
+            path.display().to_string(),
+        ),
+        false => (
+            AutoPilotConstants::from_file(path).and_then(|base| {
+                AircraftProfile::from_file(&profiles::profile_file(&profile)?)?.apply(&base)
+            }),
+            format!("{} with profile {}", path.display(), profile),
+        ),
+    };
+
+    match constants {
+        Ok(constants) => {
+            app_state_proxy
+                .set_autopilot_constants(constants, &source)
+                .await
+        }
+        Err(e) => {
+            event!(
+                Level::ERROR,
+                "Constants not applied, keeping the last good constants: {:#}",
+                e
+            );
+
+            app_state_proxy
+                .reject_autopilot_constants(&source, &format!("{:#}", e))
+                .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_constants_are_valid() {
+        let path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("../constants.json");
+
+        assert!(AutoPilotConstants::from_file(&path).is_ok());
+        assert!(AutoPilotConstants::new().validate().is_ok());
+    }
+
+    #[test]
+    fn test_all_problems_reported() {
+        let constants = AutoPilotConstants {
+            loop_rate: 0.0,
+            max_elevator: 1.5,
+            throttle_idle: 0.5,
+            throttle_max_continuous: 0.4,
+            envelope_breach_time: f64::NAN,
+            ..AutoPilotConstants::new()
+        };
+
+        let error: String = constants.validate().unwrap_err().to_string();
+
+        assert!(error.contains("loop_rate"));
+        assert!(error.contains("max_elevator"));
+        assert!(error.contains("throttles"));
+        assert!(error.contains("envelope_breach_time"));
+    }
+
+    #[test]
+    fn test_changed_constants() {
+        let previous: AutoPilotConstants = AutoPilotConstants::new();
+        let next = AutoPilotConstants {
+            roll_p: previous.roll_p * 2.0,
+            loop_rate: 10.0,
+            ..previous.clone()
+        };
+
+        assert_eq!(changed_constants(&previous, &next), vec!["loop_rate", "roll_p"]);
+        assert!(changed_constants(&previous, &previous).is_empty());
+    }
+
+    #[test]
+    fn test_patched_constants() {
+        let constants: AutoPilotConstants = AutoPilotConstants::new();
+
+        let patch = serde_json::json!({"roll_p": 0.008, "loop_rate": 10.0});
+        let patched: AutoPilotConstants = constants.patched(patch.as_object().unwrap()).unwrap();
+        assert_eq!(changed_constants(&constants, &patched), vec!["loop_rate", "roll_p"]);
+
+        // unknown names, wrong types and invalid values are all refused
+        for patch in [
+            serde_json::json!({"roll_pp": 0.01}),
+            serde_json::json!({"roll_p": "high"}),
+            serde_json::json!({"loop_rate": -1.0}),
+        ] {
+            assert!(constants.patched(patch.as_object().unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_saved_constants_load_again() {
+        let path: PathBuf = std::env::temp_dir()
+            .join(format!("pp_autopilot_constants_{}.json", std::process::id()));
+        let constants = AutoPilotConstants {
+            roll_p: 0.008,
+            ..AutoPilotConstants::new()
+        };
+
+        constants.to_file(&path).unwrap();
+        let loaded: AutoPilotConstants = AutoPilotConstants::from_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(changed_constants(&constants, &loaded).is_empty());
+    }
+}
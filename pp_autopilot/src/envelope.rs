@@ -0,0 +1,319 @@
+use serde::Serialize;
+
+use crate::types::{AutoPilotConstants, CommandType, PlaneStateStruct};
+
+// the limits of the flight envelope. the commands are limited from a margin inside a limit, and a breach of
+// the limit itself that lasts the breach time disconnects the autopilot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct EnvelopeLimits {
+    // [deg]
+    pub alpha_max: f64,
+    pub alpha_margin: f64,
+    // indicated airspeed [kt]
+    pub speed_min: f64,
+    pub speed_max: f64,
+    pub speed_margin: f64,
+    // [deg]
+    pub roll_max: f64,
+    pub roll_margin: f64,
+    pub pitch_max: f64,
+    pub pitch_min: f64,
+    pub pitch_margin: f64,
+    // normal load factor [g]
+    pub gload_max: f64,
+    pub gload_min: f64,
+    pub gload_margin: f64,
+    // [s]
+    pub breach_time: f64,
+    pub throttle_idle: f64,
+    pub throttle_toga: f64,
+}
+
+impl EnvelopeLimits {
+    pub(super) fn from_constants(constants: &AutoPilotConstants) -> Self {
+        EnvelopeLimits {
+            alpha_max: constants.envelope_alpha_max,
+            alpha_margin: constants.envelope_alpha_margin,
+            speed_min: constants.envelope_speed_min,
+            speed_max: constants.envelope_speed_max,
+            speed_margin: constants.envelope_speed_margin,
+            roll_max: constants.envelope_roll_max,
+            roll_margin: constants.envelope_roll_margin,
+            pitch_max: constants.envelope_pitch_max,
+            pitch_min: constants.envelope_pitch_min,
+            pitch_margin: constants.envelope_pitch_margin,
+            gload_max: constants.envelope_gload_max,
+            gload_min: constants.envelope_gload_min,
+            gload_margin: constants.envelope_gload_margin,
+            breach_time: constants.envelope_breach_time,
+            throttle_idle: constants.throttle_idle,
+            throttle_toga: constants.throttle_toga,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct EnvelopeInput {
+    pub alpha: f64,
+    pub speed: f64,
+    pub roll: f64,
+    pub pitch: f64,
+    pub gload: f64,
+    pub on_ground: bool,
+}
+
+impl EnvelopeInput {
+    pub(super) fn from_plane_state(plane_state_struct: &PlaneStateStruct) -> Self {
+        EnvelopeInput {
+            alpha: plane_state_struct.alpha,
+            speed: plane_state_struct.v_ind,
+            roll: plane_state_struct.roll,
+            pitch: plane_state_struct.pitch,
+            gload: plane_state_struct.gload_normal,
+            on_ground: plane_state_struct.on_runway,
+        }
+    }
+}
+
+// the share of the command authority that is left in every direction, 1 is all of it and 0 none
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(super) struct EnvelopeProtection {
+    pub envelope_nose_up: f64,
+    pub envelope_nose_down: f64,
+    pub envelope_roll_left: f64,
+    pub envelope_roll_right: f64,
+    pub envelope_throttle_min: f64,
+    pub envelope_throttle_max: f64,
+    // the limit that is exceeded now, and for how long [s]
+    pub envelope_breach: String,
+    pub envelope_breach_time: f64,
+}
+
+impl Default for EnvelopeProtection {
+    fn default() -> Self {
+        EnvelopeProtection {
+            envelope_nose_up: 1.0,
+            envelope_nose_down: 1.0,
+            envelope_roll_left: 1.0,
+            envelope_roll_right: 1.0,
+            envelope_throttle_min: 0.0,
+            envelope_throttle_max: 1.0,
+            envelope_breach: String::new(),
+            envelope_breach_time: 0.0,
+        }
+    }
+}
+
+impl EnvelopeProtection {
+    // returns why the autopilot has to disconnect, when a limit has been exceeded for the breach time.
+    // on the ground nothing is limited, the take off roll is below every speed limit
+    pub(super) fn update(
+        &mut self,
+        limits: &EnvelopeLimits,
+        input: &EnvelopeInput,
+        dt: f64,
+    ) -> Option<String> {
+        if input.on_ground {
+            *self = EnvelopeProtection::default();
+            return None;
+        }
+
+        let alpha_high: f64 = band(input.alpha, limits.alpha_max, limits.alpha_margin, true);
+        let speed_low: f64 = band(input.speed, limits.speed_min, limits.speed_margin, false);
+        let speed_high: f64 = band(input.speed, limits.speed_max, limits.speed_margin, true);
+        let roll_right: f64 = band(input.roll, limits.roll_max, limits.roll_margin, true);
+        let roll_left: f64 = band(input.roll, -limits.roll_max, limits.roll_margin, false);
+        let pitch_high: f64 = band(input.pitch, limits.pitch_max, limits.pitch_margin, true);
+        let pitch_low: f64 = band(input.pitch, limits.pitch_min, limits.pitch_margin, false);
+        let gload_high: f64 = band(input.gload, limits.gload_max, limits.gload_margin, true);
+        let gload_low: f64 = band(input.gload, limits.gload_min, limits.gload_margin, false);
+
+        self.envelope_nose_up = 1.0 - alpha_high.max(speed_low).max(pitch_high).max(gload_high);
+        self.envelope_nose_down = 1.0 - speed_high.max(pitch_low).max(gload_low);
+        self.envelope_roll_right = 1.0 - roll_right;
+        self.envelope_roll_left = 1.0 - roll_left;
+
+        // power is added when slow, and taken away when fast
+        let throttle_range: f64 = limits.throttle_toga - limits.throttle_idle;
+        self.envelope_throttle_min = limits.throttle_idle + alpha_high.max(speed_low) * throttle_range;
+        self.envelope_throttle_max = limits.throttle_toga - speed_high * throttle_range;
+
+        let breach: Option<String> = breach(limits, input);
+
+        self.envelope_breach_time = match breach {
+            Some(_) => self.envelope_breach_time + dt,
+            None => 0.0,
+        };
+        self.envelope_breach = breach.clone().unwrap_or_default();
+
+        breach.filter(|_| self.envelope_breach_time >= limits.breach_time)
+    }
+
+    // scales a command down in the directions that are protected
+    pub(super) fn limit(&self, command_type: CommandType, value: f64) -> f64 {
+        match command_type {
+            CommandType::Aileron if value > 0.0 => value * self.envelope_roll_right,
+            CommandType::Aileron => value * self.envelope_roll_left,
+            CommandType::Elevator if value > 0.0 => value * self.envelope_nose_up,
+            CommandType::Elevator => value * self.envelope_nose_down,
+            // when slow and fast at the same time the minimum wins, stalling is worse
+            CommandType::Throttle => value
+                .min(self.envelope_throttle_max)
+                .max(self.envelope_throttle_min),
+        }
+    }
+}
+
+// how far a value is into the margin before a limit, 0 outside the margin and 1 at or beyond the limit
+fn band(value: f64, limit: f64, margin: f64, upper: bool) -> f64 {
+    let distance: f64 = match upper {
+        true => limit - value,
+        false => value - limit,
+    };
+
+    match margin > 0.0 {
+        true => (1.0 - distance / margin).clamp(0.0, 1.0),
+        false if distance <= 0.0 => 1.0,
+        false => 0.0,
+    }
+}
+
+fn breach(limits: &EnvelopeLimits, input: &EnvelopeInput) -> Option<String> {
+    if input.alpha > limits.alpha_max {
+        Some(format!(
+            "angle of attack {:.1} deg above the limit of {:.1} deg",
+            input.alpha, limits.alpha_max
+        ))
+    } else if input.speed < limits.speed_min {
+        Some(format!(
+            "airspeed {:.0} kt below the limit of {:.0} kt",
+            input.speed, limits.speed_min
+        ))
+    } else if input.speed > limits.speed_max {
+        Some(format!(
+            "airspeed {:.0} kt above the limit of {:.0} kt",
+            input.speed, limits.speed_max
+        ))
+    } else if input.roll.abs() > limits.roll_max {
+        Some(format!(
+            "bank angle {:.0} deg beyond the limit of {:.0} deg",
+            input.roll, limits.roll_max
+        ))
+    } else if input.pitch > limits.pitch_max || input.pitch < limits.pitch_min {
+        Some(format!(
+            "pitch {:.1} deg outside the limits of {:.1} to {:.1} deg",
+            input.pitch, limits.pitch_min, limits.pitch_max
+        ))
+    } else if input.gload > limits.gload_max || input.gload < limits.gload_min {
+        Some(format!(
+            "load factor {:.2} g outside the limits of {:.2} to {:.2} g",
+            input.gload, limits.gload_min, limits.gload_max
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: EnvelopeLimits = EnvelopeLimits {
+        alpha_max: 15.0,
+        alpha_margin: 3.0,
+        speed_min: 50.0,
+        speed_max: 160.0,
+        speed_margin: 10.0,
+        roll_max: 45.0,
+        roll_margin: 10.0,
+        pitch_max: 25.0,
+        pitch_min: -20.0,
+        pitch_margin: 5.0,
+        gload_max: 3.0,
+        gload_min: -1.0,
+        gload_margin: 0.5,
+        breach_time: 0.5,
+        throttle_idle: 0.0,
+        throttle_toga: 1.0,
+    };
+
+    const CRUISE: EnvelopeInput = EnvelopeInput {
+        alpha: 3.0,
+        speed: 100.0,
+        roll: 0.0,
+        pitch: 2.0,
+        gload: 1.0,
+        on_ground: false,
+    };
+
+    #[test]
+    fn test_no_protection_inside_the_envelope() {
+        let mut protection: EnvelopeProtection = EnvelopeProtection::default();
+
+        assert_eq!(protection.update(&LIMITS, &CRUISE, 0.2), None);
+        assert_eq!(protection, EnvelopeProtection::default());
+        assert_eq!(protection.limit(CommandType::Elevator, 0.3), 0.3);
+        assert_eq!(protection.limit(CommandType::Throttle, 0.6), 0.6);
+    }
+
+    #[test]
+    fn test_commands_limited_near_the_edges() {
+        let mut protection: EnvelopeProtection = EnvelopeProtection::default();
+
+        // halfway into the low speed margin, and banked halfway into the roll margin to the right
+        let slow = EnvelopeInput {
+            speed: 55.0,
+            roll: 40.0,
+            ..CRUISE
+        };
+
+        assert_eq!(protection.update(&LIMITS, &slow, 0.2), None);
+        assert_eq!(protection.limit(CommandType::Elevator, 0.4), 0.2);
+        assert_eq!(protection.limit(CommandType::Elevator, -0.4), -0.4);
+        assert_eq!(protection.limit(CommandType::Aileron, 0.2), 0.1);
+        assert_eq!(protection.limit(CommandType::Aileron, -0.2), -0.2);
+        assert_eq!(protection.limit(CommandType::Throttle, 0.2), 0.5);
+
+        // at the overspeed limit there is nothing left to push or to add power with
+        let fast = EnvelopeInput {
+            speed: 160.0,
+            ..CRUISE
+        };
+
+        assert_eq!(protection.update(&LIMITS, &fast, 0.2), None);
+        assert_eq!(protection.limit(CommandType::Elevator, -0.3), 0.0);
+        assert_eq!(protection.limit(CommandType::Throttle, 0.8), 0.0);
+    }
+
+    #[test]
+    fn test_breach_disconnects_after_the_breach_time() {
+        let mut protection: EnvelopeProtection = EnvelopeProtection::default();
+        let stalled = EnvelopeInput {
+            alpha: 17.0,
+            ..CRUISE
+        };
+
+        assert_eq!(protection.update(&LIMITS, &stalled, 0.2), None);
+        assert!(protection.envelope_breach.starts_with("angle of attack"));
+
+        // a short spike does not disconnect
+        assert_eq!(protection.update(&LIMITS, &CRUISE, 0.2), None);
+        assert_eq!(protection.envelope_breach_time, 0.0);
+
+        assert_eq!(protection.update(&LIMITS, &stalled, 0.3), None);
+        assert!(protection.update(&LIMITS, &stalled, 0.3).is_some());
+    }
+
+    #[test]
+    fn test_nothing_limited_on_the_ground() {
+        let mut protection: EnvelopeProtection = EnvelopeProtection::default();
+        let take_off_roll = EnvelopeInput {
+            speed: 20.0,
+            on_ground: true,
+            ..CRUISE
+        };
+
+        assert_eq!(protection.update(&LIMITS, &take_off_roll, 1.0), None);
+        assert_eq!(protection, EnvelopeProtection::default());
+    }
+}
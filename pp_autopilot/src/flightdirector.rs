@@ -0,0 +1,177 @@
+use serde::Serialize;
+
+use crate::pid::Pid;
+use crate::types::CommandType;
+
+// the flight director shows the pilot what the guidance commands, so the modes can be hand flown.
+// the autopilot is coupled to the controls separately, the guidance runs the same either way
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub(super) struct FlightDirector {
+    pub flight_director_on: bool,
+    pub autopilot_coupled: bool,
+    // [deg] the bank and pitch the guidance commands, none for a channel in standby or with the flight director off
+    pub fd_bank_cue: Option<f64>,
+    pub fd_pitch_cue: Option<f64>,
+    // [0..1]
+    pub fd_throttle_cue: Option<f64>,
+}
+
+// the autopilot flies, like it always did
+impl Default for FlightDirector {
+    fn default() -> Self {
+        FlightDirector {
+            flight_director_on: false,
+            autopilot_coupled: true,
+            fd_bank_cue: None,
+            fd_pitch_cue: None,
+            fd_throttle_cue: None,
+        }
+    }
+}
+
+// what the guidance commands this cycle, none for a channel that commands nothing
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(super) struct FlightDirectorCues {
+    pub bank: Option<f64>,
+    pub pitch: Option<f64>,
+    pub throttle: Option<f64>,
+}
+
+impl FlightDirector {
+    // a command only moves the control when the autopilot is coupled, the autothrottle is not part of
+    // the autopilot and keeps the throttle
+    pub(super) fn moves_control(&self, command_type: CommandType, autothrottle_engaged: bool) -> bool {
+        self.autopilot_coupled
+            || (matches!(command_type, CommandType::Throttle) && autothrottle_engaged)
+    }
+
+    pub(super) fn update_cues(&mut self, cues: &FlightDirectorCues) {
+        let cues: FlightDirectorCues = match self.flight_director_on {
+            true => *cues,
+            false => FlightDirectorCues::default(),
+        };
+
+        self.fd_bank_cue = cues.bank;
+        self.fd_pitch_cue = cues.pitch;
+        self.fd_throttle_cue = cues.throttle;
+    }
+
+    // AP only while it flies a mode, FD whenever the bars are shown
+    pub(super) fn annunciation(&self, engaged: bool) -> &'static str {
+        match (self.autopilot_coupled && engaged, self.flight_director_on) {
+            (true, true) => "AP FD",
+            (true, false) => "AP",
+            (false, true) => "FD",
+            (false, false) => "",
+        }
+    }
+}
+
+// the inner loop of a channel starts from the yoke where it is held, so a trimmed climb or an established
+// turn is taken over without a bump, when the autopilot is coupled and when a mode engages
+pub(super) fn couple_inner_loop(pid: &mut Pid, yoke: Option<f64>) {
+    match yoke {
+        Some(yoke) => pid.initialize(yoke),
+        None => pid.reset(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pid::{PidGains, PidInput};
+
+    #[test]
+    fn test_moves_control() {
+        let coupled = FlightDirector::default();
+        assert!(coupled.moves_control(CommandType::Aileron, false));
+        assert!(coupled.moves_control(CommandType::Elevator, false));
+        assert!(coupled.moves_control(CommandType::Throttle, false));
+
+        let uncoupled = FlightDirector {
+            flight_director_on: true,
+            autopilot_coupled: false,
+            ..Default::default()
+        };
+        assert!(!uncoupled.moves_control(CommandType::Aileron, true));
+        assert!(!uncoupled.moves_control(CommandType::Elevator, true));
+
+        // the throttle of the vertical modes is a cue, the autothrottle still flies it
+        assert!(!uncoupled.moves_control(CommandType::Throttle, false));
+        assert!(uncoupled.moves_control(CommandType::Throttle, true));
+    }
+
+    #[test]
+    fn test_cues() {
+        let cues = FlightDirectorCues {
+            bank: Some(-15.0),
+            pitch: Some(4.5),
+            throttle: None,
+        };
+
+        let mut flight_director = FlightDirector::default();
+        flight_director.update_cues(&cues);
+        assert_eq!(flight_director.fd_bank_cue, None);
+        assert_eq!(flight_director.fd_pitch_cue, None);
+
+        flight_director.flight_director_on = true;
+        flight_director.update_cues(&cues);
+        assert_eq!(flight_director.fd_bank_cue, Some(-15.0));
+        assert_eq!(flight_director.fd_pitch_cue, Some(4.5));
+        assert_eq!(flight_director.fd_throttle_cue, None);
+
+        // switching the bars off clears the cues on the next cycle
+        flight_director.flight_director_on = false;
+        flight_director.update_cues(&cues);
+        assert_eq!(flight_director.fd_bank_cue, None);
+    }
+
+    #[test]
+    fn test_couple_inner_loop() {
+        let gains = PidGains {
+            kp: 0.05,
+            ki: 0.02,
+            kd: 0.01,
+            output_min: -1.0,
+            output_max: 1.0,
+            ..Default::default()
+        };
+        let input = PidInput {
+            error: 3.0,
+            measurement: 12.0,
+            measurement_rate: Some(1.5),
+            feedforward: 0.02,
+        };
+
+        // the loop wound up while the pilot flew, the first command after coupling is the yoke
+        let mut pid = Pid {
+            integral: 0.6,
+            output: -0.4,
+            ..Default::default()
+        };
+        couple_inner_loop(&mut pid, Some(-0.12));
+        assert!((pid.update(&gains, &input, 0.1) - -0.12).abs() < 1e-9);
+
+        // without a yoke position it starts from nothing
+        let mut pid = Pid {
+            integral: 0.6,
+            ..Default::default()
+        };
+        couple_inner_loop(&mut pid, None);
+        assert_eq!(pid.integral, 0.0);
+    }
+
+    #[test]
+    fn test_annunciation() {
+        let mut flight_director = FlightDirector::default();
+        assert_eq!(flight_director.annunciation(true), "AP");
+        assert_eq!(flight_director.annunciation(false), "");
+
+        flight_director.flight_director_on = true;
+        assert_eq!(flight_director.annunciation(true), "AP FD");
+
+        flight_director.autopilot_coupled = false;
+        assert_eq!(flight_director.annunciation(true), "FD");
+    }
+}
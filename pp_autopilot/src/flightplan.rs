@@ -0,0 +1,526 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::navigation::Position;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Waypoint {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub altitude_constraint: Option<AltitudeConstraint>,
+    // indicated airspeed [kt] to fly on the leg towards this waypoint
+    #[serde(default)]
+    pub speed_constraint: Option<f64>,
+}
+
+// altitude restriction at a waypoint [ft], used by VNAV to build the vertical profile
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AltitudeConstraint {
+    At(f64),
+    AtOrAbove(f64),
+    AtOrBelow(f64),
+}
+
+impl Waypoint {
+    pub fn position(&self) -> Position {
+        Position {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
+
+    // the same checks for a waypoint from a file, one that is inserted and new constraints
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(anyhow!("Waypoint {} has an invalid position", self.name));
+        }
+
+        if let Some(speed) = self.speed_constraint {
+            if !(speed > 0.0 && speed.is_finite()) {
+                return Err(anyhow!(
+                    "Waypoint {} has an invalid speed constraint of {} kt",
+                    self.name,
+                    speed
+                ));
+            }
+        }
+
+        if let Some(
+            AltitudeConstraint::At(altitude)
+            | AltitudeConstraint::AtOrAbove(altitude)
+            | AltitudeConstraint::AtOrBelow(altitude),
+        ) = self.altitude_constraint
+        {
+            if !altitude.is_finite() {
+                return Err(anyhow!(
+                    "Waypoint {} has an invalid altitude constraint of {} ft",
+                    self.name,
+                    altitude
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// the active flight plan, LNAV flies the leg from leg_origin to the active waypoint
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FlightPlan {
+    pub waypoints: Vec<Waypoint>,
+    pub active_waypoint: usize,
+    pub leg_origin: Position,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlightPlanFormat {
+    Fms,
+    Gpx,
+    Kml,
+    Json,
+}
+
+impl FlightPlan {
+    // replace the route, the first leg is flown from the given position
+    pub fn load(&mut self, waypoints: Vec<Waypoint>, current_position: Position) {
+        self.waypoints = waypoints;
+        self.active_waypoint = 0;
+        self.leg_origin = current_position;
+    }
+
+    pub fn clear(&mut self) {
+        self.waypoints.clear();
+        self.active_waypoint = 0;
+    }
+
+    pub fn active(&self) -> Option<&Waypoint> {
+        self.waypoints.get(self.active_waypoint)
+    }
+
+    // true when there is no waypoint left to fly to
+    pub fn is_finished(&self) -> bool {
+        self.active_waypoint >= self.waypoints.len()
+    }
+
+    // insert a waypoint before the given index, inserting at the active waypoint makes it the new active waypoint
+    pub fn insert_waypoint(&mut self, index: usize, waypoint: Waypoint) -> anyhow::Result<()> {
+        if index > self.waypoints.len() {
+            return Err(anyhow!(
+                "Cannot insert waypoint at index {}, flight plan has {} waypoints",
+                index,
+                self.waypoints.len()
+            ));
+        }
+
+        waypoint.validate()?;
+
+        self.waypoints.insert(index, waypoint);
+
+        if index < self.active_waypoint {
+            self.active_waypoint += 1;
+        }
+
+        Ok(())
+    }
+
+    // delete a waypoint, deleting the active waypoint makes the next one active
+    pub fn delete_waypoint(&mut self, index: usize) -> anyhow::Result<Waypoint> {
+        if index >= self.waypoints.len() {
+            return Err(anyhow!(
+                "Cannot delete waypoint at index {}, flight plan has {} waypoints",
+                index,
+                self.waypoints.len()
+            ));
+        }
+
+        if index < self.active_waypoint {
+            self.active_waypoint -= 1;
+        }
+
+        Ok(self.waypoints.remove(index))
+    }
+
+    // fly direct from the current position to the waypoint, skipping the waypoints before it
+    pub fn direct_to(&mut self, index: usize, current_position: Position) -> anyhow::Result<()> {
+        if index >= self.waypoints.len() {
+            return Err(anyhow!(
+                "Cannot fly direct to waypoint {}, flight plan has {} waypoints",
+                index,
+                self.waypoints.len()
+            ));
+        }
+
+        self.active_waypoint = index;
+        self.leg_origin = current_position;
+
+        Ok(())
+    }
+
+    pub fn set_constraints(
+        &mut self,
+        index: usize,
+        altitude_constraint: Option<AltitudeConstraint>,
+        speed_constraint: Option<f64>,
+    ) -> anyhow::Result<()> {
+        let number_of_waypoints: usize = self.waypoints.len();
+
+        let waypoint: &mut Waypoint = self.waypoints.get_mut(index).ok_or_else(|| {
+            anyhow!(
+                "Cannot set constraints on waypoint {}, flight plan has {} waypoints",
+                index,
+                number_of_waypoints
+            )
+        })?;
+
+        let constrained: Waypoint = Waypoint {
+            altitude_constraint,
+            speed_constraint,
+            ..waypoint.clone()
+        };
+        constrained.validate()?;
+
+        *waypoint = constrained;
+
+        Ok(())
+    }
+
+    // the active waypoint has been passed, the next leg starts there
+    pub fn sequence(&mut self) {
+        if let Some(passed) = self.active() {
+            self.leg_origin = passed.position();
+            self.active_waypoint += 1;
+        }
+    }
+}
+
+// parse a flight plan file, the format is detected from the contents if not given
+pub fn parse_flight_plan(
+    data: &str,
+    format: Option<FlightPlanFormat>,
+) -> anyhow::Result<Vec<Waypoint>> {
+    let format: FlightPlanFormat = match format {
+        Some(f) => f,
+        None => detect_format(data)?,
+    };
+
+    let waypoints: Vec<Waypoint> = match format {
+        FlightPlanFormat::Fms => parse_fms(data)?,
+        FlightPlanFormat::Gpx => parse_gpx(data)?,
+        FlightPlanFormat::Kml => parse_kml(data)?,
+        FlightPlanFormat::Json => serde_json::from_str(data)?,
+    };
+
+    for waypoint in waypoints.iter() {
+        waypoint.validate()?;
+    }
+
+    Ok(waypoints)
+}
+
+fn detect_format(data: &str) -> anyhow::Result<FlightPlanFormat> {
+    let trimmed = data.trim_start();
+
+    if trimmed.starts_with('[') {
+        Ok(FlightPlanFormat::Json)
+    } else if trimmed.contains("<gpx") {
+        Ok(FlightPlanFormat::Gpx)
+    } else if trimmed.contains("<kml") {
+        Ok(FlightPlanFormat::Kml)
+    } else if trimmed.starts_with("I\n")
+        || trimmed.starts_with("A\n")
+        || trimmed.starts_with("I\r\n")
+        || trimmed.starts_with("A\r\n")
+    {
+        Ok(FlightPlanFormat::Fms)
+    } else {
+        Err(anyhow!("Unknown flight plan format"))
+    }
+}
+
+// X-Plane .fms files, both version 3 (X-Plane 10) and version 1100 (X-Plane 11)
+//
+// I
+// 1100 Version
+// ...
+// NUMENR 3
+// 1 EHAM ADEP 0.000000 52.308613 4.763889
+// 11 SPY DRCT 0.000000 52.540278 4.853889
+// 28 +52.100_+004.500 DRCT 3000.000000 52.100000 4.500000
+//
+// version 3 lines have no airway column: type, ident, altitude, latitude, longitude
+fn parse_fms(data: &str) -> anyhow::Result<Vec<Waypoint>> {
+    let mut lines = data.lines().map(str::trim).filter(|l| !l.is_empty());
+
+    match lines.next() {
+        Some("I") | Some("A") => {}
+        _ => return Err(anyhow!("Not an X-Plane fms file, missing header")),
+    }
+
+    let version: u32 = lines
+        .next()
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow!("Not an X-Plane fms file, missing version"))?;
+
+    let mut waypoints: Vec<Waypoint> = Vec::new();
+
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        // waypoint lines start with a numeric type, the header lines (CYCLE, ADEP, NUMENR, ...) do not
+        // version 3 files also have two single number header lines (cycle and number of waypoints)
+        if fields.len() < 5 || fields[0].parse::<u32>().is_err() {
+            continue;
+        }
+
+        let (altitude, latitude, longitude) = match (version, fields.len()) {
+            (3, 5) => (fields[2], fields[3], fields[4]),
+            (v, 6) if v >= 1100 => (fields[3], fields[4], fields[5]),
+            _ => return Err(anyhow!("Cannot parse fms waypoint line: {}", line)),
+        };
+
+        // the altitude of an airport (type 1) is its elevation, for the other waypoints it is the planned altitude
+        let altitude: f64 = altitude.parse()?;
+        let altitude_constraint: Option<AltitudeConstraint> = match fields[0] {
+            "1" => None,
+            _ if altitude > 0.0 => Some(AltitudeConstraint::At(altitude)),
+            _ => None,
+        };
+
+        waypoints.push(Waypoint {
+            name: fields[1].to_string(),
+            latitude: latitude.parse()?,
+            longitude: longitude.parse()?,
+            altitude_constraint,
+            speed_constraint: None,
+        });
+    }
+
+    if waypoints.is_empty() {
+        return Err(anyhow!("No waypoints found in fms file"));
+    }
+
+    Ok(waypoints)
+}
+
+// GPX routes (rte/rtept), falling back to plain waypoints (wpt) when there is no route
+fn parse_gpx(data: &str) -> anyhow::Result<Vec<Waypoint>> {
+    let document = roxmltree::Document::parse(data)?;
+
+    let mut waypoints: Vec<Waypoint> = Vec::new();
+
+    for tag in ["rtept", "wpt"] {
+        for (index, node) in document
+            .descendants()
+            .filter(|n| n.has_tag_name(tag))
+            .enumerate()
+        {
+            let latitude: f64 = node
+                .attribute("lat")
+                .ok_or_else(|| anyhow!("GPX {} without lat attribute", tag))?
+                .parse()?;
+            let longitude: f64 = node
+                .attribute("lon")
+                .ok_or_else(|| anyhow!("GPX {} without lon attribute", tag))?
+                .parse()?;
+
+            let name: String = node
+                .children()
+                .find(|c| c.has_tag_name("name"))
+                .and_then(|c| c.text())
+                .map(|t| t.trim().to_string())
+                .unwrap_or_else(|| format!("WPT{:02}", index + 1));
+
+            waypoints.push(Waypoint {
+                name,
+                latitude,
+                longitude,
+                ..Default::default()
+            });
+        }
+
+        if !waypoints.is_empty() {
+            break;
+        }
+    }
+
+    if waypoints.is_empty() {
+        return Err(anyhow!("No route or waypoints found in GPX file"));
+    }
+
+    Ok(waypoints)
+}
+
+// KML LineStrings, coordinates are "lon,lat[,alt]" tuples separated by whitespace
+fn parse_kml(data: &str) -> anyhow::Result<Vec<Waypoint>> {
+    let document = roxmltree::Document::parse(data)?;
+
+    let mut waypoints: Vec<Waypoint> = Vec::new();
+
+    for line_string in document
+        .descendants()
+        .filter(|n| n.has_tag_name("LineString"))
+    {
+        let coordinates: &str = line_string
+            .descendants()
+            .find(|n| n.has_tag_name("coordinates"))
+            .and_then(|n| n.text())
+            .ok_or_else(|| anyhow!("KML LineString without coordinates"))?;
+
+        for tuple in coordinates.split_whitespace() {
+            let mut values = tuple.split(',');
+
+            let longitude: f64 = values
+                .next()
+                .ok_or_else(|| anyhow!("Invalid KML coordinate: {}", tuple))?
+                .parse()?;
+            let latitude: f64 = values
+                .next()
+                .ok_or_else(|| anyhow!("Invalid KML coordinate: {}", tuple))?
+                .parse()?;
+
+            waypoints.push(Waypoint {
+                name: format!("WPT{:02}", waypoints.len() + 1),
+                latitude,
+                longitude,
+                ..Default::default()
+            });
+        }
+    }
+
+    if waypoints.is_empty() {
+        return Err(anyhow!("No LineString found in KML file"));
+    }
+
+    Ok(waypoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fms() {
+        let fms = "I\n1100 Version\nCYCLE 1710\nADEP EHAM\nADES EHRD\nNUMENR 3\n\
+            1 EHAM ADEP 0.000000 52.308613 4.763889\n\
+            11 SPY DRCT 3000.000000 52.540278 4.853889\n\
+            1 EHRD ADES 0.000000 51.956944 4.437222\n";
+
+        let waypoints = parse_flight_plan(fms, None).unwrap();
+        assert_eq!(waypoints.len(), 3);
+        assert_eq!(waypoints[1].name, "SPY");
+        assert_eq!(
+            waypoints[1].altitude_constraint,
+            Some(AltitudeConstraint::At(3000.0))
+        );
+        assert_eq!(waypoints[0].altitude_constraint, None);
+        assert_eq!(waypoints[2].latitude, 51.956944);
+
+        let fms_v3 = "A\n3 version\n1\n2\n1 EHAM 0.000000 52.308613 4.763889\n";
+        let waypoints = parse_flight_plan(fms_v3, None).unwrap();
+        assert_eq!(waypoints[0].longitude, 4.763889);
+    }
+
+    #[test]
+    fn test_parse_gpx_and_kml() {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <rte>
+                <rtept lat="52.3" lon="4.7"><name>EHAM</name></rtept>
+                <rtept lat="52.5" lon="4.8"></rtept>
+              </rte>
+            </gpx>"#;
+
+        let waypoints = parse_flight_plan(gpx, None).unwrap();
+        assert_eq!(waypoints.len(), 2);
+        assert_eq!(waypoints[0].name, "EHAM");
+        assert_eq!(waypoints[1].name, "WPT02");
+
+        let kml = r#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document><Placemark>
+              <LineString><coordinates>4.7,52.3,0 4.8,52.5,900</coordinates></LineString>
+            </Placemark></Document></kml>"#;
+
+        let waypoints = parse_flight_plan(kml, Some(FlightPlanFormat::Kml)).unwrap();
+        assert_eq!(waypoints.len(), 2);
+        assert_eq!(waypoints[1].latitude, 52.5);
+        assert_eq!(waypoints[1].longitude, 4.8);
+    }
+
+    #[test]
+    fn test_edit_flight_plan() {
+        let waypoint = |name: &str| Waypoint {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        let mut flight_plan = FlightPlan::default();
+        flight_plan.load(
+            vec![waypoint("A"), waypoint("B"), waypoint("C")],
+            Position::default(),
+        );
+        flight_plan.sequence();
+        assert_eq!(flight_plan.active().unwrap().name, "B");
+
+        // inserting before the active waypoint keeps B active
+        flight_plan.insert_waypoint(0, waypoint("X")).unwrap();
+        assert_eq!(flight_plan.active().unwrap().name, "B");
+
+        // deleting the active waypoint makes the next one active
+        flight_plan.delete_waypoint(2).unwrap();
+        assert_eq!(flight_plan.active().unwrap().name, "C");
+
+        assert!(flight_plan.direct_to(5, Position::default()).is_err());
+        flight_plan.direct_to(0, Position::default()).unwrap();
+        assert_eq!(flight_plan.active().unwrap().name, "X");
+    }
+
+    #[test]
+    fn test_validate_waypoints() {
+        let mut flight_plan = FlightPlan::default();
+        flight_plan.load(
+            vec![Waypoint {
+                name: "A".to_string(),
+                ..Default::default()
+            }],
+            Position::default(),
+        );
+
+        // an inserted waypoint gets the checks of a waypoint from a file
+        let off_the_map = Waypoint {
+            name: "X".to_string(),
+            latitude: 95.0,
+            ..Default::default()
+        };
+        let error: String = flight_plan.insert_waypoint(0, off_the_map).unwrap_err().to_string();
+        assert!(error.contains("invalid position"));
+        assert_eq!(flight_plan.waypoints.len(), 1);
+
+        let backwards = Waypoint {
+            name: "Y".to_string(),
+            speed_constraint: Some(-120.0),
+            ..Default::default()
+        };
+        assert!(flight_plan.insert_waypoint(1, backwards).is_err());
+
+        // bad constraints leave the ones there were
+        flight_plan
+            .set_constraints(0, Some(AltitudeConstraint::At(3000.0)), Some(140.0))
+            .unwrap();
+        assert!(flight_plan.set_constraints(0, None, Some(f64::NAN)).is_err());
+        assert!(flight_plan
+            .set_constraints(0, Some(AltitudeConstraint::AtOrBelow(f64::INFINITY)), None)
+            .is_err());
+        assert_eq!(flight_plan.waypoints[0].speed_constraint, Some(140.0));
+        assert_eq!(
+            flight_plan.waypoints[0].altitude_constraint,
+            Some(AltitudeConstraint::At(3000.0))
+        );
+
+        let json = r#"[{"name": "A", "latitude": 52.0, "longitude": 4.0, "speed_constraint": 0}]"#;
+        assert!(parse_flight_plan(json, Some(FlightPlanFormat::Json)).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::types::AutoPilotConstants;
+
+// a table of values for one gain, against indicated airspeed [kt] and optionally altitude [ft]
+//
+// without altitudes there is a single row of gains, one per airspeed. with altitudes there is one row
+// per altitude. in between the breakpoints the gain is interpolated linearly, outside of them the
+// gain at the nearest breakpoint is used.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct GainSchedule {
+    pub airspeed: Vec<f64>,
+    #[serde(default)]
+    pub altitude: Vec<f64>,
+    pub gains: Vec<Vec<f64>>,
+}
+
+impl GainSchedule {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        check_breakpoints("airspeed", &self.airspeed)?;
+
+        let rows: usize = match self.altitude.is_empty() {
+            true => 1,
+            false => {
+                check_breakpoints("altitude", &self.altitude)?;
+                self.altitude.len()
+            }
+        };
+
+        if self.gains.len() != rows {
+            return Err(anyhow!(
+                "expected {} rows of gains, found {}",
+                rows,
+                self.gains.len()
+            ));
+        }
+
+        if let Some(row) = self.gains.iter().find(|r| r.len() != self.airspeed.len()) {
+            return Err(anyhow!(
+                "expected {} gains per row (one per airspeed), found {}",
+                self.airspeed.len(),
+                row.len()
+            ));
+        }
+
+        if self.gains.iter().flatten().any(|g| !g.is_finite()) {
+            return Err(anyhow!("gains must be finite numbers"));
+        }
+
+        Ok(())
+    }
+
+    pub fn interpolate(&self, airspeed: f64, altitude: f64) -> f64 {
+        let per_row: Vec<f64> = self
+            .gains
+            .iter()
+            .map(|row| interpolate(&self.airspeed, row, airspeed))
+            .collect();
+
+        match self.altitude.is_empty() {
+            true => per_row[0],
+            false => interpolate(&self.altitude, &per_row, altitude),
+        }
+    }
+}
+
+fn check_breakpoints(name: &str, breakpoints: &[f64]) -> anyhow::Result<()> {
+    if breakpoints.is_empty() {
+        return Err(anyhow!("no {} breakpoints", name));
+    }
+
+    if breakpoints.windows(2).any(|w| w[0] >= w[1]) || breakpoints.iter().any(|b| !b.is_finite()) {
+        return Err(anyhow!("{} breakpoints must be strictly increasing", name));
+    }
+
+    Ok(())
+}
+
+// linear interpolation in a table, held constant outside of the breakpoints
+fn interpolate(breakpoints: &[f64], values: &[f64], x: f64) -> f64 {
+    let last: usize = breakpoints.len() - 1;
+
+    if x <= breakpoints[0] {
+        return values[0];
+    }
+    if x >= breakpoints[last] {
+        return values[last];
+    }
+
+    let i: usize = breakpoints.partition_point(|b| *b <= x) - 1;
+    let fraction: f64 = (x - breakpoints[i]) / (breakpoints[i + 1] - breakpoints[i]);
+
+    values[i] + fraction * (values[i + 1] - values[i])
+}
+
+// the constants with every scheduled gain replaced by its value at this airspeed and altitude,
+// together with the scheduled values so they can be reported
+pub(super) fn schedule_constants(
+    constants: &AutoPilotConstants,
+    airspeed: f64,
+    altitude: f64,
+) -> anyhow::Result<(AutoPilotConstants, BTreeMap<String, f64>)> {
+    if constants.gain_schedules.is_empty() {
+        return Ok((constants.clone(), BTreeMap::new()));
+    }
+
+    let mut scheduled_gains: BTreeMap<String, f64> = BTreeMap::new();
+    let mut json: Value = serde_json::to_value(constants)?;
+
+    for (name, schedule) in constants.gain_schedules.iter() {
+        schedule
+            .validate()
+            .map_err(|e| anyhow!("gain schedule for {}: {}", name, e))?;
+
+        // only plain numbers can be scheduled, so not the schedules themselves
+        match json.get_mut(name) {
+            Some(value) if value.is_number() => {
+                let gain: f64 = schedule.interpolate(airspeed, altitude);
+                *value = serde_json::json!(gain);
+                scheduled_gains.insert(name.clone(), gain);
+            }
+            _ => return Err(anyhow!("gain schedule for unknown gain {}", name)),
+        }
+    }
+
+    Ok((serde_json::from_value(json)?, scheduled_gains))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> GainSchedule {
+        GainSchedule {
+            airspeed: vec![60.0, 100.0, 160.0],
+            altitude: Vec::new(),
+            gains: vec![vec![0.009, 0.006, 0.003]],
+        }
+    }
+
+    #[test]
+    fn test_interpolation_in_airspeed() {
+        let schedule = schedule();
+        assert!(schedule.validate().is_ok());
+
+        assert!((schedule.interpolate(80.0, 0.0) - 0.0075).abs() < 1e-12);
+        assert!((schedule.interpolate(130.0, 0.0) - 0.0045).abs() < 1e-12);
+        assert_eq!(schedule.interpolate(100.0, 0.0), 0.006);
+
+        // held at the ends of the table
+        assert_eq!(schedule.interpolate(40.0, 0.0), 0.009);
+        assert_eq!(schedule.interpolate(200.0, 0.0), 0.003);
+    }
+
+    #[test]
+    fn test_interpolation_in_airspeed_and_altitude() {
+        let schedule = GainSchedule {
+            airspeed: vec![60.0, 160.0],
+            altitude: vec![0.0, 10_000.0],
+            gains: vec![vec![1.0, 2.0], vec![3.0, 4.0]],
+        };
+        assert!(schedule.validate().is_ok());
+
+        assert!((schedule.interpolate(110.0, 0.0) - 1.5).abs() < 1e-12);
+        assert!((schedule.interpolate(110.0, 5_000.0) - 2.5).abs() < 1e-12);
+        assert!((schedule.interpolate(60.0, 20_000.0) - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let mut unordered = schedule();
+        unordered.airspeed = vec![100.0, 60.0, 160.0];
+        assert!(unordered.validate().is_err());
+
+        let mut short_row = schedule();
+        short_row.gains = vec![vec![0.009, 0.006]];
+        assert!(short_row.validate().is_err());
+
+        // two altitudes need two rows of gains
+        let mut missing_row = schedule();
+        missing_row.altitude = vec![0.0, 5_000.0];
+        assert!(missing_row.validate().is_err());
+    }
+
+    #[test]
+    fn test_schedule_constants() {
+        let mut constants = AutoPilotConstants::new();
+        constants
+            .gain_schedules
+            .insert("roll_p".to_string(), schedule());
+
+        let (scheduled, gains) = schedule_constants(&constants, 80.0, 2_000.0).unwrap();
+        assert!((scheduled.roll_p - 0.0075).abs() < 1e-12);
+        assert_eq!(scheduled.roll_d, constants.roll_d);
+        assert_eq!(gains.len(), 1);
+
+        constants
+            .gain_schedules
+            .insert("not_a_gain".to_string(), schedule());
+        assert!(schedule_constants(&constants, 80.0, 2_000.0).is_err());
+    }
+}
@@ -0,0 +1,535 @@
+use serde::{Deserialize, Serialize};
+
+use crate::navigation::{
+    self, LateralNavigationInput, LateralNavigationSolution, Position, TurnDirection, Wind,
+};
+
+// a racetrack hold: the inbound leg ends at the fix, the turns go to the holding side
+
+// [min], when neither a leg time nor a leg distance is given
+const DEFAULT_LEG_TIME: f64 = 1.0;
+const MAX_LEG_TIME: f64 = 10.0;
+// [nm]
+const MAX_LEG_DISTANCE: f64 = 50.0;
+
+// a turn is complete when the track is this close to the course it turns to [deg]
+const ROLL_OUT: f64 = 10.0;
+
+// the turn back from a parallel entry ends at this angle to the inbound course, the leg is intercepted from there [deg]
+const PARALLEL_INTERCEPT: f64 = 45.0;
+
+// the teardrop leg is this far from the outbound course, towards the holding side [deg]
+const TEARDROP_ANGLE: f64 = 30.0;
+
+// the heading to the fix relative to the inbound course that is a direct entry, for a right hold [deg]
+const DIRECT_SECTOR: (f64, f64) = (-70.0, 110.0);
+
+// the inbound leg is flown on a line this long ending at the fix [nm]
+const LEG_LINE_LENGTH: f64 = 20.0;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum HoldTurn {
+    #[default]
+    Right,
+    Left,
+}
+
+impl HoldTurn {
+    fn turn_direction(&self) -> TurnDirection {
+        match self {
+            HoldTurn::Right => TurnDirection::Right,
+            HoldTurn::Left => TurnDirection::Left,
+        }
+    }
+
+    fn opposite(&self) -> TurnDirection {
+        match self {
+            HoldTurn::Right => TurnDirection::Left,
+            HoldTurn::Left => TurnDirection::Right,
+        }
+    }
+
+    // positive for a right hold, to turn angles towards the holding side
+    fn sign(&self) -> f64 {
+        match self {
+            HoldTurn::Right => 1.0,
+            HoldTurn::Left => -1.0,
+        }
+    }
+}
+
+// the hold as it is set up, e.g. {"fix": {"latitude": 52.3, "longitude": 4.76}, "inbound_course": 270,
+// "turn_direction": "Right", "leg_time": 1.5}
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HoldSetup {
+    pub fix: Position,
+    // true course [deg]
+    pub inbound_course: f64,
+    #[serde(default)]
+    pub turn_direction: HoldTurn,
+    // [min], the inbound leg is flown in this time
+    #[serde(default)]
+    pub leg_time: Option<f64>,
+    // [nm], instead of a leg time
+    #[serde(default)]
+    pub leg_distance: Option<f64>,
+}
+
+impl HoldSetup {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        if !(-90.0..=90.0).contains(&self.fix.latitude)
+            || !(-180.0..=180.0).contains(&self.fix.longitude)
+        {
+            errors.push(format!(
+                "the fix must be a latitude from -90 to 90 and a longitude from -180 to 180, is {}, {}",
+                self.fix.latitude, self.fix.longitude
+            ));
+        }
+
+        if !(0.0..=360.0).contains(&self.inbound_course) {
+            errors.push(format!("inbound_course must be from 0 to 360, is {}", self.inbound_course));
+        }
+
+        match (self.leg_time, self.leg_distance) {
+            (Some(_), Some(_)) => {
+                errors.push("give a leg_time or a leg_distance, not both".to_string())
+            }
+            (Some(time), None) if !(0.0 < time && time <= MAX_LEG_TIME) => errors.push(format!(
+                "leg_time must be more than 0 and at most {} minutes, is {}",
+                MAX_LEG_TIME, time
+            )),
+            (None, Some(distance)) if !(0.0 < distance && distance <= MAX_LEG_DISTANCE) => {
+                errors.push(format!(
+                    "leg_distance must be more than 0 and at most {} nm, is {}",
+                    MAX_LEG_DISTANCE, distance
+                ))
+            }
+            _ => {}
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("; ")),
+        }
+    }
+
+    fn outbound_course(&self) -> f64 {
+        navigation::wrap_360(self.inbound_course + 180.0)
+    }
+
+    // [s]
+    fn leg_time(&self) -> f64 {
+        self.leg_time.unwrap_or(DEFAULT_LEG_TIME) * 60.0
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum HoldEntry {
+    Direct,
+    Teardrop,
+    Parallel,
+}
+
+// the entry for the track the fix is reached on, with the 70 degree line on the holding side
+pub(super) fn hold_entry(inbound_course: f64, track: f64, turn: HoldTurn) -> HoldEntry {
+    let relative: f64 = turn.sign() * navigation::wrap_180(track - inbound_course);
+
+    if (DIRECT_SECTOR.0..=DIRECT_SECTOR.1).contains(&relative) {
+        HoldEntry::Direct
+    } else if relative > DIRECT_SECTOR.1 {
+        HoldEntry::Teardrop
+    } else {
+        HoldEntry::Parallel
+    }
+}
+
+// ToFix: direct to the fix, the entry is chosen when it is crossed
+// EntryLeg: the teardrop or parallel leg away from the fix
+// EntryTurn: the turn back from a parallel entry, against the holding direction
+// OutboundTurn, Outbound, InboundTurn, Inbound: the racetrack itself
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum HoldPhase {
+    #[default]
+    ToFix,
+    EntryLeg,
+    EntryTurn,
+    OutboundTurn,
+    Outbound,
+    InboundTurn,
+    Inbound,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct HoldInput {
+    pub position: Position,
+    // ground track, true [deg]
+    pub track: f64,
+    pub ground_speed: f64,
+    pub true_airspeed: f64,
+    pub wind: Wind,
+    pub max_roll: f64,
+    pub cross_track_gain: f64,
+    pub max_intercept_angle: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct HoldSolution {
+    pub desired_track: f64,
+    // forced in the turns of the hold, the legs are intercepted the shortest way
+    pub turn_direction: TurnDirection,
+    pub cross_track_error: f64,
+    pub distance_to_fix: f64,
+}
+
+// the hold being flown, shown in the state
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct HoldingPattern {
+    pub hold_setup: Option<HoldSetup>,
+    pub hold_entry: Option<HoldEntry>,
+    pub hold_phase: HoldPhase,
+    // [s] since the phase started
+    pub hold_phase_time: f64,
+    // [s] the outbound leg, corrected for the wind so the inbound leg takes the leg time
+    pub hold_outbound_time: f64,
+    // the start of the leg that is flown now, outside of the racetrack this is where the leg began
+    pub hold_leg_origin: Position,
+}
+
+impl HoldingPattern {
+    // the hold starts over from where we are, with the entry chosen at the fix
+    pub fn start(&mut self, position: Position) {
+        *self = HoldingPattern {
+            hold_setup: self.hold_setup,
+            hold_leg_origin: position,
+            ..HoldingPattern::default()
+        };
+    }
+
+    fn next_phase(&mut self, phase: HoldPhase, position: Position) {
+        self.hold_phase = phase;
+        self.hold_phase_time = 0.0;
+        self.hold_leg_origin = position;
+    }
+
+    // the track to fly, and the phase moved on when its end is reached. none without a hold set up
+    pub(super) fn update(&mut self, input: &HoldInput, dt: f64) -> Option<HoldSolution> {
+        let setup: HoldSetup = self.hold_setup?;
+        let fix: Position = setup.fix;
+        let outbound_course: f64 = setup.outbound_course();
+        let turn: HoldTurn = setup.turn_direction;
+
+        self.hold_phase_time += dt;
+        self.hold_outbound_time = outbound_time(&setup, input);
+
+        let lateral_input: LateralNavigationInput = LateralNavigationInput {
+            position: input.position,
+            ground_speed: input.ground_speed,
+            max_roll: input.max_roll,
+            cross_track_gain: input.cross_track_gain,
+            max_intercept_angle: input.max_intercept_angle,
+        };
+        let distance_to_fix: f64 = navigation::distance_nm(input.position, fix);
+
+        // a leg is flown like an LNAV leg, a turn with the track it turns to
+        let leg = |from: Position, to: Position| -> LateralNavigationSolution {
+            navigation::lateral_navigation(from, to, None, &lateral_input)
+        };
+        let turn_to = |course: f64, direction: TurnDirection| -> HoldSolution {
+            HoldSolution {
+                desired_track: navigation::wrap_360(course),
+                turn_direction: direction,
+                cross_track_error: 0.0,
+                distance_to_fix,
+            }
+        };
+        let turned_to =
+            |course: f64, margin: f64| navigation::wrap_180(input.track - course).abs() <= margin;
+        let leg_flown = |origin: Position, course: f64, time: f64| match setup.leg_distance {
+            Some(distance) => {
+                navigation::along_track_distance_nm(
+                    origin,
+                    navigation::destination_point(origin, course, LEG_LINE_LENGTH),
+                    input.position,
+                ) >= distance
+            }
+            None => self.hold_phase_time >= time,
+        };
+
+        let solution: HoldSolution = match self.hold_phase {
+            HoldPhase::ToFix => {
+                let solution = leg(self.hold_leg_origin, fix);
+                let entry: HoldEntry = hold_entry(setup.inbound_course, input.track, turn);
+                self.hold_entry = Some(entry);
+
+                if solution.sequence_waypoint {
+                    let phase: HoldPhase = match entry {
+                        HoldEntry::Direct => HoldPhase::OutboundTurn,
+                        HoldEntry::Teardrop | HoldEntry::Parallel => HoldPhase::EntryLeg,
+                    };
+                    self.next_phase(phase, input.position);
+                }
+
+                leg_solution(&solution, distance_to_fix)
+            }
+            HoldPhase::EntryLeg => {
+                let course: f64 = match self.hold_entry {
+                    Some(HoldEntry::Teardrop) => {
+                        navigation::wrap_360(outbound_course - turn.sign() * TEARDROP_ANGLE)
+                    }
+                    _ => outbound_course,
+                };
+                let solution = leg(
+                    self.hold_leg_origin,
+                    navigation::destination_point(self.hold_leg_origin, course, LEG_LINE_LENGTH),
+                );
+
+                if leg_flown(self.hold_leg_origin, course, setup.leg_time()) {
+                    let phase: HoldPhase = match self.hold_entry {
+                        Some(HoldEntry::Parallel) => HoldPhase::EntryTurn,
+                        _ => HoldPhase::InboundTurn,
+                    };
+                    self.next_phase(phase, input.position);
+                }
+
+                leg_solution(&solution, distance_to_fix)
+            }
+            HoldPhase::EntryTurn => {
+                if turned_to(setup.inbound_course, PARALLEL_INTERCEPT) {
+                    self.next_phase(HoldPhase::Inbound, input.position);
+                }
+
+                turn_to(setup.inbound_course, turn.opposite())
+            }
+            HoldPhase::OutboundTurn => {
+                if turned_to(outbound_course, ROLL_OUT) {
+                    self.next_phase(HoldPhase::Outbound, input.position);
+                }
+
+                turn_to(outbound_course, turn.turn_direction())
+            }
+            HoldPhase::Outbound => {
+                let solution = leg(
+                    self.hold_leg_origin,
+                    navigation::destination_point(
+                        self.hold_leg_origin,
+                        outbound_course,
+                        LEG_LINE_LENGTH,
+                    ),
+                );
+
+                if leg_flown(self.hold_leg_origin, outbound_course, self.hold_outbound_time) {
+                    self.next_phase(HoldPhase::InboundTurn, input.position);
+                }
+
+                leg_solution(&solution, distance_to_fix)
+            }
+            HoldPhase::InboundTurn => {
+                if turned_to(setup.inbound_course, ROLL_OUT) {
+                    self.next_phase(HoldPhase::Inbound, input.position);
+                }
+
+                turn_to(setup.inbound_course, turn.turn_direction())
+            }
+            HoldPhase::Inbound => {
+                let solution = leg(
+                    navigation::destination_point(fix, outbound_course, LEG_LINE_LENGTH),
+                    fix,
+                );
+
+                // every crossing of the fix starts the next round
+                if solution.sequence_waypoint {
+                    self.next_phase(HoldPhase::OutboundTurn, input.position);
+                }
+
+                leg_solution(&solution, distance_to_fix)
+            }
+        };
+
+        Some(solution)
+    }
+}
+
+fn leg_solution(solution: &LateralNavigationSolution, distance_to_fix: f64) -> HoldSolution {
+    HoldSolution {
+        desired_track: solution.desired_track,
+        turn_direction: TurnDirection::Shortest,
+        cross_track_error: solution.cross_track_error,
+        distance_to_fix,
+    }
+}
+
+// the outbound leg takes as long as the inbound leg should, scaled with the ground speeds on both legs [s]
+pub(super) fn outbound_time(setup: &HoldSetup, input: &HoldInput) -> f64 {
+    let inbound: f64 = input.wind.ground_speed(setup.inbound_course, input.true_airspeed);
+    let outbound: f64 = input.wind.ground_speed(setup.outbound_course(), input.true_airspeed);
+
+    if inbound <= 1.0 || outbound <= 1.0 {
+        return setup.leg_time();
+    }
+
+    setup.leg_time() * inbound / outbound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIX: Position = Position {
+        latitude: 52.0,
+        longitude: 5.0,
+    };
+
+    fn setup() -> HoldSetup {
+        HoldSetup {
+            fix: FIX,
+            inbound_course: 360.0,
+            turn_direction: HoldTurn::Right,
+            leg_time: None,
+            leg_distance: None,
+        }
+    }
+
+    fn input(position: Position, track: f64) -> HoldInput {
+        HoldInput {
+            position,
+            track,
+            ground_speed: 100.0,
+            true_airspeed: 100.0,
+            wind: Wind::default(),
+            max_roll: 25.0,
+            cross_track_gain: 30.0,
+            max_intercept_angle: 45.0,
+        }
+    }
+
+    #[test]
+    fn test_hold_setup() {
+        assert!(setup().validate().is_ok());
+
+        let both = HoldSetup {
+            leg_time: Some(1.0),
+            leg_distance: Some(4.0),
+            ..setup()
+        };
+        assert!(both.validate().is_err());
+
+        let bad = HoldSetup {
+            inbound_course: 400.0,
+            leg_time: Some(0.0),
+            ..setup()
+        };
+        let error: String = bad.validate().unwrap_err();
+        assert!(error.contains("inbound_course"));
+        assert!(error.contains("leg_time"));
+
+        let parsed: HoldSetup = serde_json::from_str(
+            r#"{"fix": {"latitude": 52.0, "longitude": 5.0}, "inbound_course": 360}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed, setup());
+    }
+
+    #[test]
+    fn test_hold_entries() {
+        // right hold with inbound course north, the holding side is east
+        assert_eq!(hold_entry(360.0, 0.0, HoldTurn::Right), HoldEntry::Direct);
+        assert_eq!(hold_entry(360.0, 90.0, HoldTurn::Right), HoldEntry::Direct);
+        assert_eq!(hold_entry(360.0, 290.0, HoldTurn::Right), HoldEntry::Direct);
+        assert_eq!(hold_entry(360.0, 150.0, HoldTurn::Right), HoldEntry::Teardrop);
+        assert_eq!(hold_entry(360.0, 250.0, HoldTurn::Right), HoldEntry::Parallel);
+
+        // a left hold is the mirror image
+        assert_eq!(hold_entry(360.0, 210.0, HoldTurn::Left), HoldEntry::Teardrop);
+        assert_eq!(hold_entry(360.0, 110.0, HoldTurn::Left), HoldEntry::Parallel);
+        assert_eq!(hold_entry(90.0, 20.0, HoldTurn::Left), HoldEntry::Direct);
+    }
+
+    #[test]
+    fn test_wind_corrected_outbound_time() {
+        // 20 kt wind from the north: 80 kt inbound and 120 kt outbound
+        let mut input = input(FIX, 0.0);
+        input.wind = Wind {
+            north: -20.0,
+            east: 0.0,
+        };
+
+        let time: f64 = outbound_time(&setup(), &input);
+        assert!((time - 40.0).abs() < 1e-6, "outbound time was {}", time);
+
+        assert!((outbound_time(&setup(), &self::input(FIX, 0.0)) - 60.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_direct_entry_racetrack() {
+        let mut hold = HoldingPattern {
+            hold_setup: Some(setup()),
+            ..HoldingPattern::default()
+        };
+        let south: Position = navigation::destination_point(FIX, 180.0, 5.0);
+        hold.start(south);
+
+        // flying north to the fix is a direct entry
+        let solution = hold.update(&input(south, 0.0), 0.1).unwrap();
+        assert!(navigation::wrap_180(solution.desired_track).abs() < 1.0);
+        assert_eq!(hold.hold_entry, Some(HoldEntry::Direct));
+
+        let over_the_fix: Position = navigation::destination_point(FIX, 0.0, 0.05);
+        hold.update(&input(over_the_fix, 0.0), 0.1);
+        assert_eq!(hold.hold_phase, HoldPhase::OutboundTurn);
+
+        // the turn to the outbound leg goes right
+        let solution = hold.update(&input(over_the_fix, 0.0), 0.1).unwrap();
+        assert_eq!(solution.turn_direction, TurnDirection::Right);
+        assert_eq!(solution.desired_track, 180.0);
+
+        let abeam: Position = navigation::destination_point(FIX, 90.0, 1.0);
+        hold.update(&input(abeam, 175.0), 0.1);
+        assert_eq!(hold.hold_phase, HoldPhase::Outbound);
+
+        // without wind the outbound leg takes the leg time
+        hold.update(&input(abeam, 180.0), 59.0);
+        assert_eq!(hold.hold_phase, HoldPhase::Outbound);
+        hold.update(&input(abeam, 180.0), 1.5);
+        assert_eq!(hold.hold_phase, HoldPhase::InboundTurn);
+
+        let solution = hold.update(&input(abeam, 270.0), 0.1).unwrap();
+        assert_eq!(solution.turn_direction, TurnDirection::Right);
+        hold.update(&input(abeam, 355.0), 0.1);
+        assert_eq!(hold.hold_phase, HoldPhase::Inbound);
+    }
+
+    #[test]
+    fn test_parallel_entry() {
+        let setup = HoldSetup {
+            leg_distance: Some(4.0),
+            ..setup()
+        };
+        let mut hold = HoldingPattern {
+            hold_setup: Some(setup),
+            ..HoldingPattern::default()
+        };
+        let north: Position = navigation::destination_point(FIX, 10.0, 3.0);
+        hold.start(north);
+
+        // over the fix on a south westerly track
+        let over_the_fix: Position = navigation::destination_point(FIX, 200.0, 0.05);
+        hold.update(&input(over_the_fix, 200.0), 0.1);
+        assert_eq!(hold.hold_entry, Some(HoldEntry::Parallel));
+        assert_eq!(hold.hold_phase, HoldPhase::EntryLeg);
+
+        // the leg ends after the leg distance
+        let solution = hold.update(&input(over_the_fix, 190.0), 0.1).unwrap();
+        assert!((solution.desired_track - 180.0).abs() < 10.0);
+        hold.update(&input(navigation::destination_point(over_the_fix, 180.0, 4.1), 180.0), 0.1);
+        assert_eq!(hold.hold_phase, HoldPhase::EntryTurn);
+
+        // and it turns back against the holding direction, onto the inbound leg
+        let solution = hold.update(&input(FIX, 180.0), 0.1).unwrap();
+        assert_eq!(solution.turn_direction, TurnDirection::Left);
+        hold.update(&input(FIX, 40.0), 0.1);
+        assert_eq!(hold.hold_phase, HoldPhase::Inbound);
+    }
+}
@@ -1,8 +1,15 @@
 use crate::AutoPilotHorizontalMetrics;
 
 use super::{
+    holding::{HoldInput, HoldSolution, HoldingPattern},
+    orbit::{self, OrbitInput, OrbitSolution},
+    navigation::{
+        self, HeadingReference, LateralNavigationInput, LateralNavigationSolution, Position, Wind,
+    },
+    modes,
+    pid::{Pid, PidGains, PidInput},
     send_command,
-    types::{CommandType, HorizontalModes},
+    types::{CommandType, HorizontalModes, PidLoop},
 };
 
 pub(super) async fn execute_horizontal_guidance(
@@ -12,75 +19,507 @@ pub(super) async fn execute_horizontal_guidance(
     auto_pilot_state: &super::types::AutoPilotState,
     plane_state_struct: &super::types::PlaneStateStruct,
 ) -> anyhow::Result<()> {
+    // the wind follows from the difference between where we point and where we go
+    let previous_metrics = &auto_pilot_state.horizontal_control_metrics;
+    let wind: Wind = Wind {
+        north: previous_metrics.wind_north,
+        east: previous_metrics.wind_east,
+    }
+    .filter(
+        Wind::estimate(
+            plane_state_struct.v_true,
+            plane_state_struct.heading,
+            plane_state_struct.v_ground,
+            plane_state_struct.hpath,
+        ),
+        dt,
+        auto_pilot_state.control_constants.wind_filter_time,
+    );
+
+    // the selected bank limit applies to every mode that turns
+    let max_roll: f64 = auto_pilot_state.horizontal_guidance.bank_limit.max_bank(
+        plane_state_struct.v_true,
+        auto_pilot_state.control_constants.max_roll,
+    );
+
+    // an armed LNAV takes over from the active mode when we are about to intercept the leg
+    if auto_pilot_state.horizontal_guidance.horizontal_mode_armed == Some(HorizontalModes::LNAV) {
+        let flight_plan = &auto_pilot_state.flight_plan;
+
+        if let Some(to) = flight_plan.active() {
+            let position: Position = Position {
+                latitude: plane_state_struct.latitude,
+                longitude: plane_state_struct.longitude,
+            };
+
+            // a leg without length is flown direct, so it is intercepted right away
+            let intercept: bool = navigation::distance_nm(flight_plan.leg_origin, to.position())
+                < 0.01
+                || modes::lnav_intercept(
+                    navigation::cross_track_distance_nm(
+                        flight_plan.leg_origin,
+                        to.position(),
+                        position,
+                    ),
+                    navigation::wrap_180(
+                        plane_state_struct.hpath
+                            - navigation::initial_bearing(flight_plan.leg_origin, to.position()),
+                    ),
+                    navigation::turn_radius_nm(plane_state_struct.v_ground, max_roll),
+                );
+
+            if intercept {
+                app_state_proxy.engage_armed_horizontal_mode().await?;
+            }
+        }
+    }
+
     match auto_pilot_state.horizontal_guidance.horizontal_mode {
         HorizontalModes::Standby => {
             //println!("Horizontal mode standby, no autopilot input for ailerons");
         }
         HorizontalModes::Heading => {
-            let kp: f64 = auto_pilot_state.control_constants.heading_error_p;
-            let kd: f64 = auto_pilot_state.control_constants.heading_roll_error_d;
+            let horizontal_guidance = &auto_pilot_state.horizontal_guidance;
+
+            // the setpoint is compared with the heading in the same reference
+            let heading: f64 = match horizontal_guidance.heading_reference {
+                HeadingReference::True => plane_state_struct.heading,
+                HeadingReference::Magnetic => plane_state_struct.heading_magnetic,
+            };
 
-            let heading_error: f64 =
-                auto_pilot_state.horizontal_guidance.heading_setpoint - plane_state_struct.heading;
-            
-            
-            let target_roll_angle: f64 = (kp * heading_error).clamp(-auto_pilot_state.control_constants.max_roll, auto_pilot_state.control_constants.max_roll);
+            let heading_error: f64 = navigation::heading_error(
+                horizontal_guidance.heading_setpoint,
+                heading,
+                horizontal_guidance.turn_direction,
+            );
 
-            let roll_error: f64 = target_roll_angle - plane_state_struct.roll;
-            let target_roll_rate: f64 = (kd * roll_error).clamp(-auto_pilot_state.control_constants.max_roll_rate, auto_pilot_state.control_constants.max_roll_rate);
-            let roll_rate_error: f64 = target_roll_rate - plane_state_struct.roll_rate;
+            let target_roll_angle: f64 = command_heading(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                heading_error,
+                max_roll,
+                0.0,
+            )
+            .await?;
 
-            let p: f64 = auto_pilot_state.control_constants.roll_p;
-            let d: f64 = auto_pilot_state.control_constants.roll_d;
-            let i: f64 = auto_pilot_state.control_constants.roll_i;
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_roll_angle,
+            )
+            .await?;
+
+            tracing::event!(tracing::Level::TRACE,
+                "Heading mode - heading [deg]: {:.4}, heading error [deg]: {:.4}, target_roll_angle [deg]: {:.4}, roll [deg]: {:.4}, roll_error: {:.4}, target roll rate [deg]: {:.4}, roll rate [deg/s]: {:.4}, roll_rate_error: {:.4}, aileron [0-1]: {:.4}",
+                heading, heading_error, target_roll_angle, plane_state_struct.roll, roll_command.roll_error, roll_command.target_roll_rate, plane_state_struct.roll_rate, roll_command.roll_rate_error, roll_command.aileron
+            );
+
+            let horizontal_metrics: AutoPilotHorizontalMetrics = AutoPilotHorizontalMetrics {
+                heading,
+                heading_target: horizontal_guidance.heading_setpoint,
+                heading_error,
+                roll_angle: plane_state_struct.roll,
+                roll_angle_target: target_roll_angle,
+                roll_angle_error: roll_command.roll_error,
+                roll_angle_rate: plane_state_struct.roll_rate,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                ..wind_metrics(&wind)
+            };
 
-            // heading error not used now
             app_state_proxy
-                .add_to_heading_error_integral(heading_error * dt)
+                .update_horizontal_control_metrics(horizontal_metrics)
                 .await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
+        }
+        HorizontalModes::Track => {
+            // the heading setpoint is used as the selected track, we crab into the wind to hold it
+            let horizontal_guidance = &auto_pilot_state.horizontal_guidance;
+
+            // the ground track is true, so a magnetic track setpoint is converted with the local variation
+            let variation: f64 = match horizontal_guidance.heading_reference {
+                HeadingReference::True => 0.0,
+                HeadingReference::Magnetic => navigation::wrap_180(
+                    plane_state_struct.heading - plane_state_struct.heading_magnetic,
+                ),
+            };
+
+            let track_setpoint: f64 =
+                navigation::wrap_360(horizontal_guidance.heading_setpoint + variation);
+            let crab_angle: f64 = wind.crab_angle(track_setpoint, plane_state_struct.v_true);
+            let heading_target: f64 = navigation::wrap_360(track_setpoint + crab_angle);
+
+            let heading_error: f64 = navigation::heading_error(
+                heading_target,
+                plane_state_struct.heading,
+                horizontal_guidance.turn_direction,
+            );
+
+            let target_roll_angle: f64 = command_heading(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                heading_error,
+                max_roll,
+                0.0,
+            )
+            .await?;
+
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_roll_angle,
+            )
+            .await?;
+
+            tracing::event!(tracing::Level::TRACE,
+                "Track mode - selected track [deg]: {:.4}, track [deg]: {:.4}, wind [deg/kt]: {:.0}/{:.1}, crab angle [deg]: {:.4}, heading target [deg]: {:.4}, heading error [deg]: {:.4}, target_roll_angle [deg]: {:.4}, aileron [0-1]: {:.4}",
+                track_setpoint, plane_state_struct.hpath, wind.direction(), wind.speed(), crab_angle, heading_target, heading_error, target_roll_angle, roll_command.aileron
+            );
+
+            let horizontal_metrics: AutoPilotHorizontalMetrics = AutoPilotHorizontalMetrics {
+                heading: plane_state_struct.heading,
+                heading_target,
+                heading_error,
+                roll_angle: plane_state_struct.roll,
+                roll_angle_target: target_roll_angle,
+                roll_angle_error: roll_command.roll_error,
+                roll_angle_rate: plane_state_struct.roll_rate,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                desired_track: track_setpoint,
+                track_angle_error: navigation::wrap_180(track_setpoint - plane_state_struct.hpath),
+                crab_angle,
+                ..wind_metrics(&wind)
+            };
 
             app_state_proxy
-                .add_to_roll_error_integral(roll_error * dt)
+                .update_horizontal_control_metrics(horizontal_metrics)
                 .await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
+        }
+        HorizontalModes::LNAV => {
+            let flight_plan = &auto_pilot_state.flight_plan;
+            let active_waypoint: usize = flight_plan.active_waypoint;
+
+            let Some(to) = flight_plan.active() else {
+                // nothing left to fly, the app state will revert to heading mode
+                return Ok(());
+            };
 
-            let aileron: f64 = (roll_error * p
-                + roll_rate_error * d
-                + auto_pilot_state.horizontal_guidance.roll_error_integral * i)
-                .clamp(-auto_pilot_state.control_constants.max_aileron, auto_pilot_state.control_constants.max_aileron);
+            let next: Option<Position> = flight_plan
+                .waypoints
+                .get(active_waypoint + 1)
+                .map(|w| w.position());
+
+            let solution: LateralNavigationSolution = navigation::lateral_navigation(
+                flight_plan.leg_origin,
+                to.position(),
+                next,
+                &LateralNavigationInput {
+                    position: Position {
+                        latitude: plane_state_struct.latitude,
+                        longitude: plane_state_struct.longitude,
+                    },
+                    ground_speed: plane_state_struct.v_ground,
+                    max_roll,
+                    cross_track_gain: auto_pilot_state.control_constants.lnav_cross_track_p,
+                    max_intercept_angle: auto_pilot_state
+                        .control_constants
+                        .lnav_max_intercept_angle,
+                },
+            );
+
+            // steer on ground track, so the intercept is flown over the ground regardless of wind
+            let track_angle_error: f64 =
+                navigation::wrap_180(solution.desired_track - plane_state_struct.hpath);
+
+            let target_roll_angle: f64 = command_heading(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                track_angle_error,
+                max_roll,
+                0.0,
+            )
+            .await?;
+
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_roll_angle,
+            )
+            .await?;
 
             tracing::event!(tracing::Level::TRACE,
-                "Heading mode - heading [deg]: {:.4}, heading error [deg]: {:.4}, target_roll_angle [deg]: {:.4}, roll [deg]: {:.4}, roll_error: {:.4}, target roll rate [deg]: {:.4}, roll rate [deg/s]: {:.4}, roll_rate_error: {:.4}, aileron [0-1]: {:.4}",
-                plane_state_struct.heading, heading_error, target_roll_angle, plane_state_struct.roll, roll_error, target_roll_rate, plane_state_struct.roll_rate, roll_rate_error, aileron
+                "LNAV mode - to: {}, distance to go [nm]: {:.4}, turn anticipation [nm]: {:.4}, xte [nm]: {:.4}, desired track [deg]: {:.4}, track [deg]: {:.4}, track angle error [deg]: {:.4}, target_roll_angle [deg]: {:.4}, aileron [0-1]: {:.4}",
+                to.name, solution.distance_to_go, solution.turn_anticipation_distance, solution.cross_track_error, solution.desired_track, plane_state_struct.hpath, track_angle_error, target_roll_angle, roll_command.aileron
             );
 
             let horizontal_metrics: AutoPilotHorizontalMetrics = AutoPilotHorizontalMetrics {
                 heading: plane_state_struct.heading,
-                heading_target: auto_pilot_state.horizontal_guidance.heading_setpoint,
-                heading_error,
                 roll_angle: plane_state_struct.roll,
                 roll_angle_target: target_roll_angle,
-                roll_angle_error: roll_error,
+                roll_angle_error: roll_command.roll_error,
                 roll_angle_rate: plane_state_struct.roll_rate,
-                roll_angle_rate_target: target_roll_rate,
-                roll_angle_rate_error: roll_rate_error,
-                aileron_setpoint: aileron,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                desired_track: solution.desired_track,
+                track_angle_error,
+                cross_track_error: solution.cross_track_error,
+                distance_to_go: solution.distance_to_go,
+                active_leg: active_waypoint,
+                ..wind_metrics(&wind)
             };
 
             app_state_proxy
                 .update_horizontal_control_metrics(horizontal_metrics)
                 .await?;
-            send_command(app_state_proxy, client, CommandType::Aileron, aileron).await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
+
+            if solution.sequence_waypoint {
+                tracing::event!(
+                    tracing::Level::INFO,
+                    "LNAV sequenced waypoint {} ({})",
+                    active_waypoint,
+                    to.name
+                );
+                app_state_proxy.sequence_waypoint().await?;
+            }
         }
-        HorizontalModes::WingsLevel => {
-            let p: f64 = auto_pilot_state.control_constants.roll_p;
-            let d: f64 = auto_pilot_state.control_constants.roll_d;
+        HorizontalModes::Hold => {
+            let mut holding_pattern: HoldingPattern = auto_pilot_state.holding_pattern.clone();
+
+            let Some(solution): Option<HoldSolution> = holding_pattern.update(
+                &HoldInput {
+                    position: Position {
+                        latitude: plane_state_struct.latitude,
+                        longitude: plane_state_struct.longitude,
+                    },
+                    track: plane_state_struct.hpath,
+                    ground_speed: plane_state_struct.v_ground,
+                    true_airspeed: plane_state_struct.v_true,
+                    wind,
+                    max_roll,
+                    cross_track_gain: auto_pilot_state.control_constants.lnav_cross_track_p,
+                    max_intercept_angle: auto_pilot_state
+                        .control_constants
+                        .lnav_max_intercept_angle,
+                },
+                dt,
+            ) else {
+                // the hold can only be engaged when it is set up
+                return Ok(());
+            };
+
+            // steered on ground track like LNAV, the turns of the hold go the way of the hold
+            let track_angle_error: f64 = navigation::heading_error(
+                solution.desired_track,
+                plane_state_struct.hpath,
+                solution.turn_direction,
+            );
+
+            let target_roll_angle: f64 = command_heading(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                track_angle_error,
+                max_roll,
+                0.0,
+            )
+            .await?;
+
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_roll_angle,
+            )
+            .await?;
+
+            tracing::event!(tracing::Level::TRACE,
+                "Hold mode - phase: {:?}, entry: {:?}, phase time [s]: {:.1}, outbound time [s]: {:.1}, distance to fix [nm]: {:.4}, xte [nm]: {:.4}, desired track [deg]: {:.4}, track [deg]: {:.4}, track angle error [deg]: {:.4}, target_roll_angle [deg]: {:.4}, aileron [0-1]: {:.4}",
+                holding_pattern.hold_phase, holding_pattern.hold_entry, holding_pattern.hold_phase_time, holding_pattern.hold_outbound_time, solution.distance_to_fix, solution.cross_track_error, solution.desired_track, plane_state_struct.hpath, track_angle_error, target_roll_angle, roll_command.aileron
+            );
+
+            let horizontal_metrics: AutoPilotHorizontalMetrics = AutoPilotHorizontalMetrics {
+                heading: plane_state_struct.heading,
+                roll_angle: plane_state_struct.roll,
+                roll_angle_target: target_roll_angle,
+                roll_angle_error: roll_command.roll_error,
+                roll_angle_rate: plane_state_struct.roll_rate,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                desired_track: solution.desired_track,
+                track_angle_error,
+                cross_track_error: solution.cross_track_error,
+                distance_to_go: solution.distance_to_fix,
+                ..wind_metrics(&wind)
+            };
+
+            if holding_pattern.hold_phase != auto_pilot_state.holding_pattern.hold_phase {
+                tracing::event!(
+                    tracing::Level::INFO,
+                    "Hold phase {:?} -> {:?} ({:?} entry)",
+                    auto_pilot_state.holding_pattern.hold_phase,
+                    holding_pattern.hold_phase,
+                    holding_pattern.hold_entry
+                );
+            }
+
+            app_state_proxy
+                .update_holding_pattern(holding_pattern)
+                .await?;
+            app_state_proxy
+                .update_horizontal_control_metrics(horizontal_metrics)
+                .await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
+        }
+        HorizontalModes::Orbit => {
+            let Some(orbit_setup) = auto_pilot_state.horizontal_guidance.orbit_setup else {
+                // the orbit can only be engaged when it is set up
+                return Ok(());
+            };
+
+            let solution: OrbitSolution = orbit::orbit_guidance(
+                &orbit_setup,
+                &OrbitInput {
+                    position: Position {
+                        latitude: plane_state_struct.latitude,
+                        longitude: plane_state_struct.longitude,
+                    },
+                    ground_speed: plane_state_struct.v_ground,
+                    max_roll,
+                    convergence: auto_pilot_state.control_constants.orbit_convergence,
+                },
+            );
+
+            // steered on ground track, so the circle is flown over the ground in the wind
+            let track_angle_error: f64 =
+                navigation::wrap_180(solution.desired_track - plane_state_struct.hpath);
+
+            let target_roll_angle: f64 = command_heading(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                track_angle_error,
+                max_roll,
+                solution.roll_feedforward,
+            )
+            .await?;
+
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_roll_angle,
+            )
+            .await?;
+
+            tracing::event!(tracing::Level::TRACE,
+                "Orbit mode - distance to center [nm]: {:.4}, radial error [nm]: {:.4}, minimum radius [nm]: {:.4}, desired track [deg]: {:.4}, track [deg]: {:.4}, track angle error [deg]: {:.4}, roll feedforward [deg]: {:.4}, target_roll_angle [deg]: {:.4}, aileron [0-1]: {:.4}",
+                solution.distance_to_center, solution.radial_error, solution.minimum_radius, solution.desired_track, plane_state_struct.hpath, track_angle_error, solution.roll_feedforward, target_roll_angle, roll_command.aileron
+            );
+
+            let horizontal_metrics: AutoPilotHorizontalMetrics = AutoPilotHorizontalMetrics {
+                heading: plane_state_struct.heading,
+                roll_angle: plane_state_struct.roll,
+                roll_angle_target: target_roll_angle,
+                roll_angle_error: roll_command.roll_error,
+                roll_angle_rate: plane_state_struct.roll_rate,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                desired_track: solution.desired_track,
+                track_angle_error,
+                roll_angle_feedforward: solution.roll_feedforward,
+                orbit_radial_error: solution.radial_error,
+                orbit_distance_to_center: solution.distance_to_center,
+                orbit_minimum_radius: solution.minimum_radius,
+                ..wind_metrics(&wind)
+            };
 
-            let aileron: f64 = (-(plane_state_struct.roll * p + plane_state_struct.roll_rate * d))
-                .clamp(-auto_pilot_state.control_constants.max_aileron, auto_pilot_state.control_constants.max_aileron);
+            app_state_proxy
+                .update_horizontal_control_metrics(horizontal_metrics)
+                .await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
+        }
+        HorizontalModes::WingsLevel => {
+            let roll_command: RollCommand = command_roll_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                0.0,
+            )
+            .await?;
 
             tracing::event!(tracing::Level::TRACE,
                 "Wings level mode - roll [deg]: {:.4}, roll_rate [deg/s]: {:.4}, aileron [0-1]: {:.4}",
-                plane_state_struct.roll, plane_state_struct.roll_rate, aileron
+                plane_state_struct.roll, plane_state_struct.roll_rate, roll_command.aileron
             );
 
             let horizontal_metrics = AutoPilotHorizontalMetrics {
@@ -89,17 +528,132 @@ pub(super) async fn execute_horizontal_guidance(
                 heading_error: 0.,
                 roll_angle: plane_state_struct.roll,
                 roll_angle_target: 0.,
-                roll_angle_error: plane_state_struct.roll,
+                roll_angle_error: roll_command.roll_error,
                 roll_angle_rate: plane_state_struct.roll_rate,
-                roll_angle_rate_target: 0.,
-                roll_angle_rate_error: plane_state_struct.roll_rate,
-                aileron_setpoint: aileron,
+                roll_angle_rate_target: roll_command.target_roll_rate,
+                roll_angle_rate_error: roll_command.roll_rate_error,
+                aileron_setpoint: roll_command.aileron,
+                roll_angle_limit: max_roll,
+                track: plane_state_struct.hpath,
+                ..wind_metrics(&wind)
             };
-            
+
             app_state_proxy.update_horizontal_control_metrics(horizontal_metrics).await?;
-            send_command(app_state_proxy, client, CommandType::Aileron, aileron).await?;
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Aileron,
+                roll_command.aileron,
+            )
+            .await?;
         }
     }
 
     Ok(())
 }
+
+// estimated wind for the metrics, so the estimate is also shown (and filtered) in the modes that do not use it
+fn wind_metrics(wind: &Wind) -> AutoPilotHorizontalMetrics {
+    AutoPilotHorizontalMetrics {
+        wind_north: wind.north,
+        wind_east: wind.east,
+        wind_direction: wind.direction(),
+        wind_speed: wind.speed(),
+        ..Default::default()
+    }
+}
+
+// outer loop shared by the lateral modes: the roll angle that turns away a heading (or track) error,
+// on top of the roll angle a mode knows it needs (e.g. the bank of an orbit)
+async fn command_heading(
+    dt: f64,
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+    heading_error: f64,
+    max_roll: f64,
+    roll_feedforward: f64,
+) -> anyhow::Result<f64> {
+    let constants = &auto_pilot_state.control_constants;
+
+    let gains: PidGains = PidGains {
+        kp: constants.heading_error_p,
+        ki: constants.heading_error_i,
+        output_min: -max_roll,
+        output_max: max_roll,
+        ..Default::default()
+    };
+
+    let mut heading_pid: Pid = auto_pilot_state.horizontal_guidance.heading_pid;
+    let target_roll_angle: f64 = heading_pid.update(
+        &gains,
+        &PidInput {
+            error: heading_error,
+            measurement: plane_state_struct.heading,
+            measurement_rate: Some(plane_state_struct.yaw_rate),
+            feedforward: roll_feedforward,
+        },
+        dt,
+    );
+
+    app_state_proxy.update_pid(PidLoop::Heading, heading_pid).await?;
+
+    Ok(target_roll_angle)
+}
+
+struct RollCommand {
+    roll_error: f64,
+    target_roll_rate: f64,
+    roll_rate_error: f64,
+    aileron: f64,
+}
+
+// inner loop shared by the lateral modes: fly a target roll angle with the ailerons
+async fn command_roll_angle(
+    dt: f64,
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+    target_roll_angle: f64,
+) -> anyhow::Result<RollCommand> {
+    let kd: f64 = auto_pilot_state.control_constants.heading_roll_error_d;
+
+    let roll_error: f64 = target_roll_angle - plane_state_struct.roll;
+    let target_roll_rate: f64 = (kd * roll_error).clamp(-auto_pilot_state.control_constants.max_roll_rate, auto_pilot_state.control_constants.max_roll_rate);
+    let roll_rate_error: f64 = target_roll_rate - plane_state_struct.roll_rate;
+
+    let constants = &auto_pilot_state.control_constants;
+
+    let gains: PidGains = PidGains {
+        kp: constants.roll_p,
+        ki: constants.roll_i,
+        kd: constants.roll_d,
+        output_min: -constants.max_aileron,
+        output_max: constants.max_aileron,
+        derivative_filter_time: constants.derivative_filter_time,
+        ..Default::default()
+    };
+
+    // the damping acts on the roll rate error, so the target roll rate is fed forward
+    let mut roll_pid: Pid = auto_pilot_state.horizontal_guidance.roll_pid;
+    let aileron: f64 = roll_pid.update(
+        &gains,
+        &PidInput {
+            error: roll_error,
+            measurement: plane_state_struct.roll,
+            measurement_rate: Some(plane_state_struct.roll_rate),
+            feedforward: constants.roll_d * target_roll_rate,
+        },
+        dt,
+    );
+
+    app_state_proxy.update_pid(PidLoop::Roll, roll_pid).await?;
+
+    Ok(RollCommand {
+        roll_error,
+        target_roll_rate,
+        roll_rate_error,
+        aileron,
+    })
+}
@@ -1,33 +1,104 @@
 use axum::{
-    extract::{Path, State},
-    http::{Method, StatusCode},
-    routing::get,
+    extract::{rejection::JsonRejection, rejection::PathRejection, FromRequest, FromRequestParts},
+    extract::{Query, Request, State},
+    handler::Handler,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
 
+use futures::stream::Stream;
+use serde::Deserialize;
 use std::net::SocketAddr;
 use tokio::net::TcpSocket;
 
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{event, Level};
 
-use super::{types::AppStateProxy, utils};
+use super::{
+    api::{self, ModeRequest, Settings, StandbyValues},
+    autothrottle::ThrustLimit,
+    constantswatcher::{self, ConstantsVersion},
+    flightplan::{self, AltitudeConstraint, FlightPlanFormat, Waypoint},
+    holding::HoldSetup,
+    orbit::OrbitSetup,
+    navigation::{BankLimit, HeadingReference, TurnDirection},
+    profiles::{self, AircraftProfile},
+    statepush::{self, StreamParams},
+    types::{AppStateProxy, AutoPilotConstants, HorizontalModes, TecsPriority, VerticalModes},
+    utils,
+};
 
 // define the routes and attach the state proxy, and serve the server
 pub(super) async fn run_server(app_state_proxy: AppStateProxy) {
     let cors = CorsLayer::new()
-        // allow `GET` and `POST` when accessing the resource
-        .allow_methods([Method::GET, Method::POST])
+        // allow `GET`, `POST`, `PUT`, `PATCH` and `DELETE` when accessing the resource
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers(Any)
         // allow requests from any origin
         .allow_origin(Any);
 
+    // the routes from before the api took request bodies, they still work but say they are deprecated
+    let deprecated_routes: Router<AppStateProxy> = Router::new()
+        .route("/api/v1/activate/{direction}/{mode}", get(activate_mode))
+        .route("/api/v1/arm/{direction}/{mode}", get(arm_mode))
+        .route("/api/v1/disarm", get(disarm_modes))
+        .route("/api/v1/set/{key}/{value}", get(set_key))
+        .route("/api/v1/switch/{key}", get(switch_key))
+        .route_layer(middleware::from_fn(deprecated));
+
     // build our application with the routes
     let app: Router = Router::new()
         .route("/", get(root))
+        .route("/api/v1/openapi.json", get(get_openapi))
         .route("/api/v1/autopilot_state", get(get_autopilot_state))
-        .route("/api/v1/activate/{direction}/{mode}", get(activate_mode))
-        .route("/api/v1/set/{key}/{value}", get(set_key))
-        .route("/api/v1/switch/{key}", get(switch_key))
+        .route("/api/v1/autopilot_state/stream", get(stream_autopilot_state))
+        .route("/api/v1/standby", put(put_standby))
+        .route("/api/v1/standby/{key}/activate", post(activate_standby))
+        .route("/api/v1/modes/activate", post(post_activate_mode))
+        .route("/api/v1/modes/arm", post(post_arm_mode))
+        .route("/api/v1/modes/disarm", post(disarm_modes))
+        .route("/api/v1/settings", put(put_settings))
+        .route(
+            "/api/v1/disconnect/acknowledge",
+            post(acknowledge_disconnect)
+                .get(acknowledge_disconnect.layer(middleware::from_fn(deprecated))),
+        )
+        .route(
+            "/api/v1/flightplan",
+            get(get_flight_plan)
+                .post(upload_flight_plan)
+                .delete(clear_flight_plan),
+        )
+        .route(
+            "/api/v1/flightplan/waypoints/{index}",
+            post(insert_waypoint).delete(delete_waypoint),
+        )
+        .route(
+            "/api/v1/flightplan/waypoints/{index}/constraints",
+            put(set_waypoint_constraints),
+        )
+        .route("/api/v1/flightplan/direct/{index}", post(direct_to_waypoint))
+        .route("/api/v1/hold", get(get_hold).put(put_hold))
+        .route("/api/v1/orbit", get(get_orbit).put(put_orbit))
+        .route(
+            "/api/v1/flight_director",
+            get(get_flight_director).put(put_flight_director),
+        )
+        .route("/api/v1/constants", get(get_constants).patch(patch_constants))
+        .route("/api/v1/constants/save", post(save_constants))
+        .route("/api/v1/constants/history", get(get_constants_history))
+        .route("/api/v1/constants/rollback/{version}", post(rollback_constants))
+        .route("/api/v1/profiles", get(get_profiles))
+        .route("/api/v1/profiles/deselect", post(deselect_profile))
+        .route("/api/v1/profiles/auto_select/{enabled}", post(set_profile_auto_select))
+        .route("/api/v1/profiles/{name}", get(get_profile))
+        .route("/api/v1/profiles/{name}/select", post(select_profile))
+        .merge(deprecated_routes)
+        .fallback(not_found)
         .layer(utils::return_trace_layer())
         .layer(cors)
         .with_state(app_state_proxy);
@@ -62,11 +133,81 @@ pub(super) async fn run_server(app_state_proxy: AppStateProxy) {
         .expect("Error serving app. Exiting.");
 }
 
+// every error is answered with the same payload
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = serde_json::json!({
+        "status": "error",
+        "message": message,
+    });
+    (status, Json(error_response))
+}
+
+// a request body or path that cannot be read, answered like every other error
+struct Rejection(StatusCode, String);
+
+impl From<JsonRejection> for Rejection {
+    fn from(rejection: JsonRejection) -> Self {
+        Rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for Rejection {
+    fn from(rejection: PathRejection) -> Self {
+        Rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        error_response(self.0, self.1).into_response()
+    }
+}
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(Rejection))]
+struct Body<T>(T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(Rejection))]
+struct Path<T>(T);
+
+async fn not_found(request: Request) -> (StatusCode, Json<serde_json::Value>) {
+    error_response(
+        StatusCode::NOT_FOUND,
+        format!("There is no {} {}", request.method(), request.uri().path()),
+    )
+}
+
+// marks the response of a deprecated route, and points to the api document for the new one
+async fn deprecated(request: Request, next: Next) -> Response {
+    event!(
+        Level::WARN,
+        "Deprecated route used: {} {}",
+        request.method(),
+        request.uri().path()
+    );
+
+    let mut response: Response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert("deprecation", HeaderValue::from_static("true"));
+    response.headers_mut().insert(
+        header::LINK,
+        HeaderValue::from_static("</api/v1/openapi.json>; rel=\"deprecation\""),
+    );
+
+    response
+}
+
 // basic handler that responds with a static string - can be used as a heart beat
 async fn root() -> &'static str {
     "Hello, World!"
 }
 
+async fn get_openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], api::OPENAPI)
+}
+
 // get the current autopilot state from the app and serve as a JSON
 async fn get_autopilot_state(
     State(app_state_proxy): State<AppStateProxy>,
@@ -79,7 +220,97 @@ async fn get_autopilot_state(
     Ok(Json(state))
 }
 
-async fn switch_key(
+// the autopilot state as server sent events, pushed when it changes. with diff=true only the values
+// that changed are sent after the first state
+async fn stream_autopilot_state(
+    State(app_state_proxy): State<AppStateProxy>,
+    Query(params): Query<StreamParams>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    event!(Level::DEBUG, "State stream subscribed: {:?}", params);
+
+    Sse::new(statepush::stream_state(app_state_proxy, params)).keep_alive(KeepAlive::default())
+}
+
+// the standby values, e.g. {"heading": 95.5, "vertical_speed": -500}. they are all checked before
+// any is set
+async fn put_standby(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<StandbyValues>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    set_standby(&app_state_proxy, payload).await
+}
+
+async fn set_key(
+    Path((key, value)): Path<(String, f64)>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let values: StandbyValues = StandbyValues::from_key(&key, value)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+
+    set_standby(&app_state_proxy, values).await
+}
+
+async fn set_standby(
+    app_state_proxy: &AppStateProxy,
+    values: StandbyValues,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    // the speed is limited by the vne of the active profile
+    let speed_max: f64 = match app_state_proxy.get_auto_pilot_state().await {
+        Ok(auto_pilot_state) => auto_pilot_state.control_constants.envelope_speed_max,
+        Err(e) => {
+            return Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set the standby values: {}", e),
+            ));
+        }
+    };
+
+    values.validate(speed_max).map_err(|e| {
+        event!(Level::ERROR, "Standby values not set: {}", e);
+        error_response(StatusCode::BAD_REQUEST, format!("Standby values not set: {}", e))
+    })?;
+
+    let mut res: anyhow::Result<()> = Ok(());
+
+    if let Some(heading) = values.heading {
+        res = res.and(app_state_proxy.set_heading_standby(heading).await);
+    }
+    if let Some(altitude) = values.altitude {
+        res = res.and(app_state_proxy.set_altitude_standby(altitude).await);
+    }
+    if let Some(velocity) = values.velocity {
+        res = res.and(app_state_proxy.set_velocity_standby(velocity).await);
+    }
+    // vertical speed in fpm, negative for a descent
+    if let Some(vertical_speed) = values.vertical_speed {
+        res = res.and(app_state_proxy.set_vertical_speed_standby(vertical_speed).await);
+    }
+    // FLCH thrust as a fraction of full throttle [0-1]
+    if values.flch_climb_throttle.is_some() || values.flch_descent_throttle.is_some() {
+        res = res.and(
+            app_state_proxy
+                .set_flch_throttle(values.flch_climb_throttle, values.flch_descent_throttle)
+                .await,
+        );
+    }
+
+    match res {
+        Ok(_) => {
+            event!(Level::INFO, "Standby values set ({:?})", values);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set value: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set the standby values: {}", e),
+            ))
+        }
+    }
+}
+
+// makes a standby value the setpoint
+async fn activate_standby(
     Path(key): Path<String>,
     State(app_state_proxy): State<AppStateProxy>,
 ) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
@@ -87,8 +318,12 @@ async fn switch_key(
         "heading" => app_state_proxy.activate_heading_setpoint().await,
         "altitude" => app_state_proxy.activate_altitude_setpoint().await,
         "velocity" => app_state_proxy.activate_velocity_setpoint().await,
+        "vertical_speed" => app_state_proxy.activate_vertical_speed_setpoint().await,
         _ => {
-            return Ok(StatusCode::BAD_REQUEST);
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("There is no standby value named {}", key),
+            ));
         }
     };
 
@@ -99,48 +334,130 @@ async fn switch_key(
         }
         Err(e) => {
             event!(Level::ERROR, "Cannot activate value for: {:?}", e);
-            Ok(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot activate value for {}: {}", key, e),
+            ))
         }
     }
 }
 
-async fn set_key(
-    Path((key, value)): Path<(String, usize)>,
+async fn switch_key(
+    path: Path<String>,
+    state: State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    activate_standby(path, state).await
+}
+
+// armed modes engage by themselves, LNAV when the leg is intercepted and VNAV together with LNAV
+async fn post_arm_mode(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<ModeRequest>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    arm(&app_state_proxy, &payload.channel, &payload.mode).await
+}
+
+async fn arm_mode(
+    Path((direction, mode)): Path<(String, String)>,
     State(app_state_proxy): State<AppStateProxy>,
 ) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let res = match (key.as_str(), value) {
-        ("heading", _) => app_state_proxy.set_heading_standby(value as f64).await,
-        ("altitude", _) => app_state_proxy.set_altitude_standby(value as f64).await,
-        ("velocity", _) => app_state_proxy.set_velocity_standby(value as f64).await,
+    arm(&app_state_proxy, &direction, &mode).await
+}
+
+async fn arm(
+    app_state_proxy: &AppStateProxy,
+    direction: &str,
+    mode: &str,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let res = match (direction, mode) {
+        ("horizontal", "lnav") => app_state_proxy.arm_horizontal_mode(HorizontalModes::LNAV).await,
+        ("vertical", "vnav") => app_state_proxy.arm_vertical_mode(VerticalModes::VNAV).await,
         (_, _) => {
-            return Ok(StatusCode::BAD_REQUEST);
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("There is no {} mode {} to arm", direction, mode),
+            ));
         }
     };
 
     match res {
         Ok(_) => {
-            event!(Level::INFO, "Standby value set ({}, {})", key, value);
+            event!(Level::INFO, "Autopilot mode armed ({}, {})", direction, mode);
             Ok(StatusCode::OK)
         }
         Err(e) => {
-            event!(Level::ERROR, "Cannot set value: {:?}", e);
-            Ok(StatusCode::INTERNAL_SERVER_ERROR)
+            event!(Level::ERROR, "Cannot arm mode: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot arm mode: {}", e),
+            ))
         }
     }
 }
 
-async fn activate_mode(
-    Path((direction, mode)): Path<(String, String)>,
+async fn disarm_modes(
     State(app_state_proxy): State<AppStateProxy>,
 ) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let res = match (direction.as_str(), mode.as_str()) {
+    match app_state_proxy.disarm_modes().await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot disarm modes: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot disarm modes: {}", e),
+            ))
+        }
+    }
+}
+
+// clears the alert of an automatic disconnect
+async fn acknowledge_disconnect(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.acknowledge_disconnect().await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot acknowledge disconnect: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot acknowledge disconnect: {}", e),
+            ))
+        }
+    }
+}
+
+async fn post_activate_mode(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<ModeRequest>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    activate(&app_state_proxy, &payload.channel, &payload.mode).await
+}
+
+async fn activate(
+    app_state_proxy: &AppStateProxy,
+    direction: &str,
+    mode: &str,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let res = match (direction, mode) {
         ("horizontal", "standby") => app_state_proxy.activate_horizontal_standby_mode().await,
         ("horizontal", "wingslevel") => app_state_proxy.activate_horizontal_wingslevel_mode().await,
         ("horizontal", "heading") => app_state_proxy.activate_horizontal_heading_mode().await,
+        ("horizontal", "track") => app_state_proxy.activate_horizontal_track_mode().await,
+        ("horizontal", "lnav") => app_state_proxy.activate_horizontal_LNAV_mode().await,
+        ("horizontal", "hold") => app_state_proxy.activate_horizontal_hold_mode().await,
+        ("horizontal", "orbit") => app_state_proxy.activate_horizontal_orbit_mode().await,
         ("vertical", "standby") => app_state_proxy.activate_vertical_standby_mode().await,
         ("vertical", "tecs") => app_state_proxy.activate_vertical_TECS_mode().await,
+        ("vertical", "vnav") => app_state_proxy.activate_vertical_VNAV_mode().await,
+        ("vertical", "vs") => app_state_proxy.activate_vertical_VS_mode().await,
+        ("vertical", "flch") => app_state_proxy.activate_vertical_FLCH_mode().await,
+        ("autothrottle", "standby") => app_state_proxy.activate_autothrottle_standby_mode().await,
+        ("autothrottle", "speed") => app_state_proxy.activate_autothrottle_speed_mode().await,
         (_, _) => {
-            return Ok(StatusCode::BAD_REQUEST);
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("There is no {} mode {}", direction, mode),
+            ));
         }
     };
 
@@ -156,7 +473,661 @@ async fn activate_mode(
         }
         Err(e) => {
             event!(Level::ERROR, "Cannot set autopilot mode: {:?}", e);
-            Ok(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set autopilot mode: {}", e),
+            ))
+        }
+    }
+}
+
+// the settings, e.g. {"bank_limit": "15", "turn_direction": "Left"}
+async fn put_settings(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<Settings>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    set_settings(&app_state_proxy, payload).await
+}
+
+async fn set_settings(
+    app_state_proxy: &AppStateProxy,
+    settings: Settings,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let mut res: anyhow::Result<()> = Ok(());
+
+    // direction of the next heading changes, and whether the heading setpoints are true or magnetic
+    if let Some(turn_direction) = settings.turn_direction {
+        res = res.and(app_state_proxy.set_turn_direction(turn_direction).await);
+    }
+    if let Some(heading_reference) = settings.heading_reference {
+        res = res.and(app_state_proxy.set_heading_reference(heading_reference).await);
+    }
+    // maximum bank angle for all lateral modes
+    if let Some(bank_limit) = settings.bank_limit {
+        res = res.and(app_state_proxy.set_bank_limit(bank_limit).await);
+    }
+    // what TECS holds on to when the throttle saturates
+    if let Some(priority) = settings.tecs_priority {
+        res = res.and(app_state_proxy.set_tecs_priority(priority).await);
+    }
+    if let Some(thrust_limit) = settings.thrust_limit {
+        res = res.and(app_state_proxy.set_thrust_limit(thrust_limit).await);
+    }
+
+    match res {
+        Ok(_) => {
+            event!(Level::INFO, "Settings changed ({:?})", settings);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot change the settings: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot change the settings: {}", e),
+            ))
+        }
+    }
+}
+
+// the modes, and the settings that used to be set as a mode
+async fn activate_mode(
+    Path((direction, mode)): Path<(String, String)>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let settings: Settings = match (direction.as_str(), mode.as_str()) {
+        ("horizontal" | "vertical" | "autothrottle", _) => {
+            return activate(&app_state_proxy, &direction, &mode).await;
+        }
+        ("thrustlimit", "mct") => Settings {
+            thrust_limit: Some(ThrustLimit::MaximumContinuous),
+            ..Settings::default()
+        },
+        ("thrustlimit", "toga") => Settings {
+            thrust_limit: Some(ThrustLimit::TakeOffGoAround),
+            ..Settings::default()
+        },
+        ("turn", "shortest") => Settings {
+            turn_direction: Some(TurnDirection::Shortest),
+            ..Settings::default()
+        },
+        ("turn", "left") => Settings {
+            turn_direction: Some(TurnDirection::Left),
+            ..Settings::default()
+        },
+        ("turn", "right") => Settings {
+            turn_direction: Some(TurnDirection::Right),
+            ..Settings::default()
+        },
+        ("heading", "true") => Settings {
+            heading_reference: Some(HeadingReference::True),
+            ..Settings::default()
+        },
+        ("heading", "magnetic") => Settings {
+            heading_reference: Some(HeadingReference::Magnetic),
+            ..Settings::default()
+        },
+        ("bank", _) => Settings {
+            // the same names as in the state, e.g. 15 or StandardRate
+            bank_limit: Some(bank_limit_from_path(&mode).ok_or_else(|| {
+                error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!("There is no bank limit {}", mode),
+                )
+            })?),
+            ..Settings::default()
+        },
+        ("priority", "speed") => Settings {
+            tecs_priority: Some(TecsPriority::Speed),
+            ..Settings::default()
+        },
+        ("priority", "altitude") => Settings {
+            tecs_priority: Some(TecsPriority::Altitude),
+            ..Settings::default()
+        },
+        (_, _) => {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("There is no {} mode {}", direction, mode),
+            ));
+        }
+    };
+
+    set_settings(&app_state_proxy, settings).await
+}
+
+fn bank_limit_from_path(mode: &str) -> Option<BankLimit> {
+    match mode {
+        "auto" => Some(BankLimit::Auto),
+        "standardrate" => Some(BankLimit::StandardRate),
+        _ => serde_json::from_value(serde_json::Value::String(mode.to_string())).ok(),
+    }
+}
+
+// get the active flight plan
+async fn get_flight_plan(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let state: crate::types::AutoPilotState = app_state_proxy
+        .get_auto_pilot_state()
+        .await
+        .expect("error getting the state");
+
+    Ok(Json(state.flight_plan))
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadFlightPlanParams {
+    // format is optional, it will be detected from the contents if not given
+    format: Option<FlightPlanFormat>,
+}
+
+// upload a flight plan (X-Plane fms, GPX, KML or a JSON list of waypoints), this replaces the active flight plan
+async fn upload_flight_plan(
+    State(app_state_proxy): State<AppStateProxy>,
+    Query(params): Query<UploadFlightPlanParams>,
+    body: String,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let waypoints: Vec<Waypoint> =
+        flightplan::parse_flight_plan(&body, params.format).map_err(|e| {
+            event!(Level::ERROR, "Cannot parse flight plan: {:?}", e);
+            error_response(StatusCode::BAD_REQUEST, format!("Cannot parse flight plan: {}", e))
+        })?;
+
+    let number_of_waypoints: usize = waypoints.len();
+
+    match app_state_proxy.load_flight_plan(waypoints).await {
+        Ok(_) => {
+            event!(
+                Level::INFO,
+                "Flight plan loaded with {} waypoints",
+                number_of_waypoints
+            );
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot load flight plan: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot load flight plan: {}", e),
+            ))
+        }
+    }
+}
+
+async fn clear_flight_plan(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.clear_flight_plan().await {
+        Ok(_) => {
+            event!(Level::INFO, "Flight plan cleared");
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot clear flight plan: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot clear flight plan: {}", e),
+            ))
+        }
+    }
+}
+
+// insert a waypoint before the waypoint at index (index equal to the number of waypoints appends)
+async fn insert_waypoint(
+    Path(index): Path<usize>,
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<Waypoint>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name: String = payload.name.clone();
+
+    match app_state_proxy.insert_waypoint(index, payload).await {
+        Ok(_) => {
+            event!(Level::INFO, "Waypoint {} inserted at {}", name, index);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot insert waypoint: {:?}", e);
+            Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Cannot insert waypoint: {}", e),
+            ))
+        }
+    }
+}
+
+async fn delete_waypoint(
+    Path(index): Path<usize>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.delete_waypoint(index).await {
+        Ok(_) => {
+            event!(Level::INFO, "Waypoint {} deleted", index);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot delete waypoint: {:?}", e);
+            Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Cannot delete waypoint: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WaypointConstraints {
+    altitude_constraint: Option<AltitudeConstraint>,
+    speed_constraint: Option<f64>,
+}
+
+// replace the altitude and speed constraints of a waypoint, leaving a field out removes that constraint
+async fn set_waypoint_constraints(
+    Path(index): Path<usize>,
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<WaypointConstraints>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy
+        .set_waypoint_constraints(index, payload.altitude_constraint, payload.speed_constraint)
+        .await
+    {
+        Ok(_) => {
+            event!(
+                Level::INFO,
+                "Constraints set on waypoint {} ({:?}, {:?})",
+                index,
+                payload.altitude_constraint,
+                payload.speed_constraint
+            );
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set waypoint constraints: {:?}", e);
+            Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Cannot set waypoint constraints: {}", e),
+            ))
+        }
+    }
+}
+
+async fn direct_to_waypoint(
+    Path(index): Path<usize>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.direct_to_waypoint(index).await {
+        Ok(_) => {
+            event!(Level::INFO, "Direct to waypoint {}", index);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot fly direct to waypoint: {:?}", e);
+            Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Cannot fly direct to waypoint: {}", e),
+            ))
+        }
+    }
+}
+
+// the hold that HOLD flies, e.g. {"fix": {"latitude": 52.3, "longitude": 4.76}, "inbound_course": 270,
+// "turn_direction": "Left", "leg_time": 1.5}. a hold that is flown is entered again from where we are
+async fn put_hold(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(hold_setup): Body<HoldSetup>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    hold_setup.validate().map_err(|e| {
+        event!(Level::ERROR, "Hold not set up: {}", e);
+        error_response(StatusCode::BAD_REQUEST, format!("Hold not set up: {}", e))
+    })?;
+
+    match app_state_proxy.set_hold(hold_setup).await {
+        Ok(_) => {
+            event!(Level::INFO, "Hold set up: {:?}", hold_setup);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set up the hold: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set up the hold: {}", e),
+            ))
+        }
+    }
+}
+
+async fn get_hold(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.get_auto_pilot_state().await {
+        Ok(state) => Ok(Json(state.holding_pattern)),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot get the hold: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot get the hold: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlightDirectorSettings {
+    flight_director_on: Option<bool>,
+    autopilot_coupled: Option<bool>,
+}
+
+// the flight director and the autopilot are switched separately, e.g. {"flight_director_on": true,
+// "autopilot_coupled": false} to hand fly the modes with the cues
+async fn put_flight_director(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(settings): Body<FlightDirectorSettings>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if settings.flight_director_on.is_none() && settings.autopilot_coupled.is_none() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "Flight director not set: give flight_director_on, autopilot_coupled or both".to_string(),
+        ));
+    }
+
+    // the flight director goes on first, so the modes stay when the autopilot uncouples in the same request
+    let result: anyhow::Result<()> = async {
+        if let Some(flight_director_on) = settings.flight_director_on {
+            app_state_proxy.set_flight_director(flight_director_on).await?;
+        }
+        if let Some(autopilot_coupled) = settings.autopilot_coupled {
+            app_state_proxy.set_autopilot_coupled(autopilot_coupled).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => {
+            event!(Level::INFO, "Flight director set: {:?}", settings);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set the flight director: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set the flight director: {}", e),
+            ))
+        }
+    }
+}
+
+async fn get_flight_director(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.get_auto_pilot_state().await {
+        Ok(state) => Ok(Json(state.flight_director)),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot get the flight director: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot get the flight director: {}", e),
+            ))
+        }
+    }
+}
+
+// the circle that ORBIT flies, e.g. {"center": {"latitude": 52.37, "longitude": 4.9}, "radius": 1.5,
+// "direction": "Left"}
+async fn put_orbit(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(orbit_setup): Body<OrbitSetup>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    orbit_setup.validate().map_err(|e| {
+        event!(Level::ERROR, "Orbit not set up: {}", e);
+        error_response(StatusCode::BAD_REQUEST, format!("Orbit not set up: {}", e))
+    })?;
+
+    match app_state_proxy.set_orbit(orbit_setup).await {
+        Ok(_) => {
+            event!(Level::INFO, "Orbit set up: {:?}", orbit_setup);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set up the orbit: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set up the orbit: {}", e),
+            ))
+        }
+    }
+}
+
+async fn get_orbit(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.get_auto_pilot_state().await {
+        Ok(state) => Ok(Json(state.horizontal_guidance.orbit_setup)),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot get the orbit: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot get the orbit: {}", e),
+            ))
+        }
+    }
+}
+
+async fn current_constants(
+    app_state_proxy: &AppStateProxy,
+) -> Result<AutoPilotConstants, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.get_auto_pilot_state().await {
+        Ok(state) => Ok(state.control_constants),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot get the constants: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot get the constants: {}", e),
+            ))
+        }
+    }
+}
+
+// the constants in effect
+async fn get_constants(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    Ok(Json(current_constants(&app_state_proxy).await?))
+}
+
+// changes some of the constants, e.g. {"roll_p": 0.008}. they take effect from the next cycle,
+// and are only kept over a restart when saved
+async fn patch_constants(
+    State(app_state_proxy): State<AppStateProxy>,
+    Body(payload): Body<serde_json::Map<String, serde_json::Value>>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let constants: AutoPilotConstants = current_constants(&app_state_proxy)
+        .await?
+        .patched(&payload)
+        .map_err(|e| {
+            event!(Level::ERROR, "Constants not changed: {:#}", e);
+            error_response(StatusCode::BAD_REQUEST, format!("Constants not changed: {:#}", e))
+        })?;
+
+    let names: Vec<&str> = payload.keys().map(|name| name.as_str()).collect();
+    let source: String = format!("api, {}", names.join(", "));
+
+    match app_state_proxy
+        .set_autopilot_constants(constants.clone(), &source)
+        .await
+    {
+        Ok(_) => Ok(Json(constants)),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set the constants: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set the constants: {}", e),
+            ))
+        }
+    }
+}
+
+// writes the constants in effect to the constants file, or to the profile when one is selected.
+// the watcher then finds nothing changed when it reloads it
+async fn save_constants(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let profile: String = app_state_proxy
+        .get_auto_pilot_state()
+        .await
+        .expect("error getting the state")
+        .profile_status
+        .aircraft_profile;
+    let constants: AutoPilotConstants = current_constants(&app_state_proxy).await?;
+
+    let saved = match profile.is_empty() {
+        true => {
+            let path = constantswatcher::constants_path();
+            constants.to_file(&path).map(|_| path)
+        }
+        false => profiles::save_to_profile(&profile, &constants),
+    };
+
+    match saved {
+        Ok(path) => {
+            event!(Level::INFO, "Constants saved to {}", path.display());
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot save the constants: {:#}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot save the constants: {:#}", e),
+            ))
+        }
+    }
+}
+
+async fn get_constants_history(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let history: Vec<ConstantsVersion> = app_state_proxy
+        .get_constants_history()
+        .await
+        .expect("error getting the constants history");
+
+    Ok(Json(history))
+}
+
+// makes an earlier version the one in effect again, as a new version
+async fn rollback_constants(
+    Path(version): Path<u64>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let history: Vec<ConstantsVersion> = app_state_proxy
+        .get_constants_history()
+        .await
+        .expect("error getting the constants history");
+
+    let Some(previous) = history.into_iter().find(|v| v.version == version) else {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("There is no version {} in the history", version),
+        ));
+    };
+
+    match app_state_proxy
+        .set_autopilot_constants(previous.constants, &format!("rollback to version {}", version))
+        .await
+    {
+        Ok(_) => {
+            event!(Level::INFO, "Constants rolled back to version {}", version);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot roll back the constants: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot roll back the constants: {}", e),
+            ))
+        }
+    }
+}
+
+// the profiles in the profiles directory
+async fn get_profiles(
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match profiles::list_profiles() {
+        Ok(profiles) => Ok(Json(profiles)),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot list the profiles: {:#}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot list the profiles: {:#}", e),
+            ))
+        }
+    }
+}
+
+async fn get_profile(
+    Path(name): Path<String>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let path = profiles::profile_file(&name)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+
+    match AircraftProfile::from_file(&path) {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("Cannot read profile {}: {:#}", name, e),
+        )),
+    }
+}
+
+// the profile is applied on top of the constants file, changes made through the api are replaced
+async fn select_profile(
+    Path(name): Path<String>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match profiles::select_profile(&app_state_proxy, Some(&name)).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot select profile {}: {:#}", name, e);
+            Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Cannot select profile {}: {:#}", name, e),
+            ))
+        }
+    }
+}
+
+// back to the constants file alone
+async fn deselect_profile(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match profiles::select_profile(&app_state_proxy, None).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => {
+            event!(Level::ERROR, "Cannot deselect the profile: {:#}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot deselect the profile: {:#}", e),
+            ))
+        }
+    }
+}
+
+// select the profile for the aircraft the sim reports
+async fn set_profile_auto_select(
+    Path(enabled): Path<bool>,
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app_state_proxy.set_profile_auto_select(enabled).await {
+        Ok(_) => {
+            event!(Level::INFO, "Automatic profile selection: {}", enabled);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Cannot set the automatic profile selection: {:?}", e);
+            Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cannot set the automatic profile selection: {}", e),
+            ))
         }
     }
 }
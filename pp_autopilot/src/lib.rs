@@ -1,26 +1,63 @@
 use std::collections::{BTreeMap, HashMap};
 
+use envelope::{EnvelopeInput, EnvelopeLimits, EnvelopeProtection};
+use flightdirector::FlightDirector;
+use looptiming::{LoopClock, LoopTiming, LoopTrigger, MissedTick};
+use modes::ModeChannel;
+use pilotoverride::{AxisInput, OverrideEvent, OverrideLimits, PilotOverride};
 use serde_json::{Number, Value};
-use tokio::{sync::mpsc, time::Duration};
+use tokio::{
+    sync::{mpsc, watch},
+    time::{Duration, Instant, Interval},
+};
 use tracing::{event, Level};
 use types::*;
 
+pub mod altitudecapture;
+pub mod api;
+pub mod autothrottle;
+pub mod constantswatcher;
+pub mod envelope;
+pub mod flightdirector;
+pub mod flightplan;
+pub mod gainschedule;
+pub mod holding;
 pub mod horizontalguidance;
 pub mod httpserver;
+pub mod looptiming;
+pub mod modes;
+pub mod navigation;
+pub mod orbit;
+pub mod pid;
+pub mod pilotoverride;
+pub mod profiles;
+pub mod statepush;
+pub mod statestream;
 pub mod types;
 pub mod utils;
 pub mod verticalguidance;
+pub mod verticalprofile;
 
 pub async fn run_app(service_adresses: &(String, String, String)) -> anyhow::Result<()> {
     let (tx_state, rx_state) = mpsc::channel(8);
 
+    // the plane state samples from the planeconnector stream, the control loop runs once on every new one
+    let (tx_sample, rx_sample) = watch::channel(BTreeMap::new());
+
+    // counts the changes to the autopilot state, the state streams push the state when it moves
+    let (tx_changes, _) = watch::channel(0);
+
     // set up the app state and a proxy, that is linked through a channel. we can then clone and share the proxy with all the different procsesses
-    let app_state: AppState = AppState::new(rx_state);
-    let app_state_proxy: AppStateProxy = AppStateProxy::new(service_adresses, tx_state);
+    let app_state: AppState = AppState::new(rx_state, tx_changes.clone());
+    let app_state_proxy: AppStateProxy =
+        AppStateProxy::new(service_adresses, tx_state, tx_changes);
 
     tokio::select! {
         _ = app_state.process() => { event!(Level::INFO, "pp_autopilot app_state.process closed"); }
-        _ = run_autopilot(app_state_proxy.clone()) => { event!(Level::INFO, "pp_autopilot run_autopilot closed"); }
+        _ = constantswatcher::watch_constants(app_state_proxy.clone(), constantswatcher::constants_path()) => { event!(Level::INFO, "pp_autopilot watch_constants closed"); }
+        _ = profiles::auto_select_profile(app_state_proxy.clone()) => { event!(Level::INFO, "pp_autopilot auto_select_profile closed"); }
+        _ = statestream::listen_to_plane_state(app_state_proxy.clone(), tx_sample) => { event!(Level::INFO, "pp_autopilot listen_to_plane_state closed"); }
+        _ = run_autopilot(app_state_proxy.clone(), rx_sample) => { event!(Level::INFO, "pp_autopilot run_autopilot closed"); }
         _ = share_state_with_data_server(app_state_proxy.clone()) => { event!(Level::INFO, "pp_autopilot share_state_with_data_server closed");  }
         _ = httpserver::run_server(app_state_proxy.clone()) => { event!(Level::INFO, "pp_autopilot httpserver closed"); }
     }
@@ -30,15 +67,51 @@ pub async fn run_app(service_adresses: &(String, String, String)) -> anyhow::Res
     Ok(())
 }
 
-async fn run_autopilot(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
-    const MILLISECONDS_PER_LOOP: u64 = 200;
+async fn run_autopilot(
+    app_state_proxy: AppStateProxy,
+    mut samples: watch::Receiver<BTreeMap<String, Value>>,
+) -> anyhow::Result<()> {
     let reqwest_client: reqwest::Client = reqwest::Client::new();
 
     let mut local_error_state: bool = true;
+    let mut incomplete_plane_state: bool = false;
+    let mut no_simulator_time: bool = false;
+
+    // the loop runs on new samples or at the rate in the constants, and starts over when the loop constants change
+    let constants: AutoPilotConstants = app_state_proxy
+        .get_auto_pilot_state()
+        .await?
+        .control_constants;
+    let mut loop_constants: (LoopTrigger, f64, MissedTick, LoopClock) = loop_settings(&constants);
+    let mut period: f64 = looptiming::loop_period(constants.loop_rate);
+    let mut interval: Interval = loop_interval(period, constants.loop_missed_tick, Instant::now());
+    let mut timing: LoopTiming = LoopTiming::new(period);
+    let mut sample_timeout: Duration = looptiming::sample_timeout(constants.sample_timeout);
+    let mut envelope_protection: EnvelopeProtection = EnvelopeProtection::default();
+    let mut pilot_override: PilotOverride = PilotOverride::default();
+
+    let start: Instant = Instant::now();
+    let mut previous_wake_up: Option<Instant> = None;
+    let mut previous_clock: Option<f64> = None;
 
     loop {
-        match update_state(&app_state_proxy).await {
+        if loop_constants.0 == LoopTrigger::Interval {
+            interval.tick().await;
+        }
+
+        let sample: anyhow::Result<BTreeMap<String, Value>> =
+            next_sample(&mut samples, sample_timeout).await;
+
+        let wake_up: Instant = Instant::now();
+
+        match sample {
             Ok(plane_state) => {
+                if let Some(sample_number) =
+                    plane_state.get("sample_number").and_then(|n| n.as_u64())
+                {
+                    timing.record_sample(sample_number);
+                }
+
                 app_state_proxy.set_plane_state(plane_state).await?;
                 event!(Level::TRACE, "Plane state updated");
 
@@ -56,8 +129,7 @@ async fn run_autopilot(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
                     local_error_state = false;
                     app_state_proxy.set_flying(false).await?;
                     app_state_proxy.clear_plane_state().await?;
-                    app_state_proxy.activate_vertical_standby_mode().await?;
-                    app_state_proxy.activate_horizontal_standby_mode().await?;
+                    app_state_proxy.revert_to_standby("no plane state").await?;
 
                     event!(
                         Level::ERROR,
@@ -68,16 +140,167 @@ async fn run_autopilot(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
             }
         };
 
-        let auto_pilot_state: types::AutoPilotState =
+        let mut auto_pilot_state: types::AutoPilotState =
             app_state_proxy.get_auto_pilot_state().await?;
 
-        // refresh the constants now every cycle, to iterate fast
-        app_state_proxy.refresh_autopilot_constants().await?;
+        // changed loop constants take effect before anything can skip the rest of the cycle
+        let constants: &AutoPilotConstants = &auto_pilot_state.control_constants;
+        sample_timeout = looptiming::sample_timeout(constants.sample_timeout);
+
+        if loop_constants != loop_settings(constants) {
+            loop_constants = loop_settings(constants);
+            period = looptiming::loop_period(constants.loop_rate);
+            interval = loop_interval(
+                period,
+                constants.loop_missed_tick,
+                Instant::now() + Duration::from_secs_f64(period),
+            );
+            timing = LoopTiming::new(period);
+            previous_wake_up = None;
+            previous_clock = None;
+
+            event!(
+                Level::INFO,
+                "Autopilot loop triggered by {:?} ({:.1} Hz, missed ticks {:?}), dt from the {:?} clock",
+                constants.loop_trigger,
+                1.0 / period,
+                constants.loop_missed_tick,
+                constants.loop_clock
+            );
+        }
 
         if auto_pilot_state.are_we_flying {
-            let plane_state: PlaneStateStruct = app_state_proxy.get_plane_state_as_struct().await?;
+            // a sample without the values the guidance needs, e.g. a DATA row that is not enabled in xplane,
+            // is nothing to fly on: the modes go to standby until the samples are complete again
+            let plane_state: PlaneStateStruct = match app_state_proxy.get_plane_state_as_struct().await {
+                Ok(plane_state) => {
+                    incomplete_plane_state = false;
+                    plane_state
+                }
+                Err(e) => {
+                    if modes::is_engaged(&auto_pilot_state) {
+                        app_state_proxy.revert_to_standby("incomplete plane state").await?;
+                    }
+
+                    if !incomplete_plane_state {
+                        incomplete_plane_state = true;
+                        event!(
+                            Level::ERROR,
+                            "Incomplete plane state so autopilot set to standby: {:#}",
+                            e
+                        );
+                    }
+
+                    previous_wake_up = None;
+                    previous_clock = None;
+                    app_state_proxy.update_flight_director_cues().await?;
+                    continue;
+                }
+            };
+
+            // the guidance flies with the gains for the current airspeed and altitude
+            match gainschedule::schedule_constants(
+                &auto_pilot_state.control_constants,
+                plane_state.v_ind,
+                plane_state.altitude_msl,
+            ) {
+                Ok((constants, scheduled_gains)) => {
+                    auto_pilot_state.control_constants = constants;
+                    app_state_proxy.update_scheduled_gains(scheduled_gains).await?;
+                }
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        "Gain schedules not applied, flying with the unscheduled constants: {:?}",
+                        e
+                    );
+                }
+            }
 
-            let dt: f64 = MILLISECONDS_PER_LOOP as f64 / 1000.0;
+            // dt from the clock that is configured, instead of assuming the loop runs on time. without
+            // the times row from xplane the local clock is used, and dt starts over when the clock changes
+            let local_clock: f64 = wake_up.duration_since(start).as_secs_f64();
+            let clock: f64 = match (auto_pilot_state.control_constants.loop_clock, plane_state.sim_time) {
+                (LoopClock::Simulator, Some(sim_time)) => {
+                    if no_simulator_time {
+                        no_simulator_time = false;
+                        previous_clock = None;
+                    }
+                    sim_time
+                }
+                (LoopClock::Simulator, None) => {
+                    if !no_simulator_time {
+                        no_simulator_time = true;
+                        previous_clock = None;
+                        event!(
+                            Level::WARN,
+                            "No simulator time in the plane state, enable the times row (1) in xplane, using the local clock"
+                        );
+                    }
+                    local_clock
+                }
+                (LoopClock::Local, _) => local_clock,
+            };
+            let dt: f64 = looptiming::measured_dt(previous_clock, clock, period);
+            previous_clock = Some(clock);
+
+            // the pilot flying against the last commands takes that channel over
+            let (aileron, elevator): (AxisInput, AxisInput) =
+                override_inputs(&auto_pilot_state, &plane_state);
+            let overridden: Vec<ModeChannel> = pilot_override.update(
+                &OverrideLimits::from_constants(&auto_pilot_state.control_constants),
+                &aileron,
+                &elevator,
+                dt,
+            );
+            app_state_proxy
+                .update_pilot_override(pilot_override.clone())
+                .await?;
+
+            for channel in overridden.iter() {
+                let axis: &AxisInput = match channel {
+                    ModeChannel::Vertical => &elevator,
+                    _ => &aileron,
+                };
+
+                app_state_proxy
+                    .override_channel(OverrideEvent {
+                        timestamp: chrono::Utc::now().timestamp_millis(),
+                        channel: *channel,
+                        command: axis.command.unwrap_or_default(),
+                        yoke: axis.yoke.unwrap_or_default(),
+                        surface: axis.surface.unwrap_or_default(),
+                    })
+                    .await?;
+            }
+
+            // the envelope is checked before the guidance runs, so a breach disconnects before the next command
+            let breach: Option<String> = envelope_protection.update(
+                &EnvelopeLimits::from_constants(&auto_pilot_state.control_constants),
+                &EnvelopeInput::from_plane_state(&plane_state),
+                dt,
+            );
+            auto_pilot_state.envelope_protection = envelope_protection.clone();
+            app_state_proxy
+                .update_envelope_protection(envelope_protection.clone())
+                .await?;
+
+            let disconnect: Option<String> = breach.filter(|_| modes::is_engaged(&auto_pilot_state));
+
+            if let Some(reason) = &disconnect {
+                app_state_proxy
+                    .disconnect_autopilot(&format!("envelope protection, {}", reason))
+                    .await?;
+            }
+
+            // the channels that were taken over or disconnected are in standby now, so the guidance below
+            // sends them no commands
+            if !overridden.is_empty() || disconnect.is_some() {
+                auto_pilot_state = AutoPilotState {
+                    control_constants: auto_pilot_state.control_constants,
+                    ..app_state_proxy.get_auto_pilot_state().await?
+                };
+            }
 
             verticalguidance::execute_vertical_guidance(
                 dt,
@@ -88,6 +311,15 @@ async fn run_autopilot(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
             )
             .await?;
 
+            autothrottle::execute_autothrottle(
+                dt,
+                &reqwest_client,
+                &app_state_proxy,
+                &auto_pilot_state,
+                &plane_state,
+            )
+            .await?;
+
             horizontalguidance::execute_horizontal_guidance(
                 dt,
                 &reqwest_client,
@@ -95,19 +327,110 @@ async fn run_autopilot(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
                 &auto_pilot_state,
                 &plane_state,
             )
-            .await?
+            .await?;
+
+            timing.record(
+                previous_wake_up.map(|previous| wake_up.duration_since(previous).as_secs_f64()),
+                wake_up.elapsed().as_secs_f64(),
+                dt,
+            );
+            previous_wake_up = Some(wake_up);
+
+            if timing.loop_execution_time > period {
+                event!(
+                    Level::DEBUG,
+                    "Autopilot cycle took {:.3} s, longer than the period of {:.3} s",
+                    timing.loop_execution_time,
+                    period
+                );
+            }
+
+            app_state_proxy.update_loop_timing(timing.clone()).await?;
+        } else {
+            // start measuring again when we are back in the air
+            previous_wake_up = None;
+            previous_clock = None;
         }
 
-        let _ = tokio::time::sleep(Duration::from_millis(MILLISECONDS_PER_LOOP)).await;
+        // the cues follow what the guidance commanded this cycle, and go when the modes do
+        app_state_proxy.update_flight_director_cues().await?;
     }
 }
 
+// a channel is only watched for an override while the autopilot flies it
+fn override_inputs(
+    auto_pilot_state: &AutoPilotState,
+    plane_state: &PlaneStateStruct,
+) -> (AxisInput, AxisInput) {
+    let last_commands = &auto_pilot_state.last_commands;
+    let coupled: bool = auto_pilot_state.flight_director.autopilot_coupled;
+    let lateral: bool = coupled
+        && auto_pilot_state.horizontal_guidance.horizontal_mode != HorizontalModes::Standby;
+    let vertical: bool =
+        coupled && auto_pilot_state.vertical_guidance.vertical_mode != VerticalModes::Standby;
+
+    let aileron: AxisInput = AxisInput {
+        command: last_commands.last_aileron_command.filter(|_| lateral),
+        yoke: plane_state.aileron_commanded,
+        surface: plane_state.aileron_actual,
+    };
+
+    let elevator: AxisInput = AxisInput {
+        command: last_commands.last_elevator_command.filter(|_| vertical),
+        yoke: plane_state.elevator_commanded,
+        surface: plane_state.elevator_actual,
+    };
+
+    (aileron, elevator)
+}
+
+fn loop_settings(constants: &AutoPilotConstants) -> (LoopTrigger, f64, MissedTick, LoopClock) {
+    (
+        constants.loop_trigger,
+        constants.loop_rate,
+        constants.loop_missed_tick,
+        constants.loop_clock,
+    )
+}
+
+// waits for a sample the loop has not worked on yet
+async fn next_sample(
+    samples: &mut watch::Receiver<BTreeMap<String, Value>>,
+    timeout: Duration,
+) -> anyhow::Result<BTreeMap<String, Value>> {
+    match tokio::time::timeout(timeout, samples.changed()).await {
+        Ok(Ok(())) => Ok(samples.borrow_and_update().clone()),
+        Ok(Err(_)) => Err(anyhow::Error::new(
+            SpecificErrors::PlaneConnectorNotReachable,
+        )),
+        Err(_) => Err(anyhow::Error::new(SpecificErrors::StateNotUpdatedRecently)),
+    }
+}
+
+fn loop_interval(period: f64, missed_tick: MissedTick, start: Instant) -> Interval {
+    let mut interval: Interval = tokio::time::interval_at(start, Duration::from_secs_f64(period));
+    interval.set_missed_tick_behavior(missed_tick.behavior());
+    interval
+}
+
 async fn send_command(
     app_state_proxy: &AppStateProxy,
     client: &reqwest::Client,
+    auto_pilot_state: &AutoPilotState,
     command_type: types::CommandType,
     value: f64,
 ) -> anyhow::Result<()> {
+    // with the autopilot uncoupled the guidance only drives the flight director
+    let flight_director: &FlightDirector = &auto_pilot_state.flight_director;
+    if !flight_director.moves_control(command_type, autothrottle::is_engaged(auto_pilot_state)) {
+        return Ok(());
+    }
+
+    // near the edges of the flight envelope the command is limited, whatever the guidance asks for
+    let value: f64 = auto_pilot_state
+        .envelope_protection
+        .limit(command_type, value);
+
     let mut map: HashMap<String, Value> = HashMap::new();
 
     match command_type {
@@ -122,10 +445,15 @@ async fn send_command(
         }
     }
 
-    map.insert(
-        "value".to_string(),
-        Value::Number(Number::from_f64(value).unwrap()),
-    );
+    // a NaN or infinite command is not sent, it would not be valid json
+    let value_number: Number = Number::from_f64(value).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{:?} command {} is not a finite number",
+            command_type,
+            value
+        )
+    })?;
+    map.insert("value".to_string(), Value::Number(value_number));
 
     match client
         .post(app_state_proxy.service_adresses.1.to_owned() + "/command")
@@ -133,36 +461,11 @@ async fn send_command(
         .send()
         .await
     {
-        Ok(_res) => Ok(()),
+        Ok(_res) => app_state_proxy.record_command(command_type, value).await,
         Err(e) => Err(e.into()),
     }
 }
 
-async fn update_state(app_state_proxy: &AppStateProxy) -> anyhow::Result<BTreeMap<String, Value>> {
-    let res = match reqwest::get(app_state_proxy.service_adresses.1.to_owned() + "/state").await {
-        Ok(res) => res,
-        Err(_) => {
-            return Err(anyhow::Error::new(
-                SpecificErrors::PlaneConnectorNotReachable,
-            ))
-        }
-    };
-
-    match res.status() {
-        reqwest::StatusCode::OK => {
-            let state = res.json::<BTreeMap<String, Value>>().await?;
-            if !state.contains_key("last_updated_timestamp") {
-                //todo check the recent update datetime, and if not recent, return error
-                return Err(anyhow::Error::new(SpecificErrors::StateNotUpdatedRecently));
-            }
-            Ok(state)
-        }
-        _ => Err(anyhow::Error::new(
-            SpecificErrors::PlaneConnectorReturnedError,
-        )),
-    }
-}
-
 async fn share_state_with_data_server(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
     let client = reqwest::Client::new();
 
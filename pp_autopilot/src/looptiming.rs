@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, MissedTickBehavior};
+
+// used when the configured loop rate is not a positive number [Hz]
+pub(super) const DEFAULT_LOOP_RATE: f64 = 5.0;
+
+// used when the configured sample timeout is not a positive number [s]
+pub(super) const DEFAULT_SAMPLE_TIMEOUT: f64 = 1.0;
+
+// a measured dt is limited to this many nominal periods, so a stall (e.g. the sim paused) does not
+// feed a huge step into the integrators
+pub(super) const MAX_DT_PERIODS: f64 = 5.0;
+
+// and it never goes to zero, e.g. when the plane state was not updated between two cycles
+pub(super) const MIN_DT: f64 = 0.001;
+
+// what starts a cycle: every new sample of the plane state, or a tick of the interval after which the
+// loop waits for the next sample. either way a sample is never used twice
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LoopTrigger {
+    #[default]
+    Sample,
+    Interval,
+}
+
+// what the interval does when a cycle took longer than the period
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissedTick {
+    // run the missed cycles back to back to catch up
+    Burst,
+    // start counting the period again from the late cycle
+    Delay,
+    // drop the missed cycles and continue on the original schedule
+    #[default]
+    Skip,
+}
+
+impl MissedTick {
+    pub(super) fn behavior(&self) -> MissedTickBehavior {
+        match self {
+            MissedTick::Burst => MissedTickBehavior::Burst,
+            MissedTick::Delay => MissedTickBehavior::Delay,
+            MissedTick::Skip => MissedTickBehavior::Skip,
+        }
+    }
+}
+
+// where dt comes from: the local clock when the loop wakes up, or the simulator time in the plane
+// state (DATA row 1 in xplane), which stops when the sim is paused and follows time acceleration
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LoopClock {
+    #[default]
+    Local,
+    Simulator,
+}
+
+// nominal period [s] for a loop rate [Hz]
+pub(super) fn loop_period(rate: f64) -> f64 {
+    match rate.is_finite() && rate > 0.0 {
+        true => 1.0 / rate,
+        false => 1.0 / DEFAULT_LOOP_RATE,
+    }
+}
+
+// how long to wait for a new sample before the autopilot goes to standby
+pub(super) fn sample_timeout(timeout: f64) -> Duration {
+    match timeout.is_finite() && timeout > 0.0 {
+        true => Duration::from_secs_f64(timeout),
+        false => Duration::from_secs_f64(DEFAULT_SAMPLE_TIMEOUT),
+    }
+}
+
+// dt [s] between two samples of a clock [s], the nominal period for the first sample
+pub(super) fn measured_dt(previous: Option<f64>, now: f64, period: f64) -> f64 {
+    match previous {
+        Some(previous) => (now - previous).clamp(MIN_DT, MAX_DT_PERIODS * period),
+        None => period,
+    }
+}
+
+// statistics of the control loop, all times in seconds
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(super) struct LoopTiming {
+    pub loop_cycles: u64,
+    pub loop_period_target: f64,
+    pub loop_period: f64,
+    pub loop_period_mean: f64,
+    pub loop_period_max: f64,
+    // rms deviation of the period from the target
+    pub loop_jitter: f64,
+    pub loop_execution_time: f64,
+    pub loop_execution_time_max: f64,
+    // cycles that took longer than the period
+    pub loop_overruns: u64,
+    // the dt the guidance used in the last cycle
+    pub loop_dt: f64,
+    // number of the last plane state sample, and the samples that came in while the loop was busy
+    pub loop_sample_number: u64,
+    pub loop_samples_skipped: u64,
+}
+
+impl LoopTiming {
+    pub(super) fn new(period_target: f64) -> Self {
+        LoopTiming {
+            loop_period_target: period_target,
+            ..Default::default()
+        }
+    }
+
+    // the period is measured between two wake ups, so the very first cycle only has an execution time
+    pub(super) fn record(&mut self, period: Option<f64>, execution_time: f64, dt: f64) {
+        if let Some(period) = period {
+            let periods: f64 = self.loop_cycles as f64;
+            let deviation: f64 = period - self.loop_period_target;
+
+            self.loop_period = period;
+            self.loop_period_mean = (self.loop_period_mean * periods + period) / (periods + 1.0);
+            self.loop_period_max = self.loop_period_max.max(period);
+            self.loop_jitter =
+                ((self.loop_jitter.powi(2) * periods + deviation.powi(2)) / (periods + 1.0)).sqrt();
+            self.loop_cycles += 1;
+        }
+
+        self.loop_execution_time = execution_time;
+        self.loop_execution_time_max = self.loop_execution_time_max.max(execution_time);
+        self.loop_dt = dt;
+
+        if execution_time > self.loop_period_target {
+            self.loop_overruns += 1;
+        }
+    }
+
+    // the planeconnector numbers its samples, a restart of the planeconnector starts over
+    pub(super) fn record_sample(&mut self, sample_number: u64) {
+        if self.loop_sample_number > 0 && sample_number > self.loop_sample_number {
+            self.loop_samples_skipped += sample_number - self.loop_sample_number - 1;
+        }
+
+        self.loop_sample_number = sample_number;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_period() {
+        assert_eq!(loop_period(10.0), 0.1);
+        assert_eq!(loop_period(0.0), 0.2);
+        assert_eq!(loop_period(f64::NAN), 0.2);
+    }
+
+    #[test]
+    fn test_measured_dt() {
+        assert_eq!(measured_dt(None, 10.0, 0.2), 0.2);
+        assert!((measured_dt(Some(10.0), 10.25, 0.2) - 0.25).abs() < 1e-12);
+
+        // limited on both sides
+        assert_eq!(measured_dt(Some(10.0), 30.0, 0.2), 1.0);
+        assert_eq!(measured_dt(Some(10.0), 10.0, 0.2), MIN_DT);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut timing = LoopTiming::new(0.2);
+
+        timing.record(None, 0.05, 0.2);
+        assert_eq!(timing.loop_cycles, 0);
+        assert_eq!(timing.loop_execution_time, 0.05);
+
+        timing.record(Some(0.1), 0.05, 0.1);
+        timing.record(Some(0.3), 0.25, 0.3);
+
+        assert_eq!(timing.loop_cycles, 2);
+        assert!((timing.loop_period_mean - 0.2).abs() < 1e-12);
+        assert_eq!(timing.loop_period_max, 0.3);
+        assert!((timing.loop_jitter - 0.1).abs() < 1e-12);
+        assert_eq!(timing.loop_execution_time_max, 0.25);
+        assert_eq!(timing.loop_overruns, 1);
+        assert_eq!(timing.loop_dt, 0.3);
+    }
+
+    #[test]
+    fn test_skipped_samples() {
+        let mut timing = LoopTiming::new(0.2);
+
+        timing.record_sample(10);
+        timing.record_sample(11);
+        timing.record_sample(14);
+        assert_eq!(timing.loop_samples_skipped, 2);
+
+        // planeconnector restarted
+        timing.record_sample(1);
+        timing.record_sample(2);
+        assert_eq!(timing.loop_samples_skipped, 2);
+    }
+
+    #[test]
+    fn test_sample_timeout() {
+        assert_eq!(sample_timeout(0.5), Duration::from_millis(500));
+        assert_eq!(sample_timeout(-1.0), Duration::from_secs(1));
+        assert_eq!(sample_timeout(f64::INFINITY), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_missed_tick_from_constants() {
+        let tick: MissedTick = serde_json::from_str("\"burst\"").unwrap();
+        assert_eq!(tick.behavior(), MissedTickBehavior::Burst);
+        assert_eq!(MissedTick::default().behavior(), MissedTickBehavior::Skip);
+    }
+}
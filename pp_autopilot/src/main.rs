@@ -10,6 +10,14 @@ async fn main() {
         std::env::set_var("RUST_LOG", "info")
     }
 
+    if std::env::var("CONSTANTS_PATH").is_err() {
+        std::env::set_var("CONSTANTS_PATH", "./constants.json")
+    }
+
+    if std::env::var("PROFILES_PATH").is_err() {
+        std::env::set_var("PROFILES_PATH", "./profiles")
+    }
+
     pp_autopilot::utils::start_tracing_subscriber();
 
     //data server, planeconnector, autopilot
@@ -0,0 +1,385 @@
+use serde::Serialize;
+
+use crate::altitudecapture::AltitudeCaptureState;
+use crate::autothrottle;
+use crate::flightdirector::FlightDirector;
+use crate::types::{
+    AutoPilotState, AutoThrottle, AutoThrottleModes, HorizontalGuidance, HorizontalModes,
+    VerticalGuidance, VerticalModes,
+};
+
+// number of mode transitions kept in the state
+pub(super) const MODE_LOG_LENGTH: usize = 50;
+
+// an armed LNAV engages at once when we are this close to the leg [nm]
+const LNAV_ON_LEG: f64 = 0.05;
+
+// and otherwise when within a turn radius and not flying away from the leg more than this [deg]
+const LNAV_MAX_INTERCEPT_TRACK_ANGLE: f64 = 120.0;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum ModeChannel {
+    Lateral,
+    Vertical,
+    AutoThrottle,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ModeTransition {
+    // [ms]
+    pub timestamp: i64,
+    pub channel: ModeChannel,
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+// what the conditions for engaging a mode are checked against
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ModeConditions {
+    pub plane_state: bool,
+    pub route: bool,
+    pub hold: bool,
+    pub orbit: bool,
+}
+
+pub(super) fn check_horizontal_mode(
+    mode: HorizontalModes,
+    conditions: &ModeConditions,
+) -> Result<(), &'static str> {
+    match mode {
+        HorizontalModes::Standby => Ok(()),
+        _ if !conditions.plane_state => Err("there is no plane state"),
+        HorizontalModes::LNAV if !conditions.route => Err("there is no route to fly"),
+        HorizontalModes::Hold if !conditions.hold => Err("there is no hold set up"),
+        HorizontalModes::Orbit if !conditions.orbit => Err("there is no orbit set up"),
+        _ => Ok(()),
+    }
+}
+
+pub(super) fn check_vertical_mode(
+    mode: VerticalModes,
+    conditions: &ModeConditions,
+) -> Result<(), &'static str> {
+    match mode {
+        VerticalModes::Standby => Ok(()),
+        _ if !conditions.plane_state => Err("there is no plane state"),
+        VerticalModes::VNAV if !conditions.route => Err("there is no route to fly"),
+        _ => Ok(()),
+    }
+}
+
+pub(super) fn check_autothrottle_mode(
+    mode: AutoThrottleModes,
+    conditions: &ModeConditions,
+) -> Result<(), &'static str> {
+    match mode {
+        AutoThrottleModes::Standby => Ok(()),
+        _ if !conditions.plane_state => Err("there is no plane state"),
+        _ => Ok(()),
+    }
+}
+
+// something is flown by the autopilot, so there is something to disconnect. the modes of an
+// uncoupled autopilot only drive the flight director
+pub(super) fn is_engaged(auto_pilot_state: &AutoPilotState) -> bool {
+    let modes_engaged: bool = auto_pilot_state.horizontal_guidance.horizontal_mode
+        != HorizontalModes::Standby
+        || auto_pilot_state.vertical_guidance.vertical_mode != VerticalModes::Standby;
+
+    (modes_engaged && auto_pilot_state.flight_director.autopilot_coupled)
+        || autothrottle::is_engaged(auto_pilot_state)
+}
+
+// only LNAV is armed, it engages when the active leg is intercepted
+pub(super) fn check_horizontal_arm(
+    mode: HorizontalModes,
+    active: HorizontalModes,
+    conditions: &ModeConditions,
+) -> Result<(), &'static str> {
+    match mode {
+        HorizontalModes::LNAV if active == HorizontalModes::LNAV => Err("LNAV is already engaged"),
+        HorizontalModes::LNAV if !conditions.route => Err("there is no route to fly"),
+        HorizontalModes::LNAV => Ok(()),
+        _ => Err("only LNAV can be armed"),
+    }
+}
+
+// only VNAV is armed, together with LNAV, and it engages when LNAV does
+pub(super) fn check_vertical_arm(
+    mode: VerticalModes,
+    active: VerticalModes,
+    horizontal_armed: Option<HorizontalModes>,
+    conditions: &ModeConditions,
+) -> Result<(), &'static str> {
+    match mode {
+        VerticalModes::VNAV if active == VerticalModes::VNAV => Err("VNAV is already engaged"),
+        VerticalModes::VNAV if !conditions.route => Err("there is no route to fly"),
+        VerticalModes::VNAV if horizontal_armed != Some(HorizontalModes::LNAV) => {
+            Err("VNAV can only be armed when LNAV is armed")
+        }
+        VerticalModes::VNAV => Ok(()),
+        _ => Err("only VNAV can be armed"),
+    }
+}
+
+// an armed LNAV engages when we are within a turn radius of the leg and closing in on it
+//
+// the cross track error is positive right of the leg, the track angle is the track relative to the leg [deg]
+pub(super) fn lnav_intercept(cross_track_error: f64, track_angle: f64, turn_radius: f64) -> bool {
+    if cross_track_error.abs() <= LNAV_ON_LEG {
+        return true;
+    }
+
+    let closing: bool = cross_track_error * track_angle < 0.0
+        && track_angle.abs() <= LNAV_MAX_INTERCEPT_TRACK_ANGLE;
+
+    closing && cross_track_error.abs() <= turn_radius
+}
+
+pub(super) fn horizontal_annunciation(mode: HorizontalModes) -> &'static str {
+    match mode {
+        HorizontalModes::Standby => "STBY",
+        HorizontalModes::WingsLevel => "WINGS LVL",
+        HorizontalModes::Heading => "HDG",
+        HorizontalModes::Track => "TRK",
+        HorizontalModes::LNAV => "LNAV",
+        HorizontalModes::Hold => "HOLD",
+        HorizontalModes::Orbit => "ORBIT",
+    }
+}
+
+// TECS shows the capture of the selected altitude
+pub(super) fn vertical_annunciation(
+    mode: VerticalModes,
+    capture_state: AltitudeCaptureState,
+) -> &'static str {
+    match (mode, capture_state) {
+        (VerticalModes::TECS, AltitudeCaptureState::Capture) => "ALT*",
+        (VerticalModes::TECS, AltitudeCaptureState::Hold) => "ALT",
+        (VerticalModes::Standby, _) => "STBY",
+        (VerticalModes::TECS, _) => "TECS",
+        (VerticalModes::VNAV, _) => "VNAV",
+        (VerticalModes::VS, _) => "V/S",
+        (VerticalModes::FLCH, _) => "FLCH",
+    }
+}
+
+pub(super) fn autothrottle_annunciation(mode: AutoThrottleModes) -> &'static str {
+    match mode {
+        AutoThrottleModes::Standby => "STBY",
+        AutoThrottleModes::Speed => "SPEED",
+        AutoThrottleModes::ThrHold => "THR HOLD",
+    }
+}
+
+// flight mode annunciations: what every channel does now, and what it will do next
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(super) struct FlightModeAnnunciation {
+    // AP when the autopilot flies the modes, FD when the flight director shows them
+    pub autopilot: String,
+    pub autothrottle: String,
+    pub lateral_active: String,
+    pub lateral_armed: String,
+    pub vertical_active: String,
+    pub vertical_armed: String,
+}
+
+impl FlightModeAnnunciation {
+    pub(super) fn new(
+        horizontal_guidance: &HorizontalGuidance,
+        vertical_guidance: &VerticalGuidance,
+        autothrottle: &AutoThrottle,
+        flight_director: &FlightDirector,
+    ) -> Self {
+        let engaged: bool = horizontal_guidance.horizontal_mode != HorizontalModes::Standby
+            || vertical_guidance.vertical_mode != VerticalModes::Standby;
+
+        let mut vertical_armed: Vec<&str> = Vec::new();
+
+        // the selected altitude is captured from every mode that climbs or descends towards it
+        match (
+            vertical_guidance.vertical_mode,
+            vertical_guidance.altitude_capture_state,
+        ) {
+            (VerticalModes::VS | VerticalModes::FLCH, _)
+            | (VerticalModes::TECS, AltitudeCaptureState::Inactive) => vertical_armed.push("ALT"),
+            _ => {}
+        }
+
+        if let Some(mode) = vertical_guidance.vertical_mode_armed {
+            vertical_armed.push(vertical_annunciation(mode, AltitudeCaptureState::Inactive));
+        }
+
+        FlightModeAnnunciation {
+            autopilot: flight_director.annunciation(engaged).to_string(),
+            autothrottle: autothrottle_annunciation(autothrottle.autothrottle_mode).to_string(),
+            lateral_active: horizontal_annunciation(horizontal_guidance.horizontal_mode)
+                .to_string(),
+            lateral_armed: horizontal_guidance
+                .horizontal_mode_armed
+                .map(horizontal_annunciation)
+                .unwrap_or_default()
+                .to_string(),
+            vertical_active: vertical_annunciation(
+                vertical_guidance.vertical_mode,
+                vertical_guidance.altitude_capture_state,
+            )
+            .to_string(),
+            vertical_armed: vertical_armed.join(" "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLYING: ModeConditions = ModeConditions {
+        plane_state: true,
+        route: true,
+        hold: true,
+        orbit: true,
+    };
+
+    #[test]
+    fn test_mode_conditions() {
+        let no_route = ModeConditions {
+            route: false,
+            ..FLYING
+        };
+        let no_plane_state = ModeConditions {
+            plane_state: false,
+            ..FLYING
+        };
+
+        assert!(check_horizontal_mode(HorizontalModes::LNAV, &FLYING).is_ok());
+        assert!(check_horizontal_mode(HorizontalModes::LNAV, &no_route).is_err());
+        assert!(check_horizontal_mode(HorizontalModes::Hold, &no_route).is_ok());
+        assert!(check_horizontal_mode(
+            HorizontalModes::Hold,
+            &ModeConditions {
+                hold: false,
+                ..FLYING
+            }
+        )
+        .is_err());
+        assert!(check_vertical_mode(VerticalModes::VNAV, &no_route).is_err());
+        assert!(check_vertical_mode(VerticalModes::VS, &no_plane_state).is_err());
+        assert!(check_autothrottle_mode(AutoThrottleModes::Speed, &no_plane_state).is_err());
+
+        // going to standby is always possible
+        assert!(check_horizontal_mode(HorizontalModes::Standby, &no_plane_state).is_ok());
+        assert!(check_vertical_mode(VerticalModes::Standby, &no_plane_state).is_ok());
+    }
+
+    #[test]
+    fn test_arming() {
+        assert!(
+            check_horizontal_arm(HorizontalModes::LNAV, HorizontalModes::Heading, &FLYING).is_ok()
+        );
+        assert!(
+            check_horizontal_arm(HorizontalModes::LNAV, HorizontalModes::LNAV, &FLYING).is_err()
+        );
+        assert!(
+            check_horizontal_arm(HorizontalModes::Track, HorizontalModes::Heading, &FLYING)
+                .is_err()
+        );
+
+        assert!(check_vertical_arm(
+            VerticalModes::VNAV,
+            VerticalModes::TECS,
+            Some(HorizontalModes::LNAV),
+            &FLYING
+        )
+        .is_ok());
+        assert!(
+            check_vertical_arm(VerticalModes::VNAV, VerticalModes::TECS, None, &FLYING).is_err()
+        );
+    }
+
+    #[test]
+    fn test_lnav_intercept() {
+        // on the leg
+        assert!(lnav_intercept(0.01, 90.0, 1.0));
+
+        // right of the leg, turning in from the right within a turn radius
+        assert!(lnav_intercept(0.8, -30.0, 1.0));
+        assert!(!lnav_intercept(1.5, -30.0, 1.0));
+
+        // parallel or flying away from the leg
+        assert!(!lnav_intercept(0.8, 0.0, 1.0));
+        assert!(!lnav_intercept(-0.8, -30.0, 1.0));
+        assert!(!lnav_intercept(0.8, -150.0, 1.0));
+    }
+
+    #[test]
+    fn test_annunciations() {
+        let horizontal_guidance = HorizontalGuidance {
+            horizontal_mode: HorizontalModes::Heading,
+            horizontal_mode_armed: Some(HorizontalModes::LNAV),
+            ..Default::default()
+        };
+        let vertical_guidance = VerticalGuidance {
+            vertical_mode: VerticalModes::VS,
+            vertical_mode_armed: Some(VerticalModes::VNAV),
+            ..Default::default()
+        };
+
+        let fma = FlightModeAnnunciation::new(
+            &horizontal_guidance,
+            &vertical_guidance,
+            &AutoThrottle::default(),
+            &FlightDirector::default(),
+        );
+
+        assert_eq!(fma.lateral_active, "HDG");
+        assert_eq!(fma.lateral_armed, "LNAV");
+        assert_eq!(fma.vertical_active, "V/S");
+        assert_eq!(fma.vertical_armed, "ALT VNAV");
+        assert_eq!(fma.autothrottle, "STBY");
+        assert_eq!(fma.autopilot, "AP");
+
+        let capturing = VerticalGuidance {
+            vertical_mode: VerticalModes::TECS,
+            altitude_capture_state: AltitudeCaptureState::Capture,
+            ..Default::default()
+        };
+
+        let fma = FlightModeAnnunciation::new(
+            &HorizontalGuidance::default(),
+            &capturing,
+            &AutoThrottle::default(),
+            &FlightDirector::default(),
+        );
+
+        assert_eq!(fma.vertical_active, "ALT*");
+        assert_eq!(fma.vertical_armed, "");
+        assert_eq!(fma.lateral_armed, "");
+        assert_eq!(fma.autopilot, "AP");
+
+        // hand flown with the flight director
+        let fma = FlightModeAnnunciation::new(
+            &horizontal_guidance,
+            &vertical_guidance,
+            &AutoThrottle::default(),
+            &FlightDirector {
+                flight_director_on: true,
+                autopilot_coupled: false,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(fma.autopilot, "FD");
+        assert_eq!(fma.lateral_active, "HDG");
+
+        let fma = FlightModeAnnunciation::new(
+            &HorizontalGuidance::default(),
+            &VerticalGuidance::default(),
+            &AutoThrottle::default(),
+            &FlightDirector::default(),
+        );
+
+        assert_eq!(fma.autopilot, "");
+    }
+}
@@ -0,0 +1,574 @@
+use serde::{Deserialize, Serialize};
+
+// great circle navigation helpers
+// all angles are in degrees, all distances in nautical miles
+
+pub(super) const EARTH_RADIUS_NM: f64 = 3440.065;
+pub(super) const KNOTS_TO_METERS_PER_SECOND: f64 = 0.514444;
+pub(super) const METERS_PER_NAUTICAL_MILE: f64 = 1852.0;
+pub(super) const GRAVITATIONAL_ACCELERATION: f64 = 9.81;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+// wraps an angle into (-180, 180], e.g. for heading and track errors
+pub(super) fn wrap_180(angle: f64) -> f64 {
+    let wrapped = wrap_360(angle);
+    if wrapped > 180.0 {
+        wrapped - 360.0
+    } else {
+        wrapped
+    }
+}
+
+// wraps an angle into [0, 360)
+pub(super) fn wrap_360(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(360.0);
+    if wrapped >= 360.0 {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+// a forced turn direction is ignored for heading changes smaller than this, so small overshoots
+// do not turn into a full circle
+pub(super) const FORCED_TURN_MINIMUM: f64 = 5.0;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum TurnDirection {
+    #[default]
+    Shortest,
+    Left,
+    Right,
+}
+
+// the heading (and track) setpoints are either true or magnetic, the ground track and navigation are always true
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum HeadingReference {
+    #[default]
+    True,
+    Magnetic,
+}
+
+// heading error to turn from the current heading to the target heading, positive is a right turn
+pub(super) fn heading_error(target: f64, current: f64, turn_direction: TurnDirection) -> f64 {
+    let shortest: f64 = wrap_180(target - current);
+
+    if shortest.abs() <= FORCED_TURN_MINIMUM {
+        return shortest;
+    }
+
+    match turn_direction {
+        TurnDirection::Shortest => shortest,
+        TurnDirection::Left => wrap_360(target - current) - 360.0,
+        TurnDirection::Right => wrap_360(target - current),
+    }
+}
+
+// haversine distance between two positions
+pub(super) fn distance_nm(from: Position, to: Position) -> f64 {
+    angular_distance(from, to) * EARTH_RADIUS_NM
+}
+
+fn angular_distance(from: Position, to: Position) -> f64 {
+    let phi1 = from.latitude.to_radians();
+    let phi2 = to.latitude.to_radians();
+    let delta_phi = (to.latitude - from.latitude).to_radians();
+    let delta_lambda = (to.longitude - from.longitude).to_radians();
+
+    let a = (delta_phi / 2.0).sin().powi(2)
+        + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
+
+    2.0 * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+// initial true course on the great circle from one position to another
+pub(super) fn initial_bearing(from: Position, to: Position) -> f64 {
+    let phi1 = from.latitude.to_radians();
+    let phi2 = to.latitude.to_radians();
+    let delta_lambda = (to.longitude - from.longitude).to_radians();
+
+    let y = delta_lambda.sin() * phi2.cos();
+    let x = phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * delta_lambda.cos();
+
+    wrap_360(y.atan2(x).to_degrees())
+}
+
+// true course on the great circle when arriving at the destination
+pub(super) fn final_bearing(from: Position, to: Position) -> f64 {
+    wrap_360(initial_bearing(to, from) + 180.0)
+}
+
+// position reached when travelling a distance along a great circle with a given initial course
+pub(super) fn destination_point(from: Position, bearing: f64, distance: f64) -> Position {
+    let delta = distance / EARTH_RADIUS_NM;
+    let theta = bearing.to_radians();
+    let phi1 = from.latitude.to_radians();
+    let lambda1 = from.longitude.to_radians();
+
+    let phi2 = (phi1.sin() * delta.cos() + phi1.cos() * delta.sin() * theta.cos()).asin();
+    let lambda2 = lambda1
+        + (theta.sin() * delta.sin() * phi1.cos()).atan2(delta.cos() - phi1.sin() * phi2.sin());
+
+    Position {
+        latitude: phi2.to_degrees(),
+        longitude: wrap_180(lambda2.to_degrees()),
+    }
+}
+
+// distance of a position from the great circle through two positions, positive when right of course
+pub(super) fn cross_track_distance_nm(from: Position, to: Position, position: Position) -> f64 {
+    let delta13 = angular_distance(from, position);
+    let theta13 = initial_bearing(from, position).to_radians();
+    let theta12 = initial_bearing(from, to).to_radians();
+
+    (delta13.sin() * (theta13 - theta12).sin()).asin() * EARTH_RADIUS_NM
+}
+
+// distance from the start of the great circle leg to the point abeam the position
+pub(super) fn along_track_distance_nm(from: Position, to: Position, position: Position) -> f64 {
+    let delta13 = angular_distance(from, position);
+    let delta_xt = cross_track_distance_nm(from, to, position) / EARTH_RADIUS_NM;
+    let theta13 = initial_bearing(from, position).to_radians();
+    let theta12 = initial_bearing(from, to).to_radians();
+
+    let along_track = (delta13.cos() / delta_xt.cos()).clamp(-1.0, 1.0).acos();
+
+    // negative when the position is behind the start of the leg
+    along_track.copysign((theta12 - theta13).cos()) * EARTH_RADIUS_NM
+}
+
+// turn radius for a coordinated turn at a given ground speed and bank angle
+pub(super) fn turn_radius_nm(ground_speed: f64, bank_angle: f64) -> f64 {
+    let velocity: f64 = ground_speed.max(0.0) * KNOTS_TO_METERS_PER_SECOND;
+    let tan_bank: f64 = bank_angle.abs().max(1.0).to_radians().tan();
+
+    velocity * velocity / (GRAVITATIONAL_ACCELERATION * tan_bank) / METERS_PER_NAUTICAL_MILE
+}
+
+// rate one turn, a full circle in two minutes [deg/s]
+pub(super) const STANDARD_RATE_TURN: f64 = 3.0;
+
+// bank angle limit for the lateral modes, auto uses the max_roll constant
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum BankLimit {
+    #[default]
+    Auto,
+    #[serde(rename = "5")]
+    Five,
+    #[serde(rename = "10")]
+    Ten,
+    #[serde(rename = "15")]
+    Fifteen,
+    #[serde(rename = "20")]
+    Twenty,
+    #[serde(rename = "25")]
+    TwentyFive,
+    #[serde(rename = "30")]
+    Thirty,
+    // the bank angle for a standard rate turn at the current true airspeed
+    StandardRate,
+}
+
+impl BankLimit {
+    // maximum bank angle [deg], never more than the max_roll constant
+    pub fn max_bank(&self, true_airspeed: f64, max_roll: f64) -> f64 {
+        let limit: f64 = match self {
+            BankLimit::Auto => max_roll,
+            BankLimit::Five => 5.0,
+            BankLimit::Ten => 10.0,
+            BankLimit::Fifteen => 15.0,
+            BankLimit::Twenty => 20.0,
+            BankLimit::TwentyFive => 25.0,
+            BankLimit::Thirty => 30.0,
+            BankLimit::StandardRate => standard_rate_bank(true_airspeed),
+        };
+
+        limit.min(max_roll.abs())
+    }
+}
+
+// bank angle for a coordinated turn at the standard rate, tan(bank) = turn rate * velocity / g
+pub(super) fn standard_rate_bank(true_airspeed: f64) -> f64 {
+    let velocity: f64 = true_airspeed.max(0.0) * KNOTS_TO_METERS_PER_SECOND;
+
+    (STANDARD_RATE_TURN.to_radians() * velocity / GRAVITATIONAL_ACCELERATION)
+        .atan()
+        .to_degrees()
+}
+
+// wind as a vector in knots, the direction the air moves towards (so not the meteorological "from" direction)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(super) struct Wind {
+    pub north: f64,
+    pub east: f64,
+}
+
+impl Wind {
+    // wind vector is ground velocity minus air velocity
+    pub fn estimate(true_airspeed: f64, heading: f64, ground_speed: f64, track: f64) -> Self {
+        Wind {
+            north: ground_speed * track.to_radians().cos()
+                - true_airspeed * heading.to_radians().cos(),
+            east: ground_speed * track.to_radians().sin()
+                - true_airspeed * heading.to_radians().sin(),
+        }
+    }
+
+    // first order low pass filter, the estimate is noisy during turns and gusts
+    pub fn filter(&self, estimate: Wind, dt: f64, time_constant: f64) -> Self {
+        let alpha: f64 = dt / (time_constant.max(0.0) + dt);
+
+        Wind {
+            north: self.north + alpha * (estimate.north - self.north),
+            east: self.east + alpha * (estimate.east - self.east),
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.north.hypot(self.east)
+    }
+
+    // direction the wind is coming from, as reported by ATC and weather reports
+    pub fn direction(&self) -> f64 {
+        wrap_360(self.east.atan2(self.north).to_degrees() + 180.0)
+    }
+
+    // angle to add to the desired track to get the heading that flies that track, positive is right
+    pub fn crab_angle(&self, track: f64, true_airspeed: f64) -> f64 {
+        if true_airspeed < 1.0 {
+            return 0.0;
+        }
+
+        let wind_to: f64 = self.east.atan2(self.north);
+        let crosswind: f64 = self.speed() * (wind_to - track.to_radians()).sin();
+
+        -(crosswind / true_airspeed).clamp(-1.0, 1.0).asin().to_degrees()
+    }
+
+    // ground speed along a track, with the crosswind taken out by the crab angle
+    pub fn ground_speed(&self, track: f64, true_airspeed: f64) -> f64 {
+        let relative: f64 = self.east.atan2(self.north) - track.to_radians();
+        let crosswind: f64 = self.speed() * relative.sin();
+        let tailwind: f64 = self.speed() * relative.cos();
+
+        (true_airspeed.powi(2) - crosswind.powi(2)).max(0.0).sqrt() + tailwind
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LateralNavigationInput {
+    pub position: Position,
+    pub ground_speed: f64,
+    pub max_roll: f64,
+    pub cross_track_gain: f64,
+    pub max_intercept_angle: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct LateralNavigationSolution {
+    pub desired_track: f64,
+    pub cross_track_error: f64,
+    pub distance_to_go: f64,
+    pub turn_anticipation_distance: f64,
+    pub sequence_waypoint: bool,
+}
+
+// computes the desired track to fly the great circle leg from `from` to `to`, and
+// whether the active waypoint should be sequenced (including turn anticipation for the next leg)
+pub(super) fn lateral_navigation(
+    from: Position,
+    to: Position,
+    next: Option<Position>,
+    input: &LateralNavigationInput,
+) -> LateralNavigationSolution {
+    let leg_length: f64 = distance_nm(from, to);
+
+    // a leg shorter than ~20 m has no meaningful course, fly direct to the waypoint
+    if leg_length < 0.01 {
+        let distance_to_go = distance_nm(input.position, to);
+        return LateralNavigationSolution {
+            desired_track: initial_bearing(input.position, to),
+            cross_track_error: 0.0,
+            distance_to_go,
+            turn_anticipation_distance: 0.0,
+            sequence_waypoint: distance_to_go < 0.1,
+        };
+    }
+
+    let cross_track_error: f64 = cross_track_distance_nm(from, to, input.position);
+    let along_track: f64 = along_track_distance_nm(from, to, input.position);
+    let distance_to_go: f64 = distance_nm(input.position, to);
+
+    // the course of the great circle changes along the leg, so take the course at the point abeam the plane
+    let abeam: Position = destination_point(from, initial_bearing(from, to), along_track.max(0.0));
+    let leg_track: f64 = if distance_nm(abeam, to) > 0.01 {
+        initial_bearing(abeam, to)
+    } else {
+        final_bearing(from, to)
+    };
+
+    let intercept_angle: f64 = (-cross_track_error * input.cross_track_gain)
+        .clamp(-input.max_intercept_angle, input.max_intercept_angle);
+
+    let desired_track: f64 = wrap_360(leg_track + intercept_angle);
+
+    // start the turn onto the next leg early, so we roll out on the new course
+    let turn_anticipation_distance: f64 = match next {
+        Some(next) => {
+            let track_change: f64 =
+                wrap_180(initial_bearing(to, next) - final_bearing(from, to)).abs();
+            turn_radius_nm(input.ground_speed, input.max_roll)
+                * (track_change.min(120.0) / 2.0).to_radians().tan()
+        }
+        None => 0.0,
+    };
+
+    let sequence_waypoint: bool = (next.is_some() && distance_to_go <= turn_anticipation_distance)
+        || along_track >= leg_length;
+
+    LateralNavigationSolution {
+        desired_track,
+        cross_track_error,
+        distance_to_go,
+        turn_anticipation_distance,
+        sequence_waypoint,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMSTERDAM: Position = Position {
+        latitude: 52.3086,
+        longitude: 4.7639,
+    };
+    const ROTTERDAM: Position = Position {
+        latitude: 51.9569,
+        longitude: 4.4372,
+    };
+
+    #[test]
+    fn test_wrap_angles() {
+        assert_eq!(wrap_180(350.0), -10.0);
+        assert_eq!(wrap_180(-190.0), 170.0);
+        assert_eq!(wrap_180(180.0), 180.0);
+        assert_eq!(wrap_360(-10.0), 350.0);
+        assert_eq!(wrap_360(720.0), 0.0);
+    }
+
+    #[test]
+    fn test_heading_error_across_north() {
+        // 350 to 10 is a 20 degree right turn, not a 340 degree left turn
+        assert_eq!(heading_error(10.0, 350.0, TurnDirection::Shortest), 20.0);
+        assert_eq!(heading_error(350.0, 10.0, TurnDirection::Shortest), -20.0);
+        assert_eq!(heading_error(0.0, 359.0, TurnDirection::Shortest), 1.0);
+        assert_eq!(heading_error(359.0, 0.0, TurnDirection::Shortest), -1.0);
+        assert_eq!(heading_error(360.0, 0.0, TurnDirection::Shortest), 0.0);
+        assert_eq!(heading_error(180.0, 0.0, TurnDirection::Shortest), 180.0);
+    }
+
+    #[test]
+    fn test_bank_limit() {
+        // at 100 kt a standard rate turn needs about 15 degrees of bank, the faster the steeper
+        assert!((standard_rate_bank(100.0) - 15.4).abs() < 0.1);
+        assert!((standard_rate_bank(200.0) - 28.8).abs() < 0.1);
+
+        assert_eq!(BankLimit::Auto.max_bank(100.0, 25.0), 25.0);
+        assert_eq!(BankLimit::Ten.max_bank(100.0, 25.0), 10.0);
+        assert_eq!(BankLimit::Thirty.max_bank(100.0, 25.0), 25.0);
+        assert!((BankLimit::StandardRate.max_bank(100.0, 25.0) - 15.4).abs() < 0.1);
+        assert_eq!(BankLimit::StandardRate.max_bank(300.0, 25.0), 25.0);
+    }
+
+    #[test]
+    fn test_forced_turn_direction() {
+        assert_eq!(heading_error(10.0, 350.0, TurnDirection::Left), -340.0);
+        assert_eq!(heading_error(350.0, 10.0, TurnDirection::Right), 340.0);
+        assert_eq!(heading_error(90.0, 0.0, TurnDirection::Left), -270.0);
+        assert_eq!(heading_error(270.0, 0.0, TurnDirection::Right), 270.0);
+
+        // halfway through a forced turn the error keeps its sign
+        assert_eq!(heading_error(10.0, 170.0, TurnDirection::Left), -160.0);
+        assert_eq!(heading_error(10.0, 190.0, TurnDirection::Left), -180.0);
+        assert_eq!(heading_error(10.0, 200.0, TurnDirection::Left), -190.0);
+
+        // close to the target the shortest way is taken, so an overshoot is corrected
+        assert_eq!(heading_error(10.0, 8.0, TurnDirection::Left), 2.0);
+        assert_eq!(heading_error(0.0, 2.0, TurnDirection::Right), -2.0);
+    }
+
+    #[test]
+    fn test_distance_and_bearing() {
+        let distance = distance_nm(AMSTERDAM, ROTTERDAM);
+        assert!((distance - 24.3).abs() < 0.1, "distance was {}", distance);
+
+        let bearing = initial_bearing(AMSTERDAM, ROTTERDAM);
+        assert!((bearing - 209.8).abs() < 0.5, "bearing was {}", bearing);
+
+        let east = initial_bearing(
+            Position::default(),
+            Position {
+                latitude: 0.0,
+                longitude: 1.0,
+            },
+        );
+        assert!((east - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cross_track_sign() {
+        // leg due north along the prime meridian, positions east are right of course
+        let from = Position::default();
+        let to = Position {
+            latitude: 1.0,
+            longitude: 0.0,
+        };
+        let right = Position {
+            latitude: 0.5,
+            longitude: 0.1,
+        };
+
+        let xte = cross_track_distance_nm(from, to, right);
+        assert!((xte - 6.0).abs() < 0.05, "xte was {}", xte);
+
+        let along = along_track_distance_nm(from, to, right);
+        assert!((along - 30.0).abs() < 0.1, "along track was {}", along);
+
+        let solution = lateral_navigation(
+            from,
+            to,
+            None,
+            &LateralNavigationInput {
+                position: right,
+                ground_speed: 100.0,
+                max_roll: 25.0,
+                cross_track_gain: 30.0,
+                max_intercept_angle: 45.0,
+            },
+        );
+
+        // right of course, so we should steer left of north to intercept
+        assert!((solution.desired_track - 315.0).abs() < 0.5);
+        assert!(!solution.sequence_waypoint);
+    }
+
+    fn lnav_input(position: Position) -> LateralNavigationInput {
+        LateralNavigationInput {
+            position,
+            ground_speed: 120.0,
+            max_roll: 25.0,
+            cross_track_gain: 30.0,
+            max_intercept_angle: 45.0,
+        }
+    }
+
+    #[test]
+    fn test_lnav_intercept() {
+        // leg due north along the prime meridian
+        let from = Position::default();
+        let to = Position {
+            latitude: 1.0,
+            longitude: 0.0,
+        };
+        let position = |longitude: f64| Position {
+            latitude: 0.5,
+            longitude,
+        };
+
+        // on the leg the track is the leg
+        let solution = lateral_navigation(from, to, None, &lnav_input(position(0.0)));
+        assert!(solution.cross_track_error.abs() < 1e-6);
+        assert!(wrap_180(solution.desired_track).abs() < 1e-6);
+
+        // 0.6 nm left of the leg: the intercept angle grows with the cross track error, to the right
+        let solution = lateral_navigation(from, to, None, &lnav_input(position(-0.01)));
+        assert!((solution.cross_track_error - -0.6).abs() < 0.01);
+        assert!((solution.desired_track - 18.0).abs() < 0.5, "track was {}", solution.desired_track);
+
+        // twice as far off is twice the intercept angle
+        let solution = lateral_navigation(from, to, None, &lnav_input(position(-0.02)));
+        assert!((solution.desired_track - 36.0).abs() < 0.5, "track was {}", solution.desired_track);
+
+        // far off the leg the intercept angle is limited, also on the other side
+        let solution = lateral_navigation(from, to, None, &lnav_input(position(-0.5)));
+        assert!((solution.desired_track - 45.0).abs() < 0.5);
+        let solution = lateral_navigation(from, to, None, &lnav_input(position(0.5)));
+        assert!((solution.desired_track - 315.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_lnav_sequencing() {
+        let from = Position::default();
+        let to = Position {
+            latitude: 1.0,
+            longitude: 0.0,
+        };
+        let to_go = |distance: f64| Position {
+            latitude: 1.0 - distance / 60.0,
+            longitude: 0.0,
+        };
+
+        // the last waypoint is sequenced when it is passed, not before
+        let solution = lateral_navigation(from, to, None, &lnav_input(to_go(0.2)));
+        assert_eq!(solution.turn_anticipation_distance, 0.0);
+        assert!(!solution.sequence_waypoint);
+        let solution = lateral_navigation(from, to, None, &lnav_input(to_go(-0.05)));
+        assert!(solution.sequence_waypoint);
+
+        // a 90 degree turn onto the next leg starts one turn radius before the waypoint
+        let east = Position {
+            latitude: 1.0,
+            longitude: 1.0,
+        };
+        let radius: f64 = turn_radius_nm(120.0, 25.0);
+        let solution = lateral_navigation(from, to, Some(east), &lnav_input(to_go(0.6)));
+        assert!((solution.turn_anticipation_distance - radius).abs() < 0.01);
+        assert!(!solution.sequence_waypoint);
+        let solution = lateral_navigation(from, to, Some(east), &lnav_input(to_go(radius - 0.05)));
+        assert!(solution.sequence_waypoint);
+
+        // straight on there is nothing to anticipate
+        let north = Position {
+            latitude: 2.0,
+            longitude: 0.0,
+        };
+        let solution = lateral_navigation(from, to, Some(north), &lnav_input(to_go(0.2)));
+        assert!(solution.turn_anticipation_distance < 0.01);
+        assert!(!solution.sequence_waypoint);
+
+        // a leg too short to have a course is flown direct to the waypoint
+        let solution = lateral_navigation(to, to, None, &lnav_input(to_go(2.0)));
+        assert!(wrap_180(solution.desired_track).abs() < 1e-6);
+        assert!(!solution.sequence_waypoint);
+        let solution = lateral_navigation(to, to, None, &lnav_input(to_go(0.05)));
+        assert!(solution.sequence_waypoint);
+    }
+
+    #[test]
+    fn test_wind_estimate_and_crab_angle() {
+        // heading north at 100 kt, drifting to the east: the wind is from the west
+        let wind = Wind::estimate(100.0, 0.0, 100.0_f64.hypot(20.0), 20.0_f64.atan2(100.0).to_degrees());
+        assert!((wind.speed() - 20.0).abs() < 1e-9);
+        assert!((wind.direction() - 270.0).abs() < 1e-9);
+
+        // to fly a track due north we have to crab left into the wind
+        let crab = wind.crab_angle(0.0, 100.0);
+        assert!((crab - -11.537).abs() < 0.01, "crab angle was {}", crab);
+
+        // a headwind needs no crab angle
+        assert!(wind.crab_angle(270.0, 100.0).abs() < 1e-9);
+
+        // flying west is flying into it, flying north needs a part of the airspeed against the crosswind
+        assert!((wind.ground_speed(270.0, 100.0) - 80.0).abs() < 1e-9);
+        assert!((wind.ground_speed(0.0, 100.0) - (100.0_f64.powi(2) - 20.0_f64.powi(2)).sqrt()).abs() < 1e-9);
+
+        let filtered = Wind::default().filter(wind, 1.0, 9.0);
+        assert!((filtered.east - 2.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+
+use crate::navigation::{self, Position};
+
+// an orbit around a point, flown along a vector field that leads onto the circle from anywhere
+
+// [nm]
+const MIN_RADIUS: f64 = 0.1;
+const MAX_RADIUS: f64 = 20.0;
+
+// closer to the center than this [nm] there is no bearing from the center, so we fly out north
+const CENTER: f64 = 0.001;
+
+// right is clockwise, seen from above
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum OrbitDirection {
+    #[default]
+    Right,
+    Left,
+}
+
+impl OrbitDirection {
+    // positive for a right orbit, like a right turn
+    fn sign(&self) -> f64 {
+        match self {
+            OrbitDirection::Right => 1.0,
+            OrbitDirection::Left => -1.0,
+        }
+    }
+}
+
+// e.g. {"center": {"latitude": 52.37, "longitude": 4.9}, "radius": 1.5, "direction": "Left"}
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OrbitSetup {
+    pub center: Position,
+    // [nm]
+    pub radius: f64,
+    #[serde(default)]
+    pub direction: OrbitDirection,
+}
+
+impl OrbitSetup {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        if !(-90.0..=90.0).contains(&self.center.latitude)
+            || !(-180.0..=180.0).contains(&self.center.longitude)
+        {
+            errors.push(format!(
+                "the center must be a latitude from -90 to 90 and a longitude from -180 to 180, is {}, {}",
+                self.center.latitude, self.center.longitude
+            ));
+        }
+
+        if !(MIN_RADIUS..=MAX_RADIUS).contains(&self.radius) {
+            errors.push(format!(
+                "radius must be from {} to {} nm, is {}",
+                MIN_RADIUS, MAX_RADIUS, self.radius
+            ));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("; ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct OrbitInput {
+    pub position: Position,
+    pub ground_speed: f64,
+    pub max_roll: f64,
+    // how sharply the vector field turns onto the circle, higher is more direct
+    pub convergence: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(super) struct OrbitSolution {
+    pub desired_track: f64,
+    // [nm] positive outside the circle
+    pub radial_error: f64,
+    pub distance_to_center: f64,
+    // [deg] the bank of the circle at the ground speed, faded out away from the circle
+    pub roll_feedforward: f64,
+    // [nm] the tightest circle at the ground speed and bank limit, a smaller orbit is flown wider
+    pub minimum_radius: f64,
+}
+
+// the vector field: tangent to the circle on it, straight to the center far outside of it, and turning
+// out of it from inside. the approach angle goes from 90 to -90 degrees with atan(k * error / radius)
+pub(super) fn orbit_guidance(setup: &OrbitSetup, input: &OrbitInput) -> OrbitSolution {
+    let distance_to_center: f64 = navigation::distance_nm(setup.center, input.position);
+    let radial_error: f64 = distance_to_center - setup.radius;
+
+    let bearing_from_center: f64 = match distance_to_center > CENTER {
+        true => navigation::initial_bearing(setup.center, input.position),
+        false => 0.0,
+    };
+
+    let approach: f64 = (input.convergence * radial_error / setup.radius)
+        .atan()
+        .to_degrees();
+    let desired_track: f64 = navigation::wrap_360(
+        bearing_from_center + setup.direction.sign() * (90.0 + approach),
+    );
+
+    // the turn the circle needs, so the heading loop does not have to find it with its integrator
+    let velocity: f64 = input.ground_speed.max(0.0) * navigation::KNOTS_TO_METERS_PER_SECOND;
+    let circle_bank: f64 = (velocity * velocity
+        / (navigation::GRAVITATIONAL_ACCELERATION
+            * setup.radius
+            * navigation::METERS_PER_NAUTICAL_MILE))
+        .atan()
+        .to_degrees()
+        .min(input.max_roll.abs());
+    let on_circle: f64 = (1.0 - radial_error.abs() / setup.radius).clamp(0.0, 1.0);
+
+    OrbitSolution {
+        desired_track,
+        radial_error,
+        distance_to_center,
+        roll_feedforward: setup.direction.sign() * circle_bank * on_circle,
+        minimum_radius: navigation::turn_radius_nm(input.ground_speed, input.max_roll),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER_POINT: Position = Position {
+        latitude: 52.0,
+        longitude: 5.0,
+    };
+
+    fn setup(direction: OrbitDirection) -> OrbitSetup {
+        OrbitSetup {
+            center: CENTER_POINT,
+            radius: 1.0,
+            direction,
+        }
+    }
+
+    fn input(position: Position) -> OrbitInput {
+        OrbitInput {
+            position,
+            ground_speed: 100.0,
+            max_roll: 25.0,
+            convergence: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_orbit_setup() {
+        assert!(setup(OrbitDirection::Right).validate().is_ok());
+
+        let bad = OrbitSetup {
+            radius: 0.0,
+            center: Position {
+                latitude: 95.0,
+                longitude: 0.0,
+            },
+            ..setup(OrbitDirection::Left)
+        };
+        let error: String = bad.validate().unwrap_err();
+        assert!(error.contains("radius"));
+        assert!(error.contains("center"));
+
+        let parsed: OrbitSetup = serde_json::from_str(
+            r#"{"center": {"latitude": 52.0, "longitude": 5.0}, "radius": 1.0}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed, setup(OrbitDirection::Right));
+    }
+
+    #[test]
+    fn test_vector_field() {
+        // north of the center on the circle: east for a right orbit, west for a left orbit
+        let north: Position = navigation::destination_point(CENTER_POINT, 0.0, 1.0);
+        let solution = orbit_guidance(&setup(OrbitDirection::Right), &input(north));
+        assert!((solution.desired_track - 90.0).abs() < 0.5);
+        assert!(solution.radial_error.abs() < 1e-3);
+        assert!(solution.roll_feedforward > 0.0);
+
+        let solution = orbit_guidance(&setup(OrbitDirection::Left), &input(north));
+        assert!((solution.desired_track - 270.0).abs() < 0.5);
+        assert!(solution.roll_feedforward < 0.0);
+
+        // far outside it points (almost) to the center, without a feedforward
+        let far: Position = navigation::destination_point(CENTER_POINT, 90.0, 20.0);
+        let solution = orbit_guidance(&setup(OrbitDirection::Right), &input(far));
+        assert!((solution.desired_track - 270.0).abs() < 5.0);
+        assert_eq!(solution.roll_feedforward, 0.0);
+
+        // inside it turns outwards
+        let inside: Position = navigation::destination_point(CENTER_POINT, 0.0, 0.2);
+        let solution = orbit_guidance(&setup(OrbitDirection::Right), &input(inside));
+        assert!(solution.desired_track > 0.0 && solution.desired_track < 90.0);
+    }
+
+    #[test]
+    fn test_converges_onto_the_circle() {
+        // a plane that flies the desired track with a limited turn rate ends up on the circle, from
+        // the center and from far away
+        for (start, direction) in [
+            (CENTER_POINT, OrbitDirection::Right),
+            (navigation::destination_point(CENTER_POINT, 200.0, 8.0), OrbitDirection::Left),
+        ] {
+            let setup: OrbitSetup = setup(direction);
+            let mut position: Position = start;
+            let mut track: f64 = 0.0;
+            let dt: f64 = 0.5;
+
+            let velocity: f64 = 100.0 * navigation::KNOTS_TO_METERS_PER_SECOND;
+            let max_turn_rate: f64 = (navigation::GRAVITATIONAL_ACCELERATION
+                * 25.0_f64.to_radians().tan()
+                / velocity)
+                .to_degrees();
+
+            for _ in 0..1200 {
+                let solution = orbit_guidance(&setup, &input(position));
+                let turn: f64 = navigation::wrap_180(solution.desired_track - track)
+                    .clamp(-max_turn_rate * dt, max_turn_rate * dt);
+                track = navigation::wrap_360(track + turn);
+                position = navigation::destination_point(position, track, 100.0 * dt / 3600.0);
+            }
+
+            let solution = orbit_guidance(&setup, &input(position));
+            assert!(
+                solution.radial_error.abs() < 0.05,
+                "radial error was {}",
+                solution.radial_error
+            );
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+
+// gains and limits of a loop, these come from the constants so they can be tuned while flying
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub output_min: f64,
+    pub output_max: f64,
+    // time constant [s] of the first order filter on the derivative, 0 for no filtering
+    pub derivative_filter_time: f64,
+    // 0 for conditional integration, otherwise back-calculation with this time constant [s]
+    pub anti_windup_tracking_time: f64,
+}
+
+impl Default for PidGains {
+    fn default() -> Self {
+        PidGains {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 0.0,
+            output_min: f64::NEG_INFINITY,
+            output_max: f64::INFINITY,
+            derivative_filter_time: 0.0,
+            anti_windup_tracking_time: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PidInput {
+    // setpoint minus measurement, the caller takes care of wrapping angles
+    pub error: f64,
+    pub measurement: f64,
+    // measured rate of the measurement (e.g. roll rate), otherwise the measurement is differentiated
+    pub measurement_rate: Option<f64>,
+    // added to the output before the limits, e.g. a trim throttle or a rate command
+    pub feedforward: f64,
+}
+
+// internal state of a loop, stored in the app state so it can be inspected and survives a mode change
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub struct Pid {
+    // integral term in output units, so a gain change does not bump the output
+    pub integral: f64,
+    // filtered rate of the measurement
+    pub derivative: f64,
+    pub previous_measurement: Option<f64>,
+    pub output: f64,
+    pub saturated: bool,
+    // set by initialize, the next update starts from the current output instead of from zero
+    pub bumpless: bool,
+}
+
+impl Pid {
+    pub fn update(&mut self, gains: &PidGains, input: &PidInput, dt: f64) -> f64 {
+        // derivative on the measurement, so a setpoint change does not kick the output
+        let rate: f64 = match (input.measurement_rate, self.previous_measurement) {
+            (Some(rate), _) => rate,
+            (None, Some(previous)) if dt > 0.0 => (input.measurement - previous) / dt,
+            (None, _) => 0.0,
+        };
+
+        let alpha: f64 = match gains.derivative_filter_time {
+            t if t > 0.0 && self.previous_measurement.is_some() => dt / (t + dt),
+            _ => 1.0,
+        };
+
+        self.derivative += alpha * (rate - self.derivative);
+        self.previous_measurement = Some(input.measurement);
+
+        let proportional: f64 = gains.kp * input.error;
+        let derivative: f64 = -gains.kd * self.derivative;
+
+        // bumpless transfer: pick the integral that gives the output we already have
+        if self.bumpless {
+            self.integral = self.output - proportional - derivative - input.feedforward;
+            self.bumpless = false;
+        }
+
+        let unlimited: f64 = proportional + self.integral + derivative + input.feedforward;
+        let output: f64 = unlimited.clamp(gains.output_min, gains.output_max);
+
+        // anti-windup
+        let increment: f64 = gains.ki * input.error * dt;
+
+        if gains.anti_windup_tracking_time > 0.0 {
+            self.integral += increment + (output - unlimited) * dt / gains.anti_windup_tracking_time;
+        } else if output == unlimited || (unlimited > output) != (increment > 0.0) {
+            // only integrate when not against a limit, or when it unwinds
+            self.integral += increment;
+        }
+
+        self.output = output;
+        self.saturated = output != unlimited;
+
+        output
+    }
+
+    pub fn reset(&mut self) {
+        *self = Pid::default();
+    }
+
+    // start from a known output, e.g. the throttle position when the autothrottle engages
+    pub fn initialize(&mut self, output: f64) {
+        *self = Pid {
+            output,
+            bumpless: true,
+            ..Pid::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gains() -> PidGains {
+        PidGains {
+            kp: 1.0,
+            ki: 0.5,
+            kd: 0.0,
+            output_min: -1.0,
+            output_max: 1.0,
+            ..Default::default()
+        }
+    }
+
+    fn input(error: f64, measurement: f64) -> PidInput {
+        PidInput {
+            error,
+            measurement,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_proportional_integral() {
+        let mut pid = Pid::default();
+
+        assert!((pid.update(&gains(), &input(0.4, 0.0), 0.2) - 0.4).abs() < 1e-9);
+        assert!((pid.integral - 0.04).abs() < 1e-9);
+
+        // the integral keeps adding while the error stays
+        assert!((pid.update(&gains(), &input(0.4, 0.0), 0.2) - 0.44).abs() < 1e-9);
+        assert!(!pid.saturated);
+    }
+
+    #[test]
+    fn test_conditional_integration() {
+        let mut pid = Pid::default();
+
+        for _ in 0..100 {
+            pid.update(&gains(), &input(2.0, 0.0), 0.2);
+        }
+
+        // saturated, the integral did not grow
+        assert!(pid.saturated);
+        assert_eq!(pid.output, 1.0);
+        assert_eq!(pid.integral, 0.0);
+
+        // it does unwind while still saturated when the error changes sign
+        pid.integral = 2.0;
+        pid.update(&gains(), &input(-0.2, 0.0), 0.2);
+        assert!(pid.saturated);
+        assert!((pid.integral - 1.98).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_back_calculation() {
+        let gains = PidGains {
+            kp: 0.5,
+            anti_windup_tracking_time: 1.0,
+            ..gains()
+        };
+        let mut pid = Pid::default();
+
+        for _ in 0..200 {
+            pid.update(&gains, &input(2.0, 0.0), 0.2);
+        }
+
+        // the integral settles where the excess output is bled off: ki * e = (unlimited - max) / tt
+        assert_eq!(pid.output, 1.0);
+        assert!((pid.integral - 1.0).abs() < 1e-6, "integral was {}", pid.integral);
+    }
+
+    #[test]
+    fn test_derivative_on_measurement() {
+        let gains = PidGains {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 1.0,
+            ..Default::default()
+        };
+        let mut pid = Pid::default();
+
+        // a setpoint step does not kick the derivative
+        assert_eq!(pid.update(&gains, &input(0.0, 5.0), 0.2), 0.0);
+        assert_eq!(pid.update(&gains, &input(10.0, 5.0), 0.2), 0.0);
+
+        // a rising measurement damps
+        assert!((pid.update(&gains, &input(10.0, 5.2), 0.2) - -1.0).abs() < 1e-9);
+
+        // a measured rate is used as is
+        let input = PidInput {
+            measurement_rate: Some(2.0),
+            ..input(0.0, 0.0)
+        };
+        assert_eq!(pid.update(&gains, &input, 0.2), -2.0);
+    }
+
+    #[test]
+    fn test_derivative_filter() {
+        let gains = PidGains {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 1.0,
+            derivative_filter_time: 0.8,
+            ..Default::default()
+        };
+        let mut pid = Pid::default();
+
+        pid.update(&gains, &input(0.0, 0.0), 0.2);
+        let output: f64 = pid.update(&gains, &input(0.0, 0.2), 0.2);
+
+        // a fifth of the step in the rate gets through after one sample
+        assert!((output - -0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bumpless_initialization_and_reset() {
+        let mut pid = Pid::default();
+        pid.initialize(0.6);
+
+        let input = PidInput {
+            feedforward: 0.3,
+            ..input(0.1, 0.0)
+        };
+
+        // the first output equals the initial output, the integral absorbs the difference
+        assert!((pid.update(&gains(), &input, 0.2) - 0.6).abs() < 1e-9);
+        assert!(!pid.bumpless);
+
+        pid.reset();
+        assert_eq!(pid, Pid::default());
+    }
+}
@@ -0,0 +1,199 @@
+use serde::Serialize;
+
+use crate::modes::ModeChannel;
+use crate::types::{AutoPilotConstants, CommandType};
+
+// number of override events kept in the state
+pub(super) const OVERRIDE_LOG_LENGTH: usize = 20;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OverrideEvent {
+    // [ms]
+    pub timestamp: i64,
+    pub channel: ModeChannel,
+    // what the autopilot commanded, and where the yoke and the control surface were [-1, 1]
+    pub command: f64,
+    pub yoke: f64,
+    pub surface: f64,
+}
+
+// the last command the autopilot sent on every axis it flies
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq)]
+pub(super) struct LastCommands {
+    pub last_aileron_command: Option<f64>,
+    pub last_elevator_command: Option<f64>,
+}
+
+impl LastCommands {
+    pub(super) fn record(&mut self, command_type: CommandType, value: f64) {
+        match command_type {
+            CommandType::Aileron => self.last_aileron_command = Some(value),
+            CommandType::Elevator => self.last_elevator_command = Some(value),
+            CommandType::Throttle => {}
+        }
+    }
+}
+
+// one axis as the autopilot and the sim see it, the command is none when the autopilot does not fly the axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct AxisInput {
+    pub command: Option<f64>,
+    pub yoke: Option<f64>,
+    pub surface: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct OverrideLimits {
+    pub threshold: f64,
+    // [s]
+    pub time: f64,
+}
+
+impl OverrideLimits {
+    pub(super) fn from_constants(constants: &AutoPilotConstants) -> Self {
+        OverrideLimits {
+            threshold: constants.pilot_override_threshold,
+            time: constants.pilot_override_time,
+        }
+    }
+}
+
+// how far the yoke and the surface are from the last command, and for how long that is more than the threshold [s].
+// both have to disagree, so the surface lagging behind the command is not an override
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(super) struct PilotOverride {
+    pub override_aileron_disagreement: f64,
+    pub override_aileron_time: f64,
+    pub override_elevator_disagreement: f64,
+    pub override_elevator_time: f64,
+}
+
+impl PilotOverride {
+    // returns the channels the pilot has taken over
+    pub(super) fn update(
+        &mut self,
+        limits: &OverrideLimits,
+        aileron: &AxisInput,
+        elevator: &AxisInput,
+        dt: f64,
+    ) -> Vec<ModeChannel> {
+        let mut channels: Vec<ModeChannel> = Vec::new();
+
+        if watch_axis(
+            &mut self.override_aileron_disagreement,
+            &mut self.override_aileron_time,
+            limits,
+            aileron,
+            dt,
+        ) {
+            channels.push(ModeChannel::Lateral);
+        }
+
+        if watch_axis(
+            &mut self.override_elevator_disagreement,
+            &mut self.override_elevator_time,
+            limits,
+            elevator,
+            dt,
+        ) {
+            channels.push(ModeChannel::Vertical);
+        }
+
+        channels
+    }
+}
+
+fn watch_axis(
+    disagreement: &mut f64,
+    time: &mut f64,
+    limits: &OverrideLimits,
+    input: &AxisInput,
+    dt: f64,
+) -> bool {
+    let (Some(command), Some(yoke), Some(surface)) = (input.command, input.yoke, input.surface)
+    else {
+        *disagreement = 0.0;
+        *time = 0.0;
+        return false;
+    };
+
+    *disagreement = (yoke - command).abs().min((surface - command).abs());
+    *time = match *disagreement > limits.threshold {
+        true => *time + dt,
+        false => 0.0,
+    };
+
+    *time >= limits.time
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: OverrideLimits = OverrideLimits {
+        threshold: 0.15,
+        time: 0.5,
+    };
+
+    const FOLLOWING: AxisInput = AxisInput {
+        command: Some(0.1),
+        yoke: Some(0.1),
+        surface: Some(0.08),
+    };
+
+    #[test]
+    fn test_sustained_disagreement_is_an_override() {
+        let mut pilot_override: PilotOverride = PilotOverride::default();
+        let pulled = AxisInput {
+            yoke: Some(0.6),
+            surface: Some(0.5),
+            ..FOLLOWING
+        };
+
+        assert!(pilot_override.update(&LIMITS, &FOLLOWING, &pulled, 0.2).is_empty());
+        assert!(pilot_override.update(&LIMITS, &FOLLOWING, &pulled, 0.2).is_empty());
+        assert_eq!(
+            pilot_override.update(&LIMITS, &FOLLOWING, &pulled, 0.2),
+            vec![ModeChannel::Vertical]
+        );
+        assert!((pilot_override.override_elevator_disagreement - 0.4).abs() < 1e-12);
+        assert_eq!(pilot_override.override_aileron_time, 0.0);
+    }
+
+    #[test]
+    fn test_short_or_partial_disagreement_is_not() {
+        let mut pilot_override: PilotOverride = PilotOverride::default();
+
+        // the surface still moving to the command
+        let lagging = AxisInput {
+            surface: Some(-0.4),
+            ..FOLLOWING
+        };
+        let bumped = AxisInput {
+            yoke: Some(-0.5),
+            surface: Some(-0.5),
+            ..FOLLOWING
+        };
+
+        for _ in 0..10 {
+            assert!(pilot_override.update(&LIMITS, &lagging, &FOLLOWING, 0.2).is_empty());
+        }
+
+        assert!(pilot_override.update(&LIMITS, &bumped, &FOLLOWING, 0.2).is_empty());
+        assert!(pilot_override.update(&LIMITS, &FOLLOWING, &FOLLOWING, 0.2).is_empty());
+        assert_eq!(pilot_override.override_aileron_time, 0.0);
+    }
+
+    #[test]
+    fn test_axis_not_flown() {
+        let mut pilot_override: PilotOverride = PilotOverride::default();
+        let not_flown = AxisInput {
+            command: None,
+            yoke: Some(0.9),
+            surface: Some(0.9),
+        };
+
+        assert!(pilot_override.update(&LIMITS, &not_flown, &not_flown, 5.0).is_empty());
+        assert_eq!(pilot_override, PilotOverride::default());
+    }
+}
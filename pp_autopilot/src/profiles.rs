@@ -0,0 +1,370 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Duration;
+use tracing::{event, Level};
+
+use crate::constantswatcher;
+use crate::types::{AppStateProxy, AutoPilotConstants};
+
+// used when PROFILES_PATH is not set
+const DEFAULT_PROFILES_PATH: &str = "./profiles";
+
+// how often the aircraft in the sim is checked when profiles are selected automatically
+const AUTO_SELECT_INTERVAL: Duration = Duration::from_millis(1000);
+
+// indicated airspeeds [kt]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub(super) struct VSpeeds {
+    // stall speed, clean
+    pub vs: f64,
+    // max flaps extended
+    pub vfe: f64,
+    // never exceed
+    pub vne: f64,
+}
+
+// the constants that differ for an aircraft. they are applied on top of the constants file, and the
+// v-speeds set the speed limits of the envelope protection
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub(super) struct AircraftProfile {
+    // the name of the file, without .json
+    #[serde(skip_deserializing, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // the ICAO types the profile is selected for automatically, e.g. C172
+    #[serde(default)]
+    pub aircraft: Vec<String>,
+    pub v_speeds: VSpeeds,
+    #[serde(default)]
+    pub constants: serde_json::Map<String, Value>,
+}
+
+impl AircraftProfile {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let mut file = File::open(path)
+            .with_context(|| format!("cannot open {}", path.display()))?;
+        let mut data = String::new();
+        file.read_to_string(&mut data)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+
+        let mut profile: AircraftProfile = serde_json::from_str(&data)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        profile.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let v: VSpeeds = profile.v_speeds;
+        if !(0.0 < v.vs && v.vs < v.vfe && v.vfe <= v.vne && v.vne.is_finite()) {
+            return Err(anyhow!(
+                "{}: v-speeds must be 0 < vs ({}) < vfe ({}) <= vne ({})",
+                path.display(),
+                v.vs,
+                v.vfe,
+                v.vne
+            ));
+        }
+
+        Ok(profile)
+    }
+
+    // the name is left out, it comes from the file name
+    pub fn to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let profile = AircraftProfile {
+            name: String::new(),
+            ..self.clone()
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut data, formatter);
+        profile.serialize(&mut serializer)?;
+
+        let mut file = File::create(path)
+            .with_context(|| format!("cannot create {}", path.display()))?;
+        file.write_all(&data)
+            .with_context(|| format!("cannot write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    // the constants for this aircraft, only returned when they are valid
+    pub fn apply(&self, base: &AutoPilotConstants) -> anyhow::Result<AutoPilotConstants> {
+        let mut patch: serde_json::Map<String, Value> = self.constants.clone();
+        patch.insert("envelope_speed_min".to_string(), self.v_speeds.vs.into());
+        patch.insert("envelope_speed_max".to_string(), self.v_speeds.vne.into());
+
+        base.patched(&patch)
+            .with_context(|| format!("profile {} does not apply", self.name))
+    }
+
+    // keeps the constants that differ from the constants file, so they can be saved in the profile. a
+    // constant that is set back to the constants file is no difference anymore. the speed limits come
+    // from the v-speeds
+    pub fn keep_changes(&mut self, base: &AutoPilotConstants, constants: &AutoPilotConstants) {
+        let Ok(Value::Object(json)) = serde_json::to_value(constants) else {
+            return;
+        };
+
+        let changed: Vec<String> = constantswatcher::changed_constants(base, constants);
+        self.constants.retain(|name, _| changed.contains(name));
+
+        for name in changed {
+            if let Some(value) = json.get(&name) {
+                self.constants.insert(name, value.clone());
+            }
+        }
+
+        self.constants.remove("envelope_speed_min");
+        self.constants.remove("envelope_speed_max");
+    }
+
+    fn flies(&self, aircraft: &str) -> bool {
+        self.aircraft.iter().any(|a| a.eq_ignore_ascii_case(aircraft))
+    }
+}
+
+// which profile is in effect, and the aircraft the sim reports
+#[derive(Debug, Default, Serialize, Clone)]
+pub(super) struct ProfileStatus {
+    // empty when only the constants file is used
+    pub aircraft_profile: String,
+    pub aircraft_profile_auto: bool,
+    pub aircraft_reported: String,
+    pub aircraft_vs: Option<f64>,
+    pub aircraft_vfe: Option<f64>,
+    pub aircraft_vne: Option<f64>,
+}
+
+pub(super) fn profiles_path() -> PathBuf {
+    PathBuf::from(
+        std::env::var("PROFILES_PATH").unwrap_or_else(|_| DEFAULT_PROFILES_PATH.to_string()),
+    )
+}
+
+// the file of a profile, names are kept to plain characters so they stay inside the directory
+pub(super) fn profile_file(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(anyhow!("{} is not a valid profile name", name));
+    }
+
+    Ok(profiles_path().join(format!("{}.json", name)))
+}
+
+// all profiles that can be read, sorted by name
+pub(super) fn list_profiles() -> anyhow::Result<Vec<AircraftProfile>> {
+    let directory: PathBuf = profiles_path();
+    let entries = std::fs::read_dir(&directory)
+        .with_context(|| format!("cannot read {}", directory.display()))?;
+
+    let mut profiles: Vec<AircraftProfile> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "json"))
+        .filter_map(|path| match AircraftProfile::from_file(&path) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                event!(Level::WARN, "Profile skipped: {:#}", e);
+                None
+            }
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(profiles)
+}
+
+// the constants file with the profile on top, or the constants file alone when no profile is given
+pub(super) fn load_constants(
+    profile: Option<&AircraftProfile>,
+) -> anyhow::Result<(AutoPilotConstants, String)> {
+    let path: PathBuf = constantswatcher::constants_path();
+    let base: AutoPilotConstants = AutoPilotConstants::from_file(&path)?;
+
+    match profile {
+        Some(profile) => Ok((
+            profile.apply(&base)?,
+            format!("{} with profile {}", path.display(), profile.name),
+        )),
+        None => Ok((base, path.display().to_string())),
+    }
+}
+
+// saves the constants that differ from the constants file in the profile, returns where they went
+pub(super) fn save_to_profile(
+    name: &str,
+    constants: &AutoPilotConstants,
+) -> anyhow::Result<PathBuf> {
+    let path: PathBuf = profile_file(name)?;
+    let base: AutoPilotConstants =
+        AutoPilotConstants::from_file(&constantswatcher::constants_path())?;
+
+    let mut profile: AircraftProfile = AircraftProfile::from_file(&path)?;
+    profile.keep_changes(&base, constants);
+    profile.to_file(&path)?;
+
+    Ok(path)
+}
+
+// makes a profile the one in effect, no name goes back to the constants file alone
+pub(super) async fn select_profile(
+    app_state_proxy: &AppStateProxy,
+    name: Option<&str>,
+) -> anyhow::Result<()> {
+    let profile: Option<AircraftProfile> = match name {
+        Some(name) => Some(AircraftProfile::from_file(&profile_file(name)?)?),
+        None => None,
+    };
+
+    let (constants, source) = load_constants(profile.as_ref())?;
+
+    app_state_proxy.set_aircraft_profile(profile.as_ref()).await?;
+    app_state_proxy
+        .set_autopilot_constants(constants, &source)
+        .await?;
+
+    event!(
+        Level::INFO,
+        "Aircraft profile selected: {}",
+        name.unwrap_or("none")
+    );
+
+    Ok(())
+}
+
+// selects the profile for the aircraft in the sim when it changes, when that is switched on.
+// a profile picked by hand stays until another aircraft is loaded
+pub(super) async fn auto_select_profile(app_state_proxy: AppStateProxy) -> anyhow::Result<()> {
+    let mut handled: String = String::new();
+
+    loop {
+        tokio::time::sleep(AUTO_SELECT_INTERVAL).await;
+
+        let status: ProfileStatus = app_state_proxy.get_auto_pilot_state().await?.profile_status;
+        let reported: String = status.aircraft_reported;
+
+        if !status.aircraft_profile_auto || reported.is_empty() || reported == handled {
+            continue;
+        }
+        handled = reported.clone();
+
+        let profile: Option<AircraftProfile> = match list_profiles() {
+            Ok(profiles) => profiles.into_iter().find(|p| p.flies(&reported)),
+            Err(e) => {
+                event!(Level::WARN, "Cannot list the aircraft profiles: {:#}", e);
+                continue;
+            }
+        };
+
+        match profile {
+            Some(profile) if profile.name == status.aircraft_profile => {}
+            Some(profile) => {
+                if let Err(e) = select_profile(&app_state_proxy, Some(&profile.name)).await {
+                    event!(Level::ERROR, "Cannot select profile {}: {:#}", profile.name, e);
+                }
+            }
+            None => event!(Level::WARN, "There is no profile for aircraft {}", reported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> AircraftProfile {
+        AircraftProfile {
+            name: "test".to_string(),
+            description: String::new(),
+            aircraft: vec!["BE9L".to_string()],
+            v_speeds: VSpeeds {
+                vs: 80.0,
+                vfe: 150.0,
+                vne: 225.0,
+            },
+            constants: serde_json::json!({"roll_p": 0.004})
+                .as_object()
+                .unwrap()
+                .clone(),
+        }
+    }
+
+    #[test]
+    fn test_repository_profiles_apply() {
+        let directory: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("../profiles");
+        let base: AutoPilotConstants = AutoPilotConstants::from_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("../constants.json"),
+        )
+        .unwrap();
+
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let profile: AircraftProfile =
+                AircraftProfile::from_file(&entry.unwrap().path()).unwrap();
+
+            assert!(profile.apply(&base).is_ok(), "{}", profile.name);
+
+            // a constant that has a gain schedule is never flown, the schedule replaces it
+            if let Some(Value::Object(schedules)) = profile.constants.get("gain_schedules") {
+                for name in schedules.keys() {
+                    assert!(!profile.constants.contains_key(name), "{} {}", profile.name, name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_profile_on_top_of_the_constants() {
+        let base: AutoPilotConstants = AutoPilotConstants::new();
+        let constants: AutoPilotConstants = profile().apply(&base).unwrap();
+
+        assert_eq!(constants.roll_p, 0.004);
+        assert_eq!(constants.envelope_speed_min, 80.0);
+        assert_eq!(constants.envelope_speed_max, 225.0);
+        assert_eq!(constants.elevator_p, base.elevator_p);
+
+        assert!(profile().flies("be9l"));
+        assert!(!profile().flies("C172"));
+    }
+
+    #[test]
+    fn test_changes_kept_in_the_profile() {
+        let base: AutoPilotConstants = AutoPilotConstants::new();
+        let mut profile: AircraftProfile = profile();
+        let constants = AutoPilotConstants {
+            elevator_p: base.elevator_p * 2.0,
+            ..profile.apply(&base).unwrap()
+        };
+
+        profile.keep_changes(&base, &constants);
+
+        let mut names: Vec<&String> = profile.constants.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["elevator_p", "roll_p"]);
+
+        // roll_p set back to the constants file is no longer kept
+        let constants = AutoPilotConstants {
+            roll_p: base.roll_p,
+            ..constants
+        };
+        profile.keep_changes(&base, &constants);
+
+        let names: Vec<&String> = profile.constants.keys().collect();
+        assert_eq!(names, vec!["elevator_p"]);
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(profile_file("king_air-c90").is_ok());
+        assert!(profile_file("../constants").is_err());
+        assert!(profile_file("").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use axum::response::sse::Event;
+use futures::stream::Stream;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use tracing::{event, Level};
+
+use crate::types::AppStateProxy;
+
+// the state is pushed at most this often when no interval is asked for
+const DEFAULT_INTERVAL_MS: u64 = 200;
+
+// and never more often than this, the metrics change every cycle
+const MIN_INTERVAL_MS: u64 = 50;
+
+// e.g. /api/v1/autopilot_state/stream?diff=true&interval_ms=100
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+pub(super) struct StreamParams {
+    // only the values that changed since the previous event, after a first full state
+    #[serde(default)]
+    pub diff: bool,
+    pub interval_ms: Option<u64>,
+}
+
+impl StreamParams {
+    fn interval(&self) -> Duration {
+        Duration::from_millis(
+            self.interval_ms
+                .unwrap_or(DEFAULT_INTERVAL_MS)
+                .max(MIN_INTERVAL_MS),
+        )
+    }
+}
+
+// the top level values of the state that are new or differ from the previous state. the state is
+// flattened, so these are the modes, setpoints and metrics themselves
+pub(super) fn state_diff(
+    previous: &Map<String, Value>,
+    state: &Map<String, Value>,
+) -> Map<String, Value> {
+    state
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+// the event for a new state, none when nothing a client would see changed. a "state" event has
+// the whole state, a "diff" event the values that changed
+fn state_event(
+    previous: Option<&Map<String, Value>>,
+    state: &Map<String, Value>,
+    diff: bool,
+) -> Option<(&'static str, Map<String, Value>)> {
+    match previous {
+        None => Some(("state", state.clone())),
+        Some(previous) if previous == state => None,
+        Some(previous) if diff => Some(("diff", state_diff(previous, state))),
+        Some(_) => Some(("state", state.clone())),
+    }
+}
+
+struct Subscriber {
+    app_state_proxy: AppStateProxy,
+    changes: watch::Receiver<u64>,
+    params: StreamParams,
+    previous: Option<Map<String, Value>>,
+    last_sent: Option<Instant>,
+}
+
+// pushes the autopilot state whenever it changes, at most once per interval
+pub(super) fn stream_state(
+    app_state_proxy: AppStateProxy,
+    params: StreamParams,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    let subscriber = Subscriber {
+        changes: app_state_proxy.subscribe_to_changes(),
+        app_state_proxy,
+        params,
+        previous: None,
+        last_sent: None,
+    };
+
+    futures::stream::unfold(subscriber, |mut subscriber| async move {
+        loop {
+            // the changes in between are taken together in the next event
+            if let Some(last_sent) = subscriber.last_sent {
+                tokio::time::sleep_until(last_sent + subscriber.params.interval()).await;
+            }
+
+            // the first state goes out straight away
+            if subscriber.previous.is_some() {
+                if subscriber.changes.changed().await.is_err() {
+                    return None;
+                }
+            } else {
+                subscriber.changes.mark_unchanged();
+            }
+
+            let state: Map<String, Value> =
+                match subscriber.app_state_proxy.get_auto_pilot_state().await {
+                    Ok(state) => match serde_json::to_value(state) {
+                        Ok(Value::Object(state)) => state,
+                        _ => return None,
+                    },
+                    Err(e) => {
+                        event!(Level::ERROR, "State stream closed: {:?}", e);
+                        return None;
+                    }
+                };
+
+            let Some((name, data)) =
+                state_event(subscriber.previous.as_ref(), &state, subscriber.params.diff)
+            else {
+                continue;
+            };
+
+            subscriber.previous = Some(state);
+            subscriber.last_sent = Some(Instant::now());
+
+            return Some((Event::default().event(name).json_data(data), subscriber));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let previous = state(serde_json::json!({
+            "horizontal_mode": "Heading",
+            "standby_heading": 90.0,
+            "armed_modes": [],
+        }));
+        let next = state(serde_json::json!({
+            "horizontal_mode": "LNAV",
+            "standby_heading": 90.0,
+            "armed_modes": [],
+            "aircraft_profile": "c172",
+        }));
+
+        assert_eq!(
+            state_diff(&previous, &next),
+            state(serde_json::json!({"horizontal_mode": "LNAV", "aircraft_profile": "c172"}))
+        );
+        assert!(state_diff(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn test_state_events() {
+        let previous = state(serde_json::json!({"vertical_mode": "VS", "standby_vs": -500}));
+        let next = state(serde_json::json!({"vertical_mode": "VS", "standby_vs": -700}));
+
+        // a new subscriber starts with the whole state, also when it asked for diffs
+        assert_eq!(state_event(None, &next, true), Some(("state", next.clone())));
+
+        assert_eq!(
+            state_event(Some(&previous), &next, true),
+            Some(("diff", state(serde_json::json!({"standby_vs": -700}))))
+        );
+        assert_eq!(state_event(Some(&previous), &next, false), Some(("state", next.clone())));
+
+        // nothing is sent when nothing changed
+        assert_eq!(state_event(Some(&next), &next, true), None);
+        assert_eq!(state_event(Some(&next), &next, false), None);
+    }
+
+    #[test]
+    fn test_stream_interval() {
+        assert_eq!(StreamParams::default().interval(), Duration::from_millis(DEFAULT_INTERVAL_MS));
+
+        let params: StreamParams = StreamParams {
+            diff: true,
+            interval_ms: Some(0),
+        };
+        assert_eq!(params.interval(), Duration::from_millis(MIN_INTERVAL_MS));
+    }
+}
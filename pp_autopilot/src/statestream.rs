@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tokio::{sync::watch, time::Duration};
+use tracing::{event, Level};
+
+use crate::types::{AppStateProxy, SpecificErrors};
+
+// wait this long before connecting again when the stream is closed or the planeconnector is not there
+const RECONNECT_DELAY: Duration = Duration::from_millis(1000);
+
+// splits a server sent event stream into the data of the events, chunks can end anywhere in a line
+#[derive(Debug, Default)]
+pub(super) struct EventParser {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl EventParser {
+    pub(super) fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events: Vec<String> = Vec::new();
+        self.buffer.extend_from_slice(chunk);
+
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line: String = String::from_utf8_lossy(&line)
+                .trim_end_matches(['\n', '\r'])
+                .to_string();
+
+            if line.is_empty() {
+                // an empty line ends the event
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+            // comments (the keep alives) and the other fields are not used
+        }
+
+        events
+    }
+}
+
+// subscribes to the plane state stream of the planeconnector, and hands every new sample to the control loop.
+// the receiver only keeps the most recent sample, so a slow loop never works on an old one
+pub(super) async fn listen_to_plane_state(
+    app_state_proxy: AppStateProxy,
+    sample_sender: watch::Sender<BTreeMap<String, Value>>,
+) -> anyhow::Result<()> {
+    let client: reqwest::Client = reqwest::Client::new();
+
+    loop {
+        match stream_samples(&app_state_proxy, &client, &sample_sender).await {
+            Ok(()) => event!(Level::INFO, "Plane state stream closed by the planeconnector"),
+            Err(e) => event!(Level::DEBUG, "Cannot stream the plane state: {:?}", e),
+        }
+
+        let _ = tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn stream_samples(
+    app_state_proxy: &AppStateProxy,
+    client: &reqwest::Client,
+    sample_sender: &watch::Sender<BTreeMap<String, Value>>,
+) -> anyhow::Result<()> {
+    let mut res = match client
+        .get(app_state_proxy.service_adresses.1.to_owned() + "/state/stream")
+        .send()
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return Err(anyhow::Error::new(
+                SpecificErrors::PlaneConnectorNotReachable,
+            ))
+        }
+    };
+
+    if res.status() != reqwest::StatusCode::OK {
+        return Err(anyhow::Error::new(
+            SpecificErrors::PlaneConnectorReturnedError,
+        ));
+    }
+
+    event!(Level::INFO, "Subscribed to the plane state stream");
+
+    let mut parser: EventParser = EventParser::default();
+
+    while let Some(chunk) = res.chunk().await? {
+        for data in parser.push(&chunk) {
+            match serde_json::from_str::<BTreeMap<String, Value>>(&data) {
+                Ok(sample) if sample.contains_key("last_updated_timestamp") => {
+                    sample_sender.send_replace(sample);
+                }
+                Ok(_) => {}
+                Err(e) => event!(Level::WARN, "Cannot parse plane state sample: {:?}", e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_split_over_chunks() {
+        let mut parser: EventParser = EventParser::default();
+
+        assert!(parser.push(b"data: {\"roll\":").is_empty());
+        assert!(parser.push(b"1.5}\n").is_empty());
+        assert_eq!(parser.push(b"\ndata: {}\n\n"), vec!["{\"roll\":1.5}", "{}"]);
+    }
+
+    #[test]
+    fn test_comments_and_line_endings() {
+        let mut parser: EventParser = EventParser::default();
+
+        // keep alives are comments, and do not make an event
+        assert!(parser.push(b":\n\n").is_empty());
+        assert_eq!(parser.push(b"event: state\r\ndata:1\r\ndata: 2\r\n\r\n"), vec!["1\n2"]);
+    }
+}
@@ -1,16 +1,37 @@
 use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
+use tracing::{event, Level};
+
+use crate::api;
+use crate::altitudecapture::{AltitudeAlert, AltitudeCaptureState};
+use crate::autothrottle::{self, ThrustLimit};
+use crate::constantswatcher::{self, ConstantsStatus, ConstantsVersion};
+use crate::envelope::EnvelopeProtection;
+use crate::flightdirector::{self, FlightDirector, FlightDirectorCues};
+use crate::flightplan::{AltitudeConstraint, FlightPlan, Waypoint};
+use crate::gainschedule::GainSchedule;
+use crate::holding::{HoldSetup, HoldingPattern};
+use crate::looptiming::{LoopClock, LoopTiming, LoopTrigger, MissedTick};
+use crate::modes::{self, FlightModeAnnunciation, ModeChannel, ModeConditions, ModeTransition};
+use crate::navigation::{self, BankLimit, HeadingReference, Position, TurnDirection};
+use crate::orbit::OrbitSetup;
+use crate::pid::Pid;
+use crate::pilotoverride::{self, LastCommands, OverrideEvent, PilotOverride};
+use crate::profiles::{AircraftProfile, ProfileStatus, VSpeeds};
+use crate::verticalprofile::VerticalProfile;
 
 #[derive(Debug)]
 pub(super) enum SpecificErrors {
@@ -32,22 +53,103 @@ impl std::fmt::Display for SpecificErrors {
 }
 
 impl std::error::Error for SpecificErrors {}
+
+// reason logged for the mode changes that come in through the api
+const PILOT_SELECTED: &str = "selected by the pilot";
 pub(super) struct AppState {
     receiver: mpsc::Receiver<StateSignal>,
     auto_pilot_state: AutoPilotState,
     plane_state: BTreeMap<String, Value>,
+    // the constants that took effect, oldest first
+    constants_history: VecDeque<ConstantsVersion>,
+    // counts the changes to the state, so the state streams know when to push it
+    state_changes: watch::Sender<u64>,
 }
 
 pub(super) struct PlaneStateStruct {
     pub v_ind: f64,
+    pub v_true: f64,
     pub altitude_msl: f64,
     pub vpath: f64,
     pub roll: f64,
     pub roll_rate: f64,
+    pub yaw_rate: f64,
     pub pitch: f64,
     pub pitch_rate: f64,
     pub gload_axial: f64,
+    pub gload_normal: f64,
+    pub alpha: f64,
     pub heading: f64,
+    pub heading_magnetic: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub hpath: f64,
+    pub v_ground: f64,
+    pub vertical_speed: f64,
+    pub altitude_agl: f64,
+    pub on_runway: bool,
+    pub throttle: f64,
+    // xplane's flight time, when the sim sends the times row [s]
+    pub sim_time: Option<f64>,
+    // the yoke and the control surfaces [-1, 1], when the sim sends them
+    pub aileron_commanded: Option<f64>,
+    pub elevator_commanded: Option<f64>,
+    pub aileron_actual: Option<f64>,
+    pub elevator_actual: Option<f64>,
+}
+
+impl PlaneStateStruct {
+    // a DATA row that is not enabled in xplane, or a partial packet, is an error for the cycle instead of a
+    // panic in the state. the yoke and the control surfaces are optional
+    fn from_plane_state(plane_state: &BTreeMap<String, Value>) -> anyhow::Result<Self> {
+        let value = |key: &str| -> anyhow::Result<f64> {
+            plane_state
+                .get(key)
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| anyhow!("the plane state has no {}", key))
+        };
+
+        Ok(PlaneStateStruct {
+            v_ind: value("Vind")?,
+            v_true: value("Vtrue")?,
+            altitude_msl: value("altitude_msl")?,
+            vpath: value("vpath")?,
+            roll: value("roll")?,
+            roll_rate: value("P")?,
+            yaw_rate: value("R")?,
+            pitch: value("pitch")?,
+            pitch_rate: value("Q")?,
+            gload_axial: value("Gload_axial")?,
+            gload_normal: value("Gload_normal")?,
+            alpha: value("alpha")?,
+            heading: value("heading_true")?,
+            heading_magnetic: value("heading_magnetic")?,
+            latitude: value("latitude")?,
+            longitude: value("longitude")?,
+            hpath: value("hpath")?,
+            v_ground: value("Vground")?,
+            vertical_speed: value("VVI")?,
+            altitude_agl: value("altitude_agl")?,
+            on_runway: plane_state
+                .get("on_runway")
+                .and_then(on_runway)
+                .ok_or_else(|| anyhow!("the plane state has no on_runway"))?,
+            throttle: value("throttle_1_actual")?,
+            sim_time: plane_state.get("sim_time").and_then(|v| v.as_f64()),
+            aileron_commanded: plane_state.get("aileron_commanded").and_then(|v| v.as_f64()),
+            elevator_commanded: plane_state.get("elevator_commanded").and_then(|v| v.as_f64()),
+            aileron_actual: plane_state.get("aileron_actual").and_then(|v| v.as_f64()),
+            elevator_actual: plane_state.get("elevator_actual").and_then(|v| v.as_f64()),
+        })
+    }
+}
+
+// the planeconnector sends on_runway as a bool, a number is taken as xplane's 0 or 1
+fn on_runway(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(on_runway) => Some(*on_runway),
+        value => value.as_f64().map(|v| v > 0.5),
+    }
 }
 
 #[derive(Debug, Default, Serialize, Clone)]
@@ -58,11 +160,53 @@ pub(super) struct AutoPilotState {
     #[serde(flatten)]
     pub horizontal_guidance: HorizontalGuidance,
     #[serde(flatten)]
+    pub autothrottle: AutoThrottle,
+    #[serde(flatten)]
+    pub flight_plan: FlightPlan,
+    #[serde(flatten)]
+    pub holding_pattern: HoldingPattern,
+    #[serde(flatten)]
+    pub vertical_profile: VerticalProfile,
+    #[serde(flatten)]
     pub control_constants: AutoPilotConstants,
     #[serde(flatten)]
     pub horizontal_control_metrics: AutoPilotHorizontalMetrics,
     #[serde(flatten)]
     pub vertical_control_metrics: AutoPilotVerticalMetrics,
+    #[serde(flatten)]
+    pub loop_timing: LoopTiming,
+    #[serde(flatten)]
+    pub envelope_protection: EnvelopeProtection,
+    #[serde(flatten)]
+    pub disconnect: AutoPilotDisconnect,
+    #[serde(flatten)]
+    pub constants_status: ConstantsStatus,
+    #[serde(flatten)]
+    pub profile_status: ProfileStatus,
+    #[serde(flatten)]
+    pub last_commands: LastCommands,
+    #[serde(flatten)]
+    pub pilot_override: PilotOverride,
+    #[serde(flatten)]
+    pub flight_director: FlightDirector,
+    // the channels the pilot took over, most recent last
+    pub override_count: u64,
+    pub override_events: VecDeque<OverrideEvent>,
+    // the gains interpolated from the gain schedules for the current airspeed and altitude
+    pub scheduled_gains: BTreeMap<String, f64>,
+    pub fma: FlightModeAnnunciation,
+    // the most recent mode changes, oldest first
+    pub mode_transitions: VecDeque<ModeTransition>,
+}
+
+// why the autopilot disconnected itself, the alert stays until the pilot acknowledges it
+#[derive(Debug, Default, Serialize, Clone)]
+pub(super) struct AutoPilotDisconnect {
+    pub disconnect_alert: bool,
+    pub disconnect_reason: String,
+    // [ms]
+    pub disconnect_timestamp: i64,
+    pub disconnect_count: u64,
 }
 
 #[derive(Debug, Default, Serialize, Clone)]
@@ -87,6 +231,17 @@ pub(super) struct AutoPilotVerticalMetrics {
     pub pitch_rate_target: f64,
     pub pitch_rate_error: f64,
     pub elevator_setpoint: f64,
+    pub vertical_speed: f64,
+    pub vertical_speed_target: f64,
+    pub vertical_speed_error: f64,
+    pub throttle_setpoint: f64,
+    pub flight_path: f64,
+    pub flight_path_target: f64,
+    pub acceleration: f64,
+    pub acceleration_target: f64,
+    pub energy_rate_error: f64,
+    pub energy_distribution_error: f64,
+    pub throttle_saturated: bool,
 }
 
 #[derive(Debug, Default, Serialize, Clone)]
@@ -101,11 +256,29 @@ pub(super) struct AutoPilotHorizontalMetrics {
     pub roll_angle_rate_target: f64,
     pub roll_angle_rate_error: f64,
     pub aileron_setpoint: f64,
+    pub roll_angle_limit: f64,
+    pub track: f64,
+    pub desired_track: f64,
+    pub track_angle_error: f64,
+    pub cross_track_error: f64,
+    pub distance_to_go: f64,
+    pub active_leg: usize,
+    pub crab_angle: f64,
+    // the bank added to the output of the heading loop, for the turn of an orbit
+    pub roll_angle_feedforward: f64,
+    pub orbit_radial_error: f64,
+    pub orbit_distance_to_center: f64,
+    pub orbit_minimum_radius: f64,
+    pub wind_north: f64,
+    pub wind_east: f64,
+    pub wind_direction: f64,
+    pub wind_speed: f64,
 }
 
 #[derive(Debug, Deserialize, Default, Serialize, Clone)]
 pub(super) struct AutoPilotConstants {
     pub heading_error_p: f64,
+    pub heading_error_i: f64,
     pub heading_roll_error_d: f64,
     pub roll_p: f64,
     pub roll_d: f64,
@@ -114,6 +287,12 @@ pub(super) struct AutoPilotConstants {
     pub tecs_cruise_throttle_base: f64,
     pub tecs_energy_p: f64,
     pub tecs_energy_i: f64,
+    pub tecs_pitch_p: f64,
+    pub tecs_pitch_i: f64,
+    pub tecs_altitude_p: f64,
+    pub tecs_velocity_p: f64,
+    pub tecs_max_flight_path: f64,
+    pub tecs_max_acceleration: f64,
     pub pitch_error_p: f64,
     pub pitch_rate_error_p: f64,
     pub elevator_p: f64,
@@ -125,20 +304,82 @@ pub(super) struct AutoPilotConstants {
     pub max_elevator: f64,
     pub max_pitch: f64,
     pub max_pitch_rate: f64,
+    pub derivative_filter_time: f64,
+    pub lnav_cross_track_p: f64,
+    pub lnav_max_intercept_angle: f64,
+    // how directly the orbit mode turns onto its circle [-]
+    pub orbit_convergence: f64,
+    pub wind_filter_time: f64,
+    pub vnav_climb_gradient: f64,
+    pub vnav_descent_angle: f64,
+    pub vnav_altitude_p: f64,
+    pub speed_throttle_p: f64,
+    pub flch_pitch_p: f64,
+    pub altitude_capture_margin: f64,
+    pub altitude_capture_time: f64,
+    pub altitude_climb_vertical_speed: f64,
+    pub altitude_vertical_acceleration: f64,
+    pub autothrottle_p: f64,
+    pub autothrottle_i: f64,
+    pub autothrottle_max_rate: f64,
+    pub autothrottle_thr_hold_altitude: f64,
+    pub throttle_idle: f64,
+    pub throttle_max_continuous: f64,
+    pub throttle_toga: f64,
+    #[serde(default)]
+    pub loop_trigger: LoopTrigger,
+    // control loop frequency [Hz] when triggered by the interval
+    pub loop_rate: f64,
+    #[serde(default)]
+    pub loop_missed_tick: MissedTick,
+    #[serde(default)]
+    pub loop_clock: LoopClock,
+    // standby when no new plane state arrives within this time [s]
+    pub sample_timeout: f64,
+    // flight envelope, the commands are limited within the margins and a breach of a limit that lasts the
+    // breach time [s] disconnects the autopilot. angles [deg], speeds [kt], load factor [g]
+    pub envelope_alpha_max: f64,
+    pub envelope_alpha_margin: f64,
+    pub envelope_speed_min: f64,
+    pub envelope_speed_max: f64,
+    pub envelope_speed_margin: f64,
+    pub envelope_roll_max: f64,
+    pub envelope_roll_margin: f64,
+    pub envelope_pitch_max: f64,
+    pub envelope_pitch_min: f64,
+    pub envelope_pitch_margin: f64,
+    pub envelope_gload_max: f64,
+    pub envelope_gload_min: f64,
+    pub envelope_gload_margin: f64,
+    pub envelope_breach_time: f64,
+    // the pilot has taken over an axis when the yoke and the control surface are further than the threshold [-]
+    // from the last command of the autopilot, for the override time [s]
+    pub pilot_override_threshold: f64,
+    pub pilot_override_time: f64,
+    // gains that vary with airspeed (and altitude), by constant name
+    #[serde(default)]
+    pub gain_schedules: BTreeMap<String, GainSchedule>,
 }
 
 impl AutoPilotConstants {
     pub fn new() -> Self {
         AutoPilotConstants {
             heading_error_p: 0.4,
+            heading_error_i: 0.0,
             heading_roll_error_d: 0.2,
             roll_p: 0.01,
             roll_d: 0.01,
             roll_i: 0.001,
             tecs_cruise_throttle_slope: 0.0000001,
             tecs_cruise_throttle_base: 0.48,
-            tecs_energy_p: 0.001,
-            tecs_energy_i: 0.001,
+            tecs_energy_p: 2.0,
+            tecs_energy_i: 0.5,
+            tecs_pitch_p: 1.0,
+            tecs_pitch_i: 0.3,
+            tecs_altitude_p: 0.0005,
+            tecs_velocity_p: 0.005,
+            tecs_max_flight_path: 5.0,
+            tecs_max_acceleration: 0.1,
             pitch_error_p: -1.5,
             pitch_rate_error_p: 0.3,
             elevator_p: 0.15,
@@ -150,26 +391,212 @@ impl AutoPilotConstants {
             max_elevator: 0.5,
             max_pitch: 15.0,
             max_pitch_rate: 15.0,
+            derivative_filter_time: 0.0,
+            lnav_cross_track_p: 30.0,
+            lnav_max_intercept_angle: 45.0,
+            orbit_convergence: 2.0,
+            wind_filter_time: 10.0,
+            vnav_climb_gradient: 500.0,
+            vnav_descent_angle: 3.0,
+            vnav_altitude_p: 0.01,
+            speed_throttle_p: 0.02,
+            flch_pitch_p: -0.5,
+            altitude_capture_margin: 50.0,
+            altitude_capture_time: 12.0,
+            altitude_climb_vertical_speed: 1000.0,
+            altitude_vertical_acceleration: 200.0,
+            autothrottle_p: 0.02,
+            autothrottle_i: 0.002,
+            autothrottle_max_rate: 0.2,
+            autothrottle_thr_hold_altitude: 400.0,
+            throttle_idle: 0.0,
+            throttle_max_continuous: 0.9,
+            throttle_toga: 1.0,
+            loop_trigger: LoopTrigger::Sample,
+            loop_rate: 5.0,
+            loop_missed_tick: MissedTick::Skip,
+            loop_clock: LoopClock::Local,
+            sample_timeout: 1.0,
+            envelope_alpha_max: 15.0,
+            envelope_alpha_margin: 3.0,
+            envelope_speed_min: 50.0,
+            envelope_speed_max: 160.0,
+            envelope_speed_margin: 8.0,
+            envelope_roll_max: 45.0,
+            envelope_roll_margin: 10.0,
+            envelope_pitch_max: 25.0,
+            envelope_pitch_min: -20.0,
+            envelope_pitch_margin: 5.0,
+            envelope_gload_max: 3.0,
+            envelope_gload_min: -1.0,
+            envelope_gload_margin: 0.5,
+            envelope_breach_time: 1.0,
+            pilot_override_threshold: 0.15,
+            pilot_override_time: 0.5,
+            gain_schedules: BTreeMap::new(),
         }
     }
 
-    pub fn from_file() -> Self {
-        let path = Path::new("./constants.json");
-        let mut file = File::open(path).unwrap();
+    // reads and validates the constants, nothing is returned unless all of them are good
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let mut file = File::open(path)
+            .with_context(|| format!("cannot open {}", path.display()))?;
         let mut data = String::new();
-        file.read_to_string(&mut data).unwrap();
+        file.read_to_string(&mut data)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+
+        let constants: AutoPilotConstants = serde_json::from_str(&data)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        constants.validate()?;
+
+        Ok(constants)
+    }
+
+    // checks the ranges of the constants, and reports all problems at once so a file can be fixed in one go
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors: Vec<String> = Vec::new();
+
+        let positive: [(&str, f64); 22] = [
+            ("max_aileron", self.max_aileron),
+            ("max_elevator", self.max_elevator),
+            ("max_roll", self.max_roll),
+            ("max_roll_rate", self.max_roll_rate),
+            ("max_pitch", self.max_pitch),
+            ("max_pitch_rate", self.max_pitch_rate),
+            ("tecs_max_flight_path", self.tecs_max_flight_path),
+            ("tecs_max_acceleration", self.tecs_max_acceleration),
+            ("lnav_max_intercept_angle", self.lnav_max_intercept_angle),
+            ("orbit_convergence", self.orbit_convergence),
+            ("vnav_climb_gradient", self.vnav_climb_gradient),
+            ("vnav_descent_angle", self.vnav_descent_angle),
+            ("altitude_capture_time", self.altitude_capture_time),
+            ("altitude_climb_vertical_speed", self.altitude_climb_vertical_speed),
+            ("altitude_vertical_acceleration", self.altitude_vertical_acceleration),
+            ("autothrottle_max_rate", self.autothrottle_max_rate),
+            ("loop_rate", self.loop_rate),
+            ("sample_timeout", self.sample_timeout),
+            ("envelope_alpha_max", self.envelope_alpha_max),
+            ("envelope_speed_min", self.envelope_speed_min),
+            ("envelope_roll_max", self.envelope_roll_max),
+            ("pilot_override_threshold", self.pilot_override_threshold),
+        ];
+
+        let non_negative: [(&str, f64); 11] = [
+            ("derivative_filter_time", self.derivative_filter_time),
+            ("wind_filter_time", self.wind_filter_time),
+            ("altitude_capture_margin", self.altitude_capture_margin),
+            ("autothrottle_thr_hold_altitude", self.autothrottle_thr_hold_altitude),
+            ("envelope_alpha_margin", self.envelope_alpha_margin),
+            ("envelope_speed_margin", self.envelope_speed_margin),
+            ("envelope_roll_margin", self.envelope_roll_margin),
+            ("envelope_pitch_margin", self.envelope_pitch_margin),
+            ("envelope_gload_margin", self.envelope_gload_margin),
+            ("envelope_breach_time", self.envelope_breach_time),
+            ("pilot_override_time", self.pilot_override_time),
+        ];
+
+        // NaN fails every comparison, so it is caught here as well
+        for (name, value) in positive {
+            if !(value > 0.0 && value.is_finite()) {
+                errors.push(format!("{} must be a positive number, is {}", name, value));
+            }
+        }
+
+        for (name, value) in non_negative {
+            if !(value >= 0.0 && value.is_finite()) {
+                errors.push(format!("{} cannot be negative, is {}", name, value));
+            }
+        }
+
+        for (name, value) in [
+            ("max_aileron", self.max_aileron),
+            ("max_elevator", self.max_elevator),
+        ] {
+            if value > 1.0 {
+                errors.push(format!("{} cannot be more than full deflection, is {}", name, value));
+            }
+        }
+
+        if !(0.0 <= self.throttle_idle
+            && self.throttle_idle <= self.throttle_max_continuous
+            && self.throttle_max_continuous <= self.throttle_toga
+            && self.throttle_toga <= 1.0)
+        {
+            errors.push(format!(
+                "throttles must be 0 <= idle ({}) <= max continuous ({}) <= toga ({}) <= 1",
+                self.throttle_idle, self.throttle_max_continuous, self.throttle_toga
+            ));
+        }
+
+        for (name, min, max) in [
+            ("envelope_speed", self.envelope_speed_min, self.envelope_speed_max),
+            ("envelope_pitch", self.envelope_pitch_min, self.envelope_pitch_max),
+            ("envelope_gload", self.envelope_gload_min, self.envelope_gload_max),
+        ] {
+            if min.partial_cmp(&max) != Some(std::cmp::Ordering::Less) {
+                errors.push(format!("{}_min ({}) must be below {}_max ({})", name, min, name, max));
+            }
+        }
+
+        // the autopilot would disconnect itself in its own turns
+        if self.max_roll > self.envelope_roll_max {
+            errors.push(format!(
+                "max_roll ({}) cannot be beyond envelope_roll_max ({})",
+                self.max_roll, self.envelope_roll_max
+            ));
+        }
+
+        // only plain numbers can be scheduled
+        let json: Value = serde_json::to_value(self)?;
+        for (name, schedule) in self.gain_schedules.iter() {
+            if !json.get(name).is_some_and(|v| v.is_number()) {
+                errors.push(format!("gain schedule for {}: there is no such constant", name));
+            } else if let Err(e) = schedule.validate() {
+                errors.push(format!("gain schedule for {}: {}", name, e));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(errors.join("; "))),
+        }
+    }
+
+    // a copy with some of the constants changed, it is only returned when the result is valid
+    pub fn patched(&self, patch: &serde_json::Map<String, Value>) -> anyhow::Result<Self> {
+        let Value::Object(mut json) = serde_json::to_value(self)? else {
+            return Err(anyhow!("constants are not an object"));
+        };
+
+        let unknown: Vec<&str> = patch
+            .keys()
+            .filter(|name| !json.contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(anyhow!("there are no constants named {}", unknown.join(", ")));
+        }
+
+        json.extend(patch.clone());
 
-        let json: AutoPilotConstants = serde_json::from_str(&data).unwrap();
-        json
+        let constants: AutoPilotConstants = serde_json::from_value(Value::Object(json))
+            .context("cannot apply the changes")?;
+        constants.validate()?;
+
+        Ok(constants)
     }
 
-    pub fn _to_file(&self) -> anyhow::Result<()> {
-        let path = Path::new("./constants.json");
-        let mut file = std::fs::File::create(path)?;
-        let list_as_json = serde_json::to_string(self).unwrap();
+    // written the way constants.json is laid out, so a saved file stays readable
+    pub fn to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let mut data: Vec<u8> = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut data, formatter);
+        self.serialize(&mut serializer)?;
 
-        file.write_all(list_as_json.as_bytes())
-            .expect("Cannot write to the file!");
+        let mut file = File::create(path)
+            .with_context(|| format!("cannot create {}", path.display()))?;
+        file.write_all(&data)
+            .with_context(|| format!("cannot write {}", path.display()))?;
 
         Ok(())
     }
@@ -181,50 +608,141 @@ impl AutoPilotState {
             are_we_flying: false,
             vertical_guidance: VerticalGuidance {
                 vertical_mode: VerticalModes::TECS,
+                vertical_mode_armed: None,
                 velocity_setpoint: 100.0,
                 velocity_standby: 80.0,
                 altitude_setpoint: 3100.0,
                 altitude_standby: 3500.0,
-                energy_error_integral: 0.0,
-                pitch_error_integral: 0.0,
+                vertical_speed_setpoint: 500.0,
+                vertical_speed_standby: 1000.0,
+                flch_climb_throttle: 0.9,
+                flch_descent_throttle: 0.2,
+                altitude_capture_state: AltitudeCaptureState::Inactive,
+                altitude_alert: AltitudeAlert::None,
+                vertical_speed_command: 0.0,
+                tecs_priority: TecsPriority::Speed,
+                energy_pid: Pid::default(),
+                distribution_pid: Pid::default(),
+                pitch_pid: Pid::default(),
+                speed_pid: Pid::default(),
+                flch_pid: Pid::default(),
             },
             horizontal_guidance: HorizontalGuidance {
                 horizontal_mode: HorizontalModes::Heading,
+                horizontal_mode_armed: None,
                 heading_setpoint: 90.0,
                 heading_standby: 120.0,
-                heading_error_integral: 0.0,
-                roll_error_integral: 0.0,
+                heading_reference: HeadingReference::True,
+                turn_direction: TurnDirection::Shortest,
+                bank_limit: BankLimit::Auto,
+                orbit_setup: None,
+                heading_pid: Pid::default(),
+                roll_pid: Pid::default(),
+            },
+            autothrottle: AutoThrottle {
+                autothrottle_mode: AutoThrottleModes::Standby,
+                thrust_limit: ThrustLimit::MaximumContinuous,
+                throttle_command: 0.0,
+                autothrottle_pid: Pid::default(),
             },
+            flight_plan: FlightPlan::default(),
+            holding_pattern: HoldingPattern::default(),
+            vertical_profile: VerticalProfile::default(),
             horizontal_control_metrics: AutoPilotHorizontalMetrics::default(),
             vertical_control_metrics: AutoPilotVerticalMetrics::default(),
+            loop_timing: LoopTiming::default(),
+            envelope_protection: EnvelopeProtection::default(),
+            disconnect: AutoPilotDisconnect::default(),
+            constants_status: ConstantsStatus::default(),
+            profile_status: ProfileStatus::default(),
+            last_commands: LastCommands::default(),
+            pilot_override: PilotOverride::default(),
+            flight_director: FlightDirector::default(),
+            override_count: 0,
+            override_events: VecDeque::new(),
             control_constants: AutoPilotConstants::new(),
+            scheduled_gains: BTreeMap::new(),
+            fma: FlightModeAnnunciation::default(),
+            mode_transitions: VecDeque::new(),
         }
     }
 }
 #[derive(Debug, Deserialize, Default, Serialize, Clone)]
 pub struct VerticalGuidance {
     pub vertical_mode: VerticalModes,
+    pub vertical_mode_armed: Option<VerticalModes>,
     pub velocity_setpoint: f64,
     pub velocity_standby: f64,
     pub altitude_setpoint: f64,
     pub altitude_standby: f64,
-    pub energy_error_integral: f64,
-    pub pitch_error_integral: f64,
+    pub vertical_speed_setpoint: f64,
+    pub vertical_speed_standby: f64,
+    pub flch_climb_throttle: f64,
+    pub flch_descent_throttle: f64,
+    pub altitude_capture_state: AltitudeCaptureState,
+    pub altitude_alert: AltitudeAlert,
+    pub vertical_speed_command: f64,
+    pub tecs_priority: TecsPriority,
+    pub energy_pid: Pid,
+    pub distribution_pid: Pid,
+    pub pitch_pid: Pid,
+    // the speed on the throttle in VS
+    pub speed_pid: Pid,
+    // the speed on the elevator in FLCH
+    pub flch_pid: Pid,
 }
 
 #[derive(Debug, Deserialize, Default, Serialize, Clone)]
 pub struct HorizontalGuidance {
     pub horizontal_mode: HorizontalModes,
+    pub horizontal_mode_armed: Option<HorizontalModes>,
     pub heading_setpoint: f64,
     pub heading_standby: f64,
-    pub heading_error_integral: f64,
-    pub roll_error_integral: f64,
+    pub heading_reference: HeadingReference,
+    pub turn_direction: TurnDirection,
+    pub bank_limit: BankLimit,
+    // the circle the orbit mode flies
+    pub orbit_setup: Option<OrbitSetup>,
+    pub heading_pid: Pid,
+    pub roll_pid: Pid,
+}
+
+#[derive(Debug, Deserialize, Default, Serialize, Clone)]
+pub struct AutoThrottle {
+    pub autothrottle_mode: AutoThrottleModes,
+    pub thrust_limit: ThrustLimit,
+    pub throttle_command: f64,
+    pub autothrottle_pid: Pid,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 pub enum VerticalModes {
     Standby,
     TECS,
+    VNAV,
+    VS,
+    FLCH,
+}
+
+// what TECS gives up when the throttle saturates and the total energy cannot be controlled
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum TecsPriority {
+    #[default]
+    Speed,
+    Altitude,
+}
+
+// the control loops with a pid state in the app state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum PidLoop {
+    Heading,
+    Roll,
+    Pitch,
+    Energy,
+    Distribution,
+    Speed,
+    FlightLevelChange,
+    AutoThrottle,
 }
 
 impl Default for VerticalModes {
@@ -233,11 +751,25 @@ impl Default for VerticalModes {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 pub enum HorizontalModes {
     Standby,
     WingsLevel,
     Heading,
+    Track,
+    LNAV,
+    Hold,
+    Orbit,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum AutoThrottleModes {
+    #[default]
+    Standby,
+    #[serde(rename = "SPEED")]
+    Speed,
+    #[serde(rename = "THR HOLD")]
+    ThrHold,
 }
 
 impl Default for HorizontalModes {
@@ -247,17 +779,393 @@ impl Default for HorizontalModes {
 }
 
 impl AppState {
-    pub fn new(rx: mpsc::Receiver<StateSignal>) -> Self {
+    pub fn new(rx: mpsc::Receiver<StateSignal>, state_changes: watch::Sender<u64>) -> Self {
+        let auto_pilot_state: AutoPilotState = AutoPilotState::new();
+
+        // the built in defaults are version 0, so they can be rolled back to as well
+        let constants_history: VecDeque<ConstantsVersion> = VecDeque::from([ConstantsVersion {
+            version: 0,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            source: "defaults".to_string(),
+            changed: Vec::new(),
+            constants: auto_pilot_state.control_constants.clone(),
+        }]);
+
         AppState {
-            auto_pilot_state: AutoPilotState::new(),
+            auto_pilot_state,
             plane_state: BTreeMap::new(),
             receiver: rx,
+            constants_history,
+            state_changes,
+        }
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            latitude: self
+                .plane_state
+                .get("latitude")
+                .and_then(|v| v.as_f64())
+                .unwrap_or_default(),
+            longitude: self
+                .plane_state
+                .get("longitude")
+                .and_then(|v| v.as_f64())
+                .unwrap_or_default(),
+        }
+    }
+
+    // when LNAV has nothing left to fly, hold the current heading, and VNAV reverts to TECS on the selected altitude
+    fn check_end_of_route(&mut self) {
+        if !self.auto_pilot_state.flight_plan.is_finished() {
+            return;
+        }
+
+        if self.auto_pilot_state.horizontal_guidance.horizontal_mode_armed.is_some()
+            || self.auto_pilot_state.vertical_guidance.vertical_mode_armed.is_some()
+        {
+            self.disarm_modes("end of route");
+        }
+
+        if let HorizontalModes::LNAV = self.auto_pilot_state.horizontal_guidance.horizontal_mode {
+            self.revert_to_heading_mode("end of route");
+        }
+
+        if let VerticalModes::VNAV = self.auto_pilot_state.vertical_guidance.vertical_mode {
+            self.auto_pilot_state.vertical_profile = VerticalProfile::default();
+            self.set_vertical_mode(VerticalModes::TECS, "end of route");
+        }
+    }
+
+    fn revert_to_heading_mode(&mut self, reason: &str) {
+        let heading_key: &str = match self.auto_pilot_state.horizontal_guidance.heading_reference {
+            HeadingReference::True => "heading_true",
+            HeadingReference::Magnetic => "heading_magnetic",
+        };
+
+        if let Some(heading) = self.plane_state.get(heading_key).and_then(|v| v.as_f64()) {
+            self.auto_pilot_state.horizontal_guidance.heading_setpoint = heading;
+        }
+
+        self.set_horizontal_mode(HorizontalModes::Heading, reason);
+    }
+
+    fn mode_conditions(&self) -> ModeConditions {
+        ModeConditions {
+            plane_state: !self.plane_state.is_empty(),
+            route: !self.auto_pilot_state.flight_plan.is_finished(),
+            hold: self.auto_pilot_state.holding_pattern.hold_setup.is_some(),
+            orbit: self.auto_pilot_state.horizontal_guidance.orbit_setup.is_some(),
+        }
+    }
+
+    fn log_mode_transition(&mut self, channel: ModeChannel, from: &str, to: &str, reason: &str) {
+        event!(
+            Level::INFO,
+            "{:?} mode {} -> {} ({})",
+            channel,
+            from,
+            to,
+            reason
+        );
+
+        let transitions = &mut self.auto_pilot_state.mode_transitions;
+
+        transitions.push_back(ModeTransition {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            channel,
+            from: from.to_string(),
+            to: to.to_string(),
+            reason: reason.to_string(),
+        });
+
+        if transitions.len() > modes::MODE_LOG_LENGTH {
+            transitions.pop_front();
+        }
+    }
+
+    // every lateral mode change goes through here: it checks the mode can be engaged, starts its outer
+    // loop from scratch and the roll loop from the aileron, and logs why the mode changed
+    fn set_horizontal_mode(&mut self, mode: HorizontalModes, reason: &str) -> bool {
+        if let Err(e) = modes::check_horizontal_mode(mode, &self.mode_conditions()) {
+            event!(Level::WARN, "{:?} mode not engaged, {}", mode, e);
+            return false;
+        }
+
+        let previous: HorizontalModes = self.auto_pilot_state.horizontal_guidance.horizontal_mode;
+        let was_armed: bool = self.auto_pilot_state.horizontal_guidance.horizontal_mode_armed == Some(mode);
+
+        match mode {
+            HorizontalModes::Heading => {
+                self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
+                self.initialize_roll_loop();
+            }
+            HorizontalModes::Track => {
+                self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
+                self.initialize_roll_loop();
+            }
+            HorizontalModes::LNAV => {
+                // an armed LNAV already has the leg it intercepts
+                if !was_armed {
+                    self.set_first_leg_origin();
+                }
+
+                self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
+                self.initialize_roll_loop();
+            }
+            HorizontalModes::Hold => {
+                // the entry is flown from where we are, also when the hold was already engaged
+                let position: Position = self.current_position();
+                self.auto_pilot_state.holding_pattern.start(position);
+
+                self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
+                self.initialize_roll_loop();
+            }
+            HorizontalModes::Orbit => {
+                self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
+                self.initialize_roll_loop();
+            }
+            HorizontalModes::Standby | HorizontalModes::WingsLevel => {}
+        }
+
+        let horizontal_guidance = &mut self.auto_pilot_state.horizontal_guidance;
+        horizontal_guidance.horizontal_mode = mode;
+
+        if was_armed || mode == HorizontalModes::Standby {
+            horizontal_guidance.horizontal_mode_armed = None;
+        }
+
+        if previous != mode {
+            self.log_mode_transition(
+                ModeChannel::Lateral,
+                modes::horizontal_annunciation(previous),
+                modes::horizontal_annunciation(mode),
+                reason,
+            );
+        }
+
+        // an armed VNAV engages together with LNAV
+        if mode == HorizontalModes::LNAV
+            && self.auto_pilot_state.vertical_guidance.vertical_mode_armed == Some(VerticalModes::VNAV)
+        {
+            self.set_vertical_mode(VerticalModes::VNAV, "LNAV engaged");
+        }
+
+        true
+    }
+
+    fn set_vertical_mode(&mut self, mode: VerticalModes, reason: &str) -> bool {
+        if let Err(e) = modes::check_vertical_mode(mode, &self.mode_conditions()) {
+            event!(Level::WARN, "{:?} mode not engaged, {}", mode, e);
+            return false;
+        }
+
+        let vertical_guidance = &self.auto_pilot_state.vertical_guidance;
+        let previous: &str = modes::vertical_annunciation(
+            vertical_guidance.vertical_mode,
+            vertical_guidance.altitude_capture_state,
+        );
+
+        match mode {
+            VerticalModes::TECS | VerticalModes::VNAV => {
+                self.reset_tecs();
+            }
+            VerticalModes::VS => {
+                // start with the current vertical speed, rounded to 100 fpm
+                if let Some(vertical_speed) = self.plane_state.get("VVI").and_then(|v| v.as_f64()) {
+                    self.auto_pilot_state.vertical_guidance.vertical_speed_setpoint =
+                        ((vertical_speed / 100.0).round() * 100.0)
+                            .clamp(-api::VERTICAL_SPEED_LIMIT, api::VERTICAL_SPEED_LIMIT);
+                }
+
+                self.initialize_pitch_loop();
+                self.auto_pilot_state.vertical_guidance.speed_pid.reset();
+            }
+            VerticalModes::FLCH => {
+                self.initialize_pitch_loop();
+                self.auto_pilot_state.vertical_guidance.flch_pid.reset();
+            }
+            VerticalModes::Standby => {}
+        }
+
+        self.reset_altitude_capture();
+
+        let vertical_guidance = &mut self.auto_pilot_state.vertical_guidance;
+        vertical_guidance.vertical_mode = mode;
+
+        if vertical_guidance.vertical_mode_armed == Some(mode) || mode == VerticalModes::Standby {
+            vertical_guidance.vertical_mode_armed = None;
         }
+
+        let current: &str =
+            modes::vertical_annunciation(mode, vertical_guidance.altitude_capture_state);
+
+        if previous != current {
+            self.log_mode_transition(ModeChannel::Vertical, previous, current, reason);
+        }
+
+        true
+    }
+
+    fn set_autothrottle_mode(&mut self, mode: AutoThrottleModes, reason: &str) -> bool {
+        if let Err(e) = modes::check_autothrottle_mode(mode, &self.mode_conditions()) {
+            event!(Level::WARN, "{:?} mode not engaged, {}", mode, e);
+            return false;
+        }
+
+        let previous: AutoThrottleModes = self.auto_pilot_state.autothrottle.autothrottle_mode;
+        self.auto_pilot_state.autothrottle.autothrottle_mode = mode;
+
+        if previous != mode {
+            self.log_mode_transition(
+                ModeChannel::AutoThrottle,
+                modes::autothrottle_annunciation(previous),
+                modes::autothrottle_annunciation(mode),
+                reason,
+            );
+        }
+
+        true
+    }
+
+    fn disarm_modes(&mut self, reason: &str) {
+        let horizontal_guidance = &mut self.auto_pilot_state.horizontal_guidance;
+        if let Some(mode) = horizontal_guidance.horizontal_mode_armed.take() {
+            let active: &str = modes::horizontal_annunciation(horizontal_guidance.horizontal_mode);
+            let armed: String = format!("{} armed", modes::horizontal_annunciation(mode));
+            self.log_mode_transition(ModeChannel::Lateral, &armed, active, reason);
+        }
+
+        let vertical_guidance = &mut self.auto_pilot_state.vertical_guidance;
+        if let Some(mode) = vertical_guidance.vertical_mode_armed.take() {
+            let active: &str = modes::vertical_annunciation(
+                vertical_guidance.vertical_mode,
+                vertical_guidance.altitude_capture_state,
+            );
+            let armed: String = format!(
+                "{} armed",
+                modes::vertical_annunciation(mode, AltitudeCaptureState::Inactive)
+            );
+            self.log_mode_transition(ModeChannel::Vertical, &armed, active, reason);
+        }
+    }
+
+    // the first leg is flown from the current position, later legs from the previous waypoint
+    fn set_first_leg_origin(&mut self) {
+        let current_position: Position = self.current_position();
+        let flight_plan = &mut self.auto_pilot_state.flight_plan;
+
+        flight_plan.leg_origin = match flight_plan.active_waypoint {
+            0 => current_position,
+            i => flight_plan.waypoints[i - 1].position(),
+        };
+    }
+
+    // the altitude capture starts over, from the vertical speed we have now
+    fn reset_altitude_capture(&mut self) {
+        let vertical_guidance = &mut self.auto_pilot_state.vertical_guidance;
+
+        vertical_guidance.altitude_capture_state = AltitudeCaptureState::Inactive;
+        vertical_guidance.altitude_alert = AltitudeAlert::None;
+        vertical_guidance.vertical_speed_command = self
+            .plane_state
+            .get("VVI")
+            .and_then(|v| v.as_f64())
+            .unwrap_or_default();
+    }
+
+    // the bank, pitch and throttle the active modes commanded in the last cycle
+    fn flight_director_cues(&self) -> FlightDirectorCues {
+        let state: &AutoPilotState = &self.auto_pilot_state;
+
+        let lateral: bool = state.horizontal_guidance.horizontal_mode != HorizontalModes::Standby;
+        let vertical: bool = state.vertical_guidance.vertical_mode != VerticalModes::Standby;
+
+        // the autothrottle owns the throttle when it is engaged, in THR HOLD it leaves it to the pilot
+        let throttle: Option<f64> = match state.autothrottle.autothrottle_mode {
+            AutoThrottleModes::Speed => Some(state.autothrottle.throttle_command),
+            AutoThrottleModes::ThrHold => None,
+            AutoThrottleModes::Standby => vertical.then_some(state.vertical_control_metrics.throttle_setpoint),
+        };
+
+        FlightDirectorCues {
+            bank: lateral.then_some(state.horizontal_control_metrics.roll_angle_target),
+            pitch: vertical.then_some(state.vertical_control_metrics.pitch_target),
+            throttle,
+        }
+    }
+
+    // the inner loops kept running against a plane the pilot flew, so they start over from the yoke
+    // when the autopilot takes the controls back, like TECS does from the throttle
+    fn couple_autopilot(&mut self) {
+        if matches!(
+            self.auto_pilot_state.vertical_guidance.vertical_mode,
+            VerticalModes::TECS | VerticalModes::VNAV
+        ) {
+            self.reset_tecs();
+        }
+
+        self.initialize_roll_loop();
+        self.initialize_pitch_loop();
+
+        // the last commands are from before the pilot flew, they are no reference for an override
+        self.auto_pilot_state.last_commands = LastCommands::default();
+        self.auto_pilot_state.flight_director.autopilot_coupled = true;
+    }
+
+    // with neither the autopilot nor the flight director there is nothing to fly the modes, the
+    // autothrottle is left alone
+    fn modes_to_standby(&mut self, reason: &str) {
+        self.disarm_modes(reason);
+        self.set_horizontal_mode(HorizontalModes::Standby, reason);
+        self.set_vertical_mode(VerticalModes::Standby, reason);
+    }
+
+    // TECS starts from the throttle position and the elevator we have now, so there is no bump
+    fn reset_tecs(&mut self) {
+        let throttle: f64 = self
+            .plane_state
+            .get("throttle_1_actual")
+            .and_then(|v| v.as_f64())
+            .unwrap_or_default();
+        let vertical_guidance = &mut self.auto_pilot_state.vertical_guidance;
+
+        vertical_guidance.energy_pid.initialize(throttle);
+        vertical_guidance.distribution_pid.reset();
+        self.initialize_pitch_loop();
+    }
+
+    // the inner loops fly the surfaces, so a new mode or setpoint takes them over from where the yoke is,
+    // also on an automatic transition like the altitude capture. only the outer loops start over
+    fn initialize_roll_loop(&mut self) {
+        let aileron: Option<f64> = self
+            .plane_state
+            .get("aileron_commanded")
+            .and_then(|v| v.as_f64());
+
+        flightdirector::couple_inner_loop(
+            &mut self.auto_pilot_state.horizontal_guidance.roll_pid,
+            aileron,
+        );
+    }
+
+    fn initialize_pitch_loop(&mut self) {
+        let elevator: Option<f64> = self
+            .plane_state
+            .get("elevator_commanded")
+            .and_then(|v| v.as_f64());
+
+        flightdirector::couple_inner_loop(
+            &mut self.auto_pilot_state.vertical_guidance.pitch_pid,
+            elevator,
+        );
     }
 
     // Process incoming commands asynchronously
     pub async fn process(mut self) {
         while let Some(signal) = self.receiver.recv().await {
+            let changes_state: bool = signal.changes_state();
+
             match signal {
                 StateSignal::SetFlying {
                     are_we_flying,
@@ -275,51 +1183,37 @@ impl AppState {
                     result_sender,
                 } => {
                     self.plane_state = plane_state;
+
+                    // the ICAO type of the aircraft, when the sim reports it
+                    if let Some(aircraft) = self.plane_state.get("aircraft").and_then(|v| v.as_str()) {
+                        let status = &mut self.auto_pilot_state.profile_status;
+                        status.aircraft_reported = aircraft.to_string();
+                    }
+
                     let _ = result_sender.send(true);
                 }
                 StateSignal::ReturnPlaneState { result_sender } => {
                     let _ = result_sender.send(self.plane_state.clone());
                 }
                 StateSignal::ReturnPlaneStateStruct { result_sender } => {
-                    //dbg!(self.plane_state.clone());
-                    let state_struct = PlaneStateStruct {
-                        v_ind: self.plane_state.get("Vind").unwrap().as_f64().unwrap(),
-                        altitude_msl: self
-                            .plane_state
-                            .get("altitude_msl")
-                            .unwrap()
-                            .as_f64()
-                            .unwrap(),
-                        vpath: self.plane_state.get("vpath").unwrap().as_f64().unwrap(),
-                        roll: self.plane_state.get("roll").unwrap().as_f64().unwrap(),
-                        roll_rate: self.plane_state.get("P").unwrap().as_f64().unwrap(),
-                        pitch: self.plane_state.get("pitch").unwrap().as_f64().unwrap(),
-                        pitch_rate: self.plane_state.get("Q").unwrap().as_f64().unwrap(),
-                        gload_axial: self
-                            .plane_state
-                            .get("Gload_axial")
-                            .unwrap()
-                            .as_f64()
-                            .unwrap(),
-                        heading: self
-                            .plane_state
-                            .get("heading_true")
-                            .unwrap()
-                            .as_f64()
-                            .unwrap(),
-                    };
-
-                    let _ = result_sender.send(state_struct);
+                    let _ = result_sender.send(PlaneStateStruct::from_plane_state(&self.plane_state));
                 }
                 StateSignal::ReturnAutoPilotState { result_sender } => {
+                    self.auto_pilot_state.fma = FlightModeAnnunciation::new(
+                        &self.auto_pilot_state.horizontal_guidance,
+                        &self.auto_pilot_state.vertical_guidance,
+                        &self.auto_pilot_state.autothrottle,
+                        &self.auto_pilot_state.flight_director,
+                    );
                     let _ = result_sender.send(self.auto_pilot_state.clone());
                 }
                 StateSignal::SetStandbyHeading {
                     standby_heading,
                     result_sender,
                 } => {
+                    // 360 is the same as 0, a negative heading is a heading left of north
                     self.auto_pilot_state.horizontal_guidance.heading_standby =
-                        standby_heading.clamp(0., 359.9);
+                        navigation::wrap_360(standby_heading);
                     let _ = result_sender.send(true);
                 }
                 StateSignal::ActivateStandbyHeading { result_sender } => {
@@ -328,172 +1222,738 @@ impl AppState {
                     self.auto_pilot_state.horizontal_guidance.heading_setpoint =
                         self.auto_pilot_state.horizontal_guidance.heading_standby;
 
-                    self.auto_pilot_state
-                        .horizontal_guidance
-                        .heading_error_integral = 0.0;
+                    self.auto_pilot_state.horizontal_guidance.heading_pid.reset();
 
-                    self.auto_pilot_state
-                        .horizontal_guidance
-                        .roll_error_integral = 0.0;
+                    self.initialize_roll_loop();
 
                     self.auto_pilot_state.horizontal_guidance.heading_standby = temp;
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetHorizontalGuidanceToStandbyMode { result_sender } => {
-                    self.auto_pilot_state.horizontal_guidance.horizontal_mode =
-                        HorizontalModes::Standby;
-                    let _ = result_sender.send(true);
-                }
-                StateSignal::SetHorizontalGuidanceToWingsLevelMode { result_sender } => {
-                    self.auto_pilot_state.horizontal_guidance.horizontal_mode =
-                        HorizontalModes::WingsLevel;
+                StateSignal::SetTurnDirection {
+                    turn_direction,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.horizontal_guidance.turn_direction = turn_direction;
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetHorizontalGuidanceToHeadingMode { result_sender } => {
-                    self.auto_pilot_state
-                        .horizontal_guidance
-                        .heading_error_integral = 0.0;
-                    self.auto_pilot_state.horizontal_guidance.horizontal_mode =
-                        HorizontalModes::Heading;
+                StateSignal::SetBankLimit {
+                    bank_limit,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.horizontal_guidance.bank_limit = bank_limit;
                     let _ = result_sender.send(true);
                 }
-                StateSignal::AddToHeadingErrorIntegral {
-                    value,
+                StateSignal::SetHeadingReference {
+                    heading_reference,
                     result_sender,
                 } => {
-                    self.auto_pilot_state
-                        .horizontal_guidance
-                        .heading_error_integral += value;
+                    let horizontal_guidance = &mut self.auto_pilot_state.horizontal_guidance;
+
+                    // keep pointing the same way, the setpoints are converted with the local variation
+                    if horizontal_guidance.heading_reference != heading_reference {
+                        let variation: Option<f64> = self
+                            .plane_state
+                            .get("heading_true")
+                            .and_then(|v| v.as_f64())
+                            .zip(self.plane_state.get("heading_magnetic").and_then(|v| v.as_f64()))
+                            .map(|(t, m)| navigation::wrap_180(t - m));
+
+                        if let Some(variation) = variation {
+                            let correction: f64 = match heading_reference {
+                                HeadingReference::True => variation,
+                                HeadingReference::Magnetic => -variation,
+                            };
+
+                            horizontal_guidance.heading_setpoint =
+                                navigation::wrap_360(horizontal_guidance.heading_setpoint + correction);
+                            horizontal_guidance.heading_standby =
+                                navigation::wrap_360(horizontal_guidance.heading_standby + correction);
+                        }
+                    }
+
+                    horizontal_guidance.heading_reference = heading_reference;
                     let _ = result_sender.send(true);
                 }
-                StateSignal::AddToRollErrorIntegral {
-                    value,
+                StateSignal::SetHorizontalGuidanceToStandbyMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::Standby, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToWingsLevelMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::WingsLevel, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToHeadingMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::Heading, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToTrackMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::Track, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToLNAVMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::LNAV, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToHoldMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::Hold, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetHorizontalGuidanceToOrbitMode { result_sender } => {
+                    let result: bool =
+                        self.set_horizontal_mode(HorizontalModes::Orbit, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetOrbit {
+                    orbit_setup,
                     result_sender,
                 } => {
-                    self.auto_pilot_state
-                        .horizontal_guidance
-                        .roll_error_integral += value;
+                    // an orbit that is flown moves to the new circle straight away
+                    self.auto_pilot_state.horizontal_guidance.orbit_setup = Some(orbit_setup);
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetStandbyVelocity {
-                    standby_velocity,
+                StateSignal::SetHold {
+                    hold_setup,
                     result_sender,
                 } => {
-                    self.auto_pilot_state.vertical_guidance.velocity_standby =
-                        standby_velocity.clamp(0.0, 180.0);
+                    self.auto_pilot_state.holding_pattern.hold_setup = Some(hold_setup);
+
+                    // a hold that is flown starts over with the new fix
+                    if self.auto_pilot_state.horizontal_guidance.horizontal_mode
+                        == HorizontalModes::Hold
+                    {
+                        let position: Position = self.current_position();
+                        self.auto_pilot_state.holding_pattern.start(position);
+                    }
                     let _ = result_sender.send(true);
                 }
-                StateSignal::ActivateStandbyVelocity { result_sender } => {
-                    std::mem::swap(
-                        &mut self.auto_pilot_state.vertical_guidance.velocity_setpoint,
-                        &mut self.auto_pilot_state.vertical_guidance.velocity_standby,
-                    );
-
+                StateSignal::UpdateHoldingPattern {
+                    holding_pattern,
+                    result_sender,
+                } => {
+                    // only while it is flown, a new hold set up in the meantime is kept
+                    let current = &self.auto_pilot_state.holding_pattern;
+                    if self.auto_pilot_state.horizontal_guidance.horizontal_mode
+                        == HorizontalModes::Hold
+                        && current.hold_setup == holding_pattern.hold_setup
+                    {
+                        self.auto_pilot_state.holding_pattern = holding_pattern;
+                    }
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetStandbyAltitude {
-                    standby_altitude,
+                StateSignal::ArmHorizontalMode {
+                    mode,
                     result_sender,
                 } => {
-                    self.auto_pilot_state.vertical_guidance.altitude_standby =
-                        standby_altitude.clamp(0.0, 25_000.0);
+                    let horizontal_guidance = &self.auto_pilot_state.horizontal_guidance;
+
+                    if let Err(e) = modes::check_horizontal_arm(
+                        mode,
+                        horizontal_guidance.horizontal_mode,
+                        &self.mode_conditions(),
+                    ) {
+                        event!(Level::WARN, "{:?} mode not armed, {}", mode, e);
+                        let _ = result_sender.send(false);
+                        continue;
+                    }
+
+                    let active: &str =
+                        modes::horizontal_annunciation(horizontal_guidance.horizontal_mode);
+                    let armed: String = format!("{} armed", modes::horizontal_annunciation(mode));
+
+                    self.set_first_leg_origin();
+                    self.auto_pilot_state.horizontal_guidance.horizontal_mode_armed = Some(mode);
+                    self.log_mode_transition(ModeChannel::Lateral, active, &armed, PILOT_SELECTED);
                     let _ = result_sender.send(true);
                 }
-                StateSignal::ActivateStandbyAltitude { result_sender } => {
-                    std::mem::swap(
-                        &mut self.auto_pilot_state.vertical_guidance.altitude_setpoint,
-                        &mut self.auto_pilot_state.vertical_guidance.altitude_standby,
+                StateSignal::ArmVerticalMode {
+                    mode,
+                    result_sender,
+                } => {
+                    let vertical_guidance = &self.auto_pilot_state.vertical_guidance;
+
+                    if let Err(e) = modes::check_vertical_arm(
+                        mode,
+                        vertical_guidance.vertical_mode,
+                        self.auto_pilot_state.horizontal_guidance.horizontal_mode_armed,
+                        &self.mode_conditions(),
+                    ) {
+                        event!(Level::WARN, "{:?} mode not armed, {}", mode, e);
+                        let _ = result_sender.send(false);
+                        continue;
+                    }
+
+                    let active: &str = modes::vertical_annunciation(
+                        vertical_guidance.vertical_mode,
+                        vertical_guidance.altitude_capture_state,
+                    );
+                    let armed: String = format!(
+                        "{} armed",
+                        modes::vertical_annunciation(mode, AltitudeCaptureState::Inactive)
                     );
 
+                    self.auto_pilot_state.vertical_guidance.vertical_mode_armed = Some(mode);
+                    self.log_mode_transition(ModeChannel::Vertical, active, &armed, PILOT_SELECTED);
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetVerticalGuidanceToStandbyMode { result_sender } => {
-                    self.auto_pilot_state.vertical_guidance.vertical_mode = VerticalModes::Standby;
+                StateSignal::DisarmModes { result_sender } => {
+                    self.disarm_modes(PILOT_SELECTED);
                     let _ = result_sender.send(true);
                 }
-                StateSignal::SetVerticalGuidanceToTECSMode { result_sender } => {
-                    self.auto_pilot_state
-                        .vertical_guidance
-                        .energy_error_integral = 0.0;
-                    self.auto_pilot_state.vertical_guidance.pitch_error_integral = 0.0;
-                    self.auto_pilot_state.vertical_guidance.vertical_mode = VerticalModes::TECS;
+                StateSignal::EngageArmedHorizontalMode { result_sender } => {
+                    let result: bool =
+                        match self.auto_pilot_state.horizontal_guidance.horizontal_mode_armed {
+                            Some(mode) => self.set_horizontal_mode(mode, "leg intercepted"),
+                            None => false,
+                        };
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::RevertToStandby {
+                    reason,
+                    result_sender,
+                } => {
+                    self.set_horizontal_mode(HorizontalModes::Standby, &reason);
+                    self.set_vertical_mode(VerticalModes::Standby, &reason);
+                    self.set_autothrottle_mode(AutoThrottleModes::Standby, &reason);
                     let _ = result_sender.send(true);
                 }
-                StateSignal::AddToEnergyErrorIntegral {
-                    value,
+                StateSignal::LoadFlightPlan {
+                    waypoints,
                     result_sender,
                 } => {
-                    //limit integral wind up to 20% max
-                    let bandwith = (self
-                        .auto_pilot_state
-                        .vertical_guidance
-                        .energy_error_integral
-                        .abs()
-                        * 0.02)
-                        .max(0.1);
+                    let current_position: Position = self.current_position();
                     self.auto_pilot_state
-                        .vertical_guidance
-                        .energy_error_integral = (self
-                        .auto_pilot_state
-                        .vertical_guidance
-                        .energy_error_integral
-                        + value)
-                        .clamp(
-                            self.auto_pilot_state
-                                .vertical_guidance
-                                .energy_error_integral
-                                - bandwith,
-                            self.auto_pilot_state
-                                .vertical_guidance
-                                .energy_error_integral
-                                + bandwith,
-                        );
+                        .flight_plan
+                        .load(waypoints, current_position);
+                    self.check_end_of_route();
                     let _ = result_sender.send(true);
                 }
-                StateSignal::AddToPitchErrorIntegral {
-                    value,
+                StateSignal::ClearFlightPlan { result_sender } => {
+                    self.auto_pilot_state.flight_plan.clear();
+                    self.check_end_of_route();
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::InsertWaypoint {
+                    index,
+                    waypoint,
                     result_sender,
                 } => {
-                    //limit integral wind up to 20% max
-                    let bandwith = (self
+                    let result = self
                         .auto_pilot_state
-                        .vertical_guidance
-                        .pitch_error_integral
-                        .abs()
-                        * 0.02)
-                        .max(0.1);
-
-                    self.auto_pilot_state.vertical_guidance.pitch_error_integral =
-                        (self.auto_pilot_state.vertical_guidance.pitch_error_integral + value)
-                            .clamp(
-                                self.auto_pilot_state.vertical_guidance.pitch_error_integral
-                                    - bandwith,
-                                self.auto_pilot_state.vertical_guidance.pitch_error_integral
-                                    + bandwith,
-                            );
-                    let _ = result_sender.send(true);
+                        .flight_plan
+                        .insert_waypoint(index, waypoint);
+                    let _ = result_sender.send(result);
                 }
-                StateSignal::RefreshAutoPilotConstants { result_sender } => {
-                    self.auto_pilot_state.control_constants = AutoPilotConstants::from_file();
-                    let _ = result_sender.send(true);
+                StateSignal::DeleteWaypoint {
+                    index,
+                    result_sender,
+                } => {
+                    let result = self.auto_pilot_state.flight_plan.delete_waypoint(index);
+                    self.check_end_of_route();
+                    let _ = result_sender.send(result.map(|_| ()));
                 }
-                StateSignal::UpdateHorizontalAutoPilotMetrics {
-                    metrics,
+                StateSignal::DirectToWaypoint {
+                    index,
                     result_sender,
                 } => {
-                    self.auto_pilot_state.horizontal_control_metrics = metrics;
-                    let _ = result_sender.send(true);
+                    let current_position: Position = self.current_position();
+                    let result = self
+                        .auto_pilot_state
+                        .flight_plan
+                        .direct_to(index, current_position);
+                    let _ = result_sender.send(result);
                 }
-                StateSignal::UpdateVerticalAutoPilotMetrics {
-                    metrics,
+                StateSignal::SetWaypointConstraints {
+                    index,
+                    altitude_constraint,
+                    speed_constraint,
                     result_sender,
                 } => {
-                    self.auto_pilot_state.vertical_control_metrics = metrics;
+                    let result = self.auto_pilot_state.flight_plan.set_constraints(
+                        index,
+                        altitude_constraint,
+                        speed_constraint,
+                    );
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SequenceWaypoint { result_sender } => {
+                    self.auto_pilot_state.flight_plan.sequence();
+                    self.check_end_of_route();
                     let _ = result_sender.send(true);
                 }
-            }
-        }
-    }
+                StateSignal::SetStandbyVelocity {
+                    standby_velocity,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_guidance.velocity_standby = standby_velocity;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::ActivateStandbyVelocity { result_sender } => {
+                    std::mem::swap(
+                        &mut self.auto_pilot_state.vertical_guidance.velocity_setpoint,
+                        &mut self.auto_pilot_state.vertical_guidance.velocity_standby,
+                    );
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetStandbyAltitude {
+                    standby_altitude,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_guidance.altitude_standby = standby_altitude;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::ActivateStandbyAltitude { result_sender } => {
+                    std::mem::swap(
+                        &mut self.auto_pilot_state.vertical_guidance.altitude_setpoint,
+                        &mut self.auto_pilot_state.vertical_guidance.altitude_standby,
+                    );
+
+                    // a new selected altitude has to be captured again
+                    self.reset_altitude_capture();
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetStandbyVerticalSpeed {
+                    standby_vertical_speed,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_guidance.vertical_speed_standby =
+                        standby_vertical_speed;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::ActivateStandbyVerticalSpeed { result_sender } => {
+                    std::mem::swap(
+                        &mut self.auto_pilot_state.vertical_guidance.vertical_speed_setpoint,
+                        &mut self.auto_pilot_state.vertical_guidance.vertical_speed_standby,
+                    );
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetFLCHThrottle {
+                    climb_throttle,
+                    descent_throttle,
+                    result_sender,
+                } => {
+                    if let Some(throttle) = climb_throttle {
+                        self.auto_pilot_state.vertical_guidance.flch_climb_throttle =
+                            throttle.clamp(0.0, 1.0);
+                    }
+
+                    if let Some(throttle) = descent_throttle {
+                        self.auto_pilot_state.vertical_guidance.flch_descent_throttle =
+                            throttle.clamp(0.0, 1.0);
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetVerticalGuidanceToStandbyMode { result_sender } => {
+                    let result: bool = self.set_vertical_mode(VerticalModes::Standby, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetVerticalGuidanceToTECSMode { result_sender } => {
+                    let result: bool = self.set_vertical_mode(VerticalModes::TECS, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetVerticalGuidanceToVNAVMode { result_sender } => {
+                    let result: bool = self.set_vertical_mode(VerticalModes::VNAV, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetVerticalGuidanceToVSMode { result_sender } => {
+                    let result: bool = self.set_vertical_mode(VerticalModes::VS, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetVerticalGuidanceToFLCHMode { result_sender } => {
+                    let result: bool = self.set_vertical_mode(VerticalModes::FLCH, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::DisconnectAutoPilot {
+                    reason,
+                    result_sender,
+                } => {
+                    event!(Level::ERROR, "Autopilot disconnected, {}", reason);
+
+                    self.disarm_modes(&reason);
+                    self.set_horizontal_mode(HorizontalModes::Standby, &reason);
+                    self.set_vertical_mode(VerticalModes::Standby, &reason);
+                    self.set_autothrottle_mode(AutoThrottleModes::Standby, &reason);
+
+                    let disconnect = &mut self.auto_pilot_state.disconnect;
+                    disconnect.disconnect_alert = true;
+                    disconnect.disconnect_reason = reason;
+                    disconnect.disconnect_timestamp = chrono::Utc::now().timestamp_millis();
+                    disconnect.disconnect_count += 1;
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::OverrideChannel {
+                    event,
+                    result_sender,
+                } => {
+                    event!(
+                        Level::WARN,
+                        "Pilot override of the {:?} channel, command {:.2}, yoke {:.2}, surface {:.2}",
+                        event.channel,
+                        event.command,
+                        event.yoke,
+                        event.surface
+                    );
+
+                    // an armed VNAV needs LNAV, so it goes with the lateral channel
+                    let reason: &str = "pilot override";
+                    match event.channel {
+                        ModeChannel::Lateral => {
+                            self.disarm_modes(reason);
+                            self.set_horizontal_mode(HorizontalModes::Standby, reason);
+                            self.auto_pilot_state.last_commands.last_aileron_command = None;
+                        }
+                        ModeChannel::Vertical => {
+                            self.auto_pilot_state.vertical_guidance.vertical_mode_armed = None;
+                            self.set_vertical_mode(VerticalModes::Standby, reason);
+                            self.auto_pilot_state.last_commands.last_elevator_command = None;
+                        }
+                        ModeChannel::AutoThrottle => {
+                            self.set_autothrottle_mode(AutoThrottleModes::Standby, reason);
+                        }
+                    }
+
+                    self.auto_pilot_state.override_count += 1;
+
+                    let events = &mut self.auto_pilot_state.override_events;
+                    events.push_back(event);
+
+                    if events.len() > pilotoverride::OVERRIDE_LOG_LENGTH {
+                        events.pop_front();
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::RecordCommand {
+                    command_type,
+                    value,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.last_commands.record(command_type, value);
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdatePilotOverride {
+                    pilot_override,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.pilot_override = pilot_override;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetFlightDirector {
+                    flight_director_on,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.flight_director.flight_director_on = flight_director_on;
+
+                    if !flight_director_on && !self.auto_pilot_state.flight_director.autopilot_coupled {
+                        self.modes_to_standby("flight director off with the autopilot uncoupled");
+                    }
+
+                    self.auto_pilot_state
+                        .flight_director
+                        .update_cues(&self.flight_director_cues());
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAutoPilotCoupled {
+                    coupled,
+                    result_sender,
+                } => {
+                    let flight_director = &mut self.auto_pilot_state.flight_director;
+
+                    match (coupled, flight_director.autopilot_coupled) {
+                        (true, false) => self.couple_autopilot(),
+                        (false, true) => {
+                            flight_director.autopilot_coupled = false;
+
+                            if !flight_director.flight_director_on {
+                                self.modes_to_standby("autopilot uncoupled without the flight director");
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateFlightDirectorCues { result_sender } => {
+                    let cues: FlightDirectorCues = self.flight_director_cues();
+                    self.auto_pilot_state.flight_director.update_cues(&cues);
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::AcknowledgeDisconnect { result_sender } => {
+                    self.auto_pilot_state.disconnect.disconnect_alert = false;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::CaptureSelectedAltitude { result_sender } => {
+                    let result: bool =
+                        self.set_vertical_mode(VerticalModes::TECS, "selected altitude capture");
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::UpdateAltitudeCapture {
+                    capture_state,
+                    vertical_speed_command,
+                    result_sender,
+                } => {
+                    let vertical_guidance = &mut self.auto_pilot_state.vertical_guidance;
+                    let mode: VerticalModes = vertical_guidance.vertical_mode;
+                    let previous: &str =
+                        modes::vertical_annunciation(mode, vertical_guidance.altitude_capture_state);
+
+                    vertical_guidance.altitude_capture_state = capture_state;
+                    vertical_guidance.vertical_speed_command = vertical_speed_command;
+
+                    let current: &str = modes::vertical_annunciation(mode, capture_state);
+                    if previous != current {
+                        self.log_mode_transition(
+                            ModeChannel::Vertical,
+                            previous,
+                            current,
+                            "altitude capture",
+                        );
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAltitudeAlert {
+                    altitude_alert,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_guidance.altitude_alert = altitude_alert;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAutoThrottleToStandbyMode { result_sender } => {
+                    let result: bool =
+                        self.set_autothrottle_mode(AutoThrottleModes::Standby, PILOT_SELECTED);
+                    let _ = result_sender.send(result);
+                }
+                StateSignal::SetAutoThrottleToSpeedMode { result_sender } => {
+                    if let Err(e) = modes::check_autothrottle_mode(
+                        AutoThrottleModes::Speed,
+                        &self.mode_conditions(),
+                    ) {
+                        event!(Level::WARN, "Autothrottle not engaged, {}", e);
+                        let _ = result_sender.send(false);
+                        continue;
+                    }
+
+                    let on_runway: bool = self
+                        .plane_state
+                        .get("on_runway")
+                        .and_then(on_runway)
+                        .unwrap_or_default();
+
+                    let autothrottle = &mut self.auto_pilot_state.autothrottle;
+
+                    // start from the throttle position we have now, so there is no bump
+                    autothrottle.throttle_command = self
+                        .plane_state
+                        .get("throttle_1_actual")
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or_default();
+                    autothrottle.autothrottle_pid.initialize(autothrottle.throttle_command);
+
+                    match autothrottle::engage_mode(on_runway) {
+                        AutoThrottleModes::ThrHold => {
+                            autothrottle.thrust_limit = ThrustLimit::TakeOffGoAround;
+                            self.set_autothrottle_mode(AutoThrottleModes::ThrHold, "take off");
+                        }
+                        mode => {
+                            self.set_autothrottle_mode(mode, PILOT_SELECTED);
+                        }
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::ReleaseAutoThrottleThrHold {
+                    throttle,
+                    result_sender,
+                } => {
+                    let autothrottle = &mut self.auto_pilot_state.autothrottle;
+
+                    autothrottle.throttle_command = throttle;
+                    autothrottle.autothrottle_pid.initialize(throttle);
+                    autothrottle.thrust_limit = ThrustLimit::MaximumContinuous;
+                    self.set_autothrottle_mode(AutoThrottleModes::Speed, "airborne");
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetThrustLimit {
+                    thrust_limit,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.autothrottle.thrust_limit = thrust_limit;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateAutoThrottle {
+                    throttle_command,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.autothrottle.throttle_command = throttle_command;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdatePid {
+                    pid_loop,
+                    pid,
+                    result_sender,
+                } => {
+                    let state = &mut self.auto_pilot_state;
+
+                    match pid_loop {
+                        PidLoop::Heading => state.horizontal_guidance.heading_pid = pid,
+                        PidLoop::Roll => state.horizontal_guidance.roll_pid = pid,
+                        PidLoop::Pitch => state.vertical_guidance.pitch_pid = pid,
+                        PidLoop::Energy => state.vertical_guidance.energy_pid = pid,
+                        PidLoop::Distribution => state.vertical_guidance.distribution_pid = pid,
+                        PidLoop::Speed => state.vertical_guidance.speed_pid = pid,
+                        PidLoop::FlightLevelChange => state.vertical_guidance.flch_pid = pid,
+                        PidLoop::AutoThrottle => state.autothrottle.autothrottle_pid = pid,
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateVerticalProfile {
+                    profile,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_profile = profile;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetTECSPriority {
+                    priority,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_guidance.tecs_priority = priority;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAutoPilotConstants {
+                    constants,
+                    source,
+                    result_sender,
+                } => {
+                    let changed: Vec<String> = constantswatcher::changed_constants(
+                        &self.auto_pilot_state.control_constants,
+                        &constants,
+                    );
+                    let status = &mut self.auto_pilot_state.constants_status;
+
+                    status.constants_source = source;
+                    status.constants_error.clear();
+
+                    // saving a file without changes is not a new version
+                    if !changed.is_empty() {
+                        event!(
+                            Level::INFO,
+                            "New constants from {} took effect, changed: {}",
+                            status.constants_source,
+                            changed.join(", ")
+                        );
+
+                        status.constants_version += 1;
+                        status.constants_timestamp = chrono::Utc::now().timestamp_millis();
+                        status.constants_changed = changed;
+                        self.auto_pilot_state.control_constants = *constants;
+
+                        let history = &mut self.constants_history;
+                        if history.len() >= constantswatcher::CONSTANTS_HISTORY_LENGTH {
+                            history.pop_front();
+                        }
+                        self.constants_history.push_back(ConstantsVersion {
+                            version: status.constants_version,
+                            timestamp: status.constants_timestamp,
+                            source: status.constants_source.clone(),
+                            changed: status.constants_changed.clone(),
+                            constants: self.auto_pilot_state.control_constants.clone(),
+                        });
+                    }
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::RejectAutoPilotConstants {
+                    source,
+                    error,
+                    result_sender,
+                } => {
+                    let status = &mut self.auto_pilot_state.constants_status;
+
+                    status.constants_source = source;
+                    status.constants_error = error;
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAircraftProfile {
+                    name,
+                    v_speeds,
+                    result_sender,
+                } => {
+                    let status = &mut self.auto_pilot_state.profile_status;
+
+                    status.aircraft_profile = name;
+                    status.aircraft_vs = v_speeds.map(|v| v.vs);
+                    status.aircraft_vfe = v_speeds.map(|v| v.vfe);
+                    status.aircraft_vne = v_speeds.map(|v| v.vne);
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetProfileAutoSelect {
+                    enabled,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.profile_status.aircraft_profile_auto = enabled;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::ReturnConstantsHistory { result_sender } => {
+                    let _ = result_sender.send(self.constants_history.iter().cloned().collect());
+                }
+                StateSignal::UpdateScheduledGains {
+                    scheduled_gains,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.scheduled_gains = scheduled_gains;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateLoopTiming {
+                    timing,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.loop_timing = timing;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateEnvelopeProtection {
+                    protection,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.envelope_protection = protection;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateHorizontalAutoPilotMetrics {
+                    metrics,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.horizontal_control_metrics = metrics;
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::UpdateVerticalAutoPilotMetrics {
+                    metrics,
+                    result_sender,
+                } => {
+                    self.auto_pilot_state.vertical_control_metrics = metrics;
+                    let _ = result_sender.send(true);
+                }
+            }
+
+            if changes_state {
+                self.state_changes
+                    .send_modify(|count| *count = count.wrapping_add(1));
+            }
+        }
+    }
 }
 
 pub(super) enum StateSignal {
@@ -510,7 +1970,7 @@ pub(super) enum StateSignal {
         result_sender: oneshot::Sender<BTreeMap<String, Value>>,
     },
     ReturnPlaneStateStruct {
-        result_sender: oneshot::Sender<PlaneStateStruct>,
+        result_sender: oneshot::Sender<anyhow::Result<PlaneStateStruct>>,
     },
     ClearPlaneState {
         result_sender: oneshot::Sender<bool>,
@@ -525,18 +1985,93 @@ pub(super) enum StateSignal {
     ActivateStandbyHeading {
         result_sender: oneshot::Sender<bool>,
     },
+    SetTurnDirection {
+        turn_direction: TurnDirection,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetHeadingReference {
+        heading_reference: HeadingReference,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetBankLimit {
+        bank_limit: BankLimit,
+        result_sender: oneshot::Sender<bool>,
+    },
     SetHorizontalGuidanceToStandbyMode {
         result_sender: oneshot::Sender<bool>,
     },
     SetHorizontalGuidanceToHeadingMode {
         result_sender: oneshot::Sender<bool>,
     },
-    AddToHeadingErrorIntegral {
-        value: f64,
+    SetHorizontalGuidanceToTrackMode {
         result_sender: oneshot::Sender<bool>,
     },
-    AddToRollErrorIntegral {
-        value: f64,
+    SetHorizontalGuidanceToLNAVMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetHorizontalGuidanceToHoldMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetHorizontalGuidanceToOrbitMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetOrbit {
+        orbit_setup: OrbitSetup,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetHold {
+        hold_setup: HoldSetup,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateHoldingPattern {
+        holding_pattern: HoldingPattern,
+        result_sender: oneshot::Sender<bool>,
+    },
+    ArmHorizontalMode {
+        mode: HorizontalModes,
+        result_sender: oneshot::Sender<bool>,
+    },
+    ArmVerticalMode {
+        mode: VerticalModes,
+        result_sender: oneshot::Sender<bool>,
+    },
+    DisarmModes {
+        result_sender: oneshot::Sender<bool>,
+    },
+    EngageArmedHorizontalMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    RevertToStandby {
+        reason: String,
+        result_sender: oneshot::Sender<bool>,
+    },
+    LoadFlightPlan {
+        waypoints: Vec<Waypoint>,
+        result_sender: oneshot::Sender<bool>,
+    },
+    ClearFlightPlan {
+        result_sender: oneshot::Sender<bool>,
+    },
+    InsertWaypoint {
+        index: usize,
+        waypoint: Waypoint,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    DeleteWaypoint {
+        index: usize,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    DirectToWaypoint {
+        index: usize,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    SetWaypointConstraints {
+        index: usize,
+        altitude_constraint: Option<AltitudeConstraint>,
+        speed_constraint: Option<f64>,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    SequenceWaypoint {
         result_sender: oneshot::Sender<bool>,
     },
     SetHorizontalGuidanceToWingsLevelMode {
@@ -556,55 +2091,927 @@ pub(super) enum StateSignal {
     ActivateStandbyAltitude {
         result_sender: oneshot::Sender<bool>,
     },
+    SetStandbyVerticalSpeed {
+        standby_vertical_speed: f64,
+        result_sender: oneshot::Sender<bool>,
+    },
+    ActivateStandbyVerticalSpeed {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetFLCHThrottle {
+        climb_throttle: Option<f64>,
+        descent_throttle: Option<f64>,
+        result_sender: oneshot::Sender<bool>,
+    },
     SetVerticalGuidanceToStandbyMode {
         result_sender: oneshot::Sender<bool>,
     },
     SetVerticalGuidanceToTECSMode {
         result_sender: oneshot::Sender<bool>,
     },
-    AddToEnergyErrorIntegral {
-        value: f64,
+    SetVerticalGuidanceToVNAVMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetVerticalGuidanceToVSMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetVerticalGuidanceToFLCHMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    DisconnectAutoPilot {
+        reason: String,
         result_sender: oneshot::Sender<bool>,
     },
-    AddToPitchErrorIntegral {
+    SetFlightDirector {
+        flight_director_on: bool,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetAutoPilotCoupled {
+        coupled: bool,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateFlightDirectorCues {
+        result_sender: oneshot::Sender<bool>,
+    },
+    AcknowledgeDisconnect {
+        result_sender: oneshot::Sender<bool>,
+    },
+    OverrideChannel {
+        event: OverrideEvent,
+        result_sender: oneshot::Sender<bool>,
+    },
+    RecordCommand {
+        command_type: CommandType,
         value: f64,
         result_sender: oneshot::Sender<bool>,
     },
-    RefreshAutoPilotConstants {
+    UpdatePilotOverride {
+        pilot_override: PilotOverride,
         result_sender: oneshot::Sender<bool>,
     },
-    UpdateHorizontalAutoPilotMetrics {
-        metrics: AutoPilotHorizontalMetrics,
+    CaptureSelectedAltitude {
         result_sender: oneshot::Sender<bool>,
     },
-    UpdateVerticalAutoPilotMetrics {
-        metrics: AutoPilotVerticalMetrics,
+    UpdateAltitudeCapture {
+        capture_state: AltitudeCaptureState,
+        vertical_speed_command: f64,
         result_sender: oneshot::Sender<bool>,
     },
-}
+    SetAltitudeAlert {
+        altitude_alert: AltitudeAlert,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetAutoThrottleToStandbyMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetAutoThrottleToSpeedMode {
+        result_sender: oneshot::Sender<bool>,
+    },
+    ReleaseAutoThrottleThrHold {
+        throttle: f64,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetThrustLimit {
+        thrust_limit: ThrustLimit,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateAutoThrottle {
+        throttle_command: f64,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdatePid {
+        pid_loop: PidLoop,
+        pid: Pid,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateVerticalProfile {
+        profile: VerticalProfile,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetTECSPriority {
+        priority: TecsPriority,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetAutoPilotConstants {
+        constants: Box<AutoPilotConstants>,
+        source: String,
+        result_sender: oneshot::Sender<bool>,
+    },
+    RejectAutoPilotConstants {
+        source: String,
+        error: String,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetAircraftProfile {
+        name: String,
+        v_speeds: Option<VSpeeds>,
+        result_sender: oneshot::Sender<bool>,
+    },
+    SetProfileAutoSelect {
+        enabled: bool,
+        result_sender: oneshot::Sender<bool>,
+    },
+    ReturnConstantsHistory {
+        result_sender: oneshot::Sender<Vec<ConstantsVersion>>,
+    },
+    UpdateScheduledGains {
+        scheduled_gains: BTreeMap<String, f64>,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateLoopTiming {
+        timing: LoopTiming,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateEnvelopeProtection {
+        protection: EnvelopeProtection,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateHorizontalAutoPilotMetrics {
+        metrics: AutoPilotHorizontalMetrics,
+        result_sender: oneshot::Sender<bool>,
+    },
+    UpdateVerticalAutoPilotMetrics {
+        metrics: AutoPilotVerticalMetrics,
+        result_sender: oneshot::Sender<bool>,
+    },
+}
+
+impl StateSignal {
+    // everything but the questions for the state
+    fn changes_state(&self) -> bool {
+        !matches!(
+            self,
+            StateSignal::ReturnPlaneState { .. }
+                | StateSignal::ReturnPlaneStateStruct { .. }
+                | StateSignal::ReturnAutoPilotState { .. }
+                | StateSignal::ReturnConstantsHistory { .. }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct AppStateProxy {
+    pub service_adresses: (String, String, String),
+    pub state_sender: mpsc::Sender<StateSignal>,
+    pub state_changes: watch::Sender<u64>,
+}
+
+impl AppStateProxy {
+    pub fn new(
+        service_adresses: &(String, String, String),
+        state_sender: mpsc::Sender<StateSignal>,
+        state_changes: watch::Sender<u64>,
+    ) -> Self {
+        AppStateProxy {
+            service_adresses: service_adresses.clone(),
+            state_sender,
+            state_changes,
+        }
+    }
+
+    // changes whenever a mode, setpoint or metric changes
+    pub fn subscribe_to_changes(&self) -> watch::Receiver<u64> {
+        self.state_changes.subscribe()
+    }
+
+    // validated constants that take effect from the next cycle
+    pub async fn set_autopilot_constants(
+        &self,
+        constants: AutoPilotConstants,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::SetAutoPilotConstants {
+                constants: Box::new(constants),
+                source: source.to_string(),
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // constants that did not pass, the last good ones stay in effect
+    pub async fn reject_autopilot_constants(
+        &self,
+        source: &str,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::RejectAutoPilotConstants {
+                source: source.to_string(),
+                error: error.to_string(),
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // the profile in effect, none when only the constants file is used
+    pub async fn set_aircraft_profile(
+        &self,
+        profile: Option<&AircraftProfile>,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::SetAircraftProfile {
+                name: profile.map(|p| p.name.clone()).unwrap_or_default(),
+                v_speeds: profile.map(|p| p.v_speeds),
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_profile_auto_select(&self, enabled: bool) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::SetProfileAutoSelect {
+                enabled,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn get_constants_history(&self) -> anyhow::Result<Vec<ConstantsVersion>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::ReturnConstantsHistory { result_sender })
+            .await?;
+
+        Ok(result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+    }
+
+    pub async fn set_flying(&self, are_we_flying: bool) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::SetFlying {
+                are_we_flying,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // plane state
+    #[allow(dead_code)]
+    pub async fn get_plane_state(&self) -> anyhow::Result<BTreeMap<String, Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::ReturnPlaneState { result_sender })
+            .await?;
+
+        Ok(result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+    }
+
+    pub async fn get_plane_state_as_struct(&self) -> anyhow::Result<PlaneStateStruct> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::ReturnPlaneStateStruct { result_sender })
+            .await?;
+
+        result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from state"))
+    }
+
+    pub async fn set_plane_state(
+        &self,
+        plane_state: BTreeMap<String, Value>,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::SetPlaneState {
+                plane_state,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn clear_plane_state(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::ClearPlaneState { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // autopilot state
+
+    pub async fn get_auto_pilot_state(&self) -> anyhow::Result<AutoPilotState> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::ReturnAutoPilotState { result_sender })
+            .await?;
+
+        Ok(result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+    }
+
+    // horizontal modes
+
+    pub async fn set_heading_standby(&self, heading: f64) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetStandbyHeading {
+                standby_heading: heading,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_heading_setpoint(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ActivateStandbyHeading { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_horizontal_standby_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToStandbyMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_turn_direction(&self, turn_direction: TurnDirection) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetTurnDirection {
+                turn_direction,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_bank_limit(&self, bank_limit: BankLimit) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetBankLimit {
+                bank_limit,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_heading_reference(
+        &self,
+        heading_reference: HeadingReference,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHeadingReference {
+                heading_reference,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_horizontal_wingslevel_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToWingsLevelMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_horizontal_heading_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToHeadingMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_horizontal_track_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToTrackMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn activate_horizontal_LNAV_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToLNAVMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot activate LNAV without waypoints")),
+        }
+    }
+
+    pub async fn activate_horizontal_hold_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToHoldMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot activate HOLD without a hold set up")),
+        }
+    }
+
+    pub async fn activate_horizontal_orbit_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHorizontalGuidanceToOrbitMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot activate ORBIT without an orbit set up")),
+        }
+    }
+
+    pub async fn set_orbit(&self, orbit_setup: OrbitSetup) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetOrbit {
+                orbit_setup,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot set up the orbit")),
+        }
+    }
+
+    // holding
+
+    pub async fn set_hold(&self, hold_setup: HoldSetup) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetHold {
+                hold_setup,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot set up the hold")),
+        }
+    }
+
+    pub async fn update_holding_pattern(
+        &self,
+        holding_pattern: HoldingPattern,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::UpdateHoldingPattern {
+                holding_pattern,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // flight plan
+
+    pub async fn load_flight_plan(&self, waypoints: Vec<Waypoint>) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::LoadFlightPlan {
+                waypoints,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn clear_flight_plan(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ClearFlightPlan { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn insert_waypoint(&self, index: usize, waypoint: Waypoint) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::InsertWaypoint {
+                index,
+                waypoint,
+                result_sender,
+            })
+            .await?;
+
+        result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+    }
+
+    pub async fn set_waypoint_constraints(
+        &self,
+        index: usize,
+        altitude_constraint: Option<AltitudeConstraint>,
+        speed_constraint: Option<f64>,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetWaypointConstraints {
+                index,
+                altitude_constraint,
+                speed_constraint,
+                result_sender,
+            })
+            .await?;
+
+        result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+    }
+
+    pub async fn delete_waypoint(&self, index: usize) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::DeleteWaypoint {
+                index,
+                result_sender,
+            })
+            .await?;
+
+        result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+    }
+
+    pub async fn direct_to_waypoint(&self, index: usize) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::DirectToWaypoint {
+                index,
+                result_sender,
+            })
+            .await?;
+
+        result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+    }
+
+    pub async fn sequence_waypoint(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SequenceWaypoint { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // vertical modes
+
+    pub async fn set_velocity_standby(&self, velocity: f64) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetStandbyVelocity {
+                standby_velocity: velocity,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_velocity_setpoint(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ActivateStandbyVelocity { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_altitude_standby(&self, altitude: f64) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetStandbyAltitude {
+                standby_altitude: altitude,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_altitude_setpoint(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ActivateStandbyAltitude { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_vertical_speed_standby(&self, vertical_speed: f64) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetStandbyVerticalSpeed {
+                standby_vertical_speed: vertical_speed,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_vertical_speed_setpoint(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ActivateStandbyVerticalSpeed { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn set_flch_throttle(
+        &self,
+        climb_throttle: Option<f64>,
+        descent_throttle: Option<f64>,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetFLCHThrottle {
+                climb_throttle,
+                descent_throttle,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
 
-#[derive(Clone)]
-pub(super) struct AppStateProxy {
-    pub service_adresses: (String, String, String),
-    pub state_sender: mpsc::Sender<StateSignal>,
-}
+    pub async fn arm_horizontal_mode(&self, mode: HorizontalModes) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ArmHorizontalMode {
+                mode,
+                result_sender,
+            })
+            .await?;
 
-impl AppStateProxy {
-    pub fn new(
-        service_adresses: &(String, String, String),
-        state_sender: mpsc::Sender<StateSignal>,
-    ) -> Self {
-        AppStateProxy {
-            service_adresses: service_adresses.clone(),
-            state_sender,
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
         }
     }
 
-    pub async fn refresh_autopilot_constants(&self) -> anyhow::Result<()> {
+    pub async fn arm_vertical_mode(&self, mode: VerticalModes) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ArmVerticalMode {
+                mode,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
 
+    pub async fn disarm_modes(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::RefreshAutoPilotConstants { result_sender })
+            .send(StateSignal::DisarmModes { result_sender })
             .await?;
 
         match result_receiver
@@ -616,12 +3023,28 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn set_flying(&self, are_we_flying: bool) -> anyhow::Result<()> {
+    // the guidance found the leg of an armed LNAV
+    pub async fn engage_armed_horizontal_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::EngageArmedHorizontalMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
 
+    // all channels to standby, e.g. when the plane state is lost
+    pub async fn revert_to_standby(&self, reason: &str) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetFlying {
-                are_we_flying,
+            .send(StateSignal::RevertToStandby {
+                reason: reason.to_string(),
                 result_sender,
             })
             .await?;
@@ -635,41 +3058,57 @@ impl AppStateProxy {
         }
     }
 
-    // plane state
-    #[allow(dead_code)]
-    pub async fn get_plane_state(&self) -> anyhow::Result<BTreeMap<String, Value>> {
+    // all channels to standby with an alert for the pilot, e.g. when the envelope is exceeded
+    pub async fn disconnect_autopilot(&self, reason: &str) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
-
         self.state_sender
-            .send(StateSignal::ReturnPlaneState { result_sender })
+            .send(StateSignal::DisconnectAutoPilot {
+                reason: reason.to_string(),
+                result_sender,
+            })
             .await?;
 
-        Ok(result_receiver
+        match result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
     }
 
-    pub async fn get_plane_state_as_struct(&self) -> anyhow::Result<PlaneStateStruct> {
+    // the pilot took a channel over, it goes to standby and the event is logged
+    pub async fn override_channel(&self, event: OverrideEvent) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::ReturnPlaneStateStruct { result_sender })
+            .send(StateSignal::OverrideChannel {
+                event,
+                result_sender,
+            })
             .await?;
 
-        Ok(result_receiver
+        match result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
     }
 
-    pub async fn set_plane_state(
+    // the command as it was sent to the sim, so a pilot flying against it can be detected
+    pub async fn record_command(
         &self,
-        plane_state: BTreeMap<String, Value>,
+        command_type: CommandType,
+        value: f64,
     ) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::SetPlaneState {
-                plane_state,
+            .send(StateSignal::RecordCommand {
+                command_type,
+                value,
                 result_sender,
             })
             .await?;
@@ -683,11 +3122,17 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn clear_plane_state(&self) -> anyhow::Result<()> {
+    pub async fn update_pilot_override(
+        &self,
+        pilot_override: PilotOverride,
+    ) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::ClearPlaneState { result_sender })
+            .send(StateSignal::UpdatePilotOverride {
+                pilot_override,
+                result_sender,
+            })
             .await?;
 
         match result_receiver
@@ -699,29 +3144,79 @@ impl AppStateProxy {
         }
     }
 
-    // autopilot state
+    // the flight director shows the cues of the active modes
+    pub async fn set_flight_director(&self, flight_director_on: bool) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetFlightDirector {
+                flight_director_on,
+                result_sender,
+            })
+            .await?;
 
-    pub async fn get_auto_pilot_state(&self) -> anyhow::Result<AutoPilotState> {
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // an uncoupled autopilot keeps its modes but leaves the controls to the pilot
+    pub async fn set_autopilot_coupled(&self, coupled: bool) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetAutoPilotCoupled {
+                coupled,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
 
+    pub async fn update_flight_director_cues(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::ReturnAutoPilotState { result_sender })
+            .send(StateSignal::UpdateFlightDirectorCues { result_sender })
             .await?;
 
-        Ok(result_receiver
+        match result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from state")))
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
     }
 
-    // horizontal modes
+    pub async fn acknowledge_disconnect(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::AcknowledgeDisconnect { result_sender })
+            .await?;
 
-    pub async fn set_heading_standby(&self, heading: f64) -> anyhow::Result<()> {
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    // VS and FLCH hand over to TECS to level off at the selected altitude
+    pub async fn capture_selected_altitude(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetStandbyHeading {
-                standby_heading: heading,
-                result_sender,
-            })
+            .send(StateSignal::CaptureSelectedAltitude { result_sender })
             .await?;
 
         match result_receiver
@@ -733,10 +3228,10 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_heading_setpoint(&self) -> anyhow::Result<()> {
+    pub async fn activate_vertical_standby_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::ActivateStandbyHeading { result_sender })
+            .send(StateSignal::SetVerticalGuidanceToStandbyMode { result_sender })
             .await?;
 
         match result_receiver
@@ -748,10 +3243,11 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_horizontal_standby_mode(&self) -> anyhow::Result<()> {
+    #[allow(non_snake_case)]
+    pub async fn activate_vertical_TECS_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetHorizontalGuidanceToStandbyMode { result_sender })
+            .send(StateSignal::SetVerticalGuidanceToTECSMode { result_sender })
             .await?;
 
         match result_receiver
@@ -763,10 +3259,27 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_horizontal_wingslevel_mode(&self) -> anyhow::Result<()> {
+    #[allow(non_snake_case)]
+    pub async fn activate_vertical_VNAV_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetHorizontalGuidanceToWingsLevelMode { result_sender })
+            .send(StateSignal::SetVerticalGuidanceToVNAVMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Cannot activate VNAV without waypoints")),
+        }
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn activate_vertical_VS_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetVerticalGuidanceToVSMode { result_sender })
             .await?;
 
         match result_receiver
@@ -778,10 +3291,11 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_horizontal_heading_mode(&self) -> anyhow::Result<()> {
+    #[allow(non_snake_case)]
+    pub async fn activate_vertical_FLCH_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetHorizontalGuidanceToHeadingMode { result_sender })
+            .send(StateSignal::SetVerticalGuidanceToFLCHMode { result_sender })
             .await?;
 
         match result_receiver
@@ -793,12 +3307,17 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn add_to_heading_error_integral(&self, value: f64) -> anyhow::Result<()> {
+    pub async fn update_altitude_capture(
+        &self,
+        capture_state: AltitudeCaptureState,
+        vertical_speed_command: f64,
+    ) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::AddToHeadingErrorIntegral {
-                value,
+            .send(StateSignal::UpdateAltitudeCapture {
+                capture_state,
+                vertical_speed_command,
                 result_sender,
             })
             .await?;
@@ -812,12 +3331,12 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn add_to_roll_error_integral(&self, value: f64) -> anyhow::Result<()> {
+    pub async fn set_altitude_alert(&self, altitude_alert: AltitudeAlert) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::AddToRollErrorIntegral {
-                value,
+            .send(StateSignal::SetAltitudeAlert {
+                altitude_alert,
                 result_sender,
             })
             .await?;
@@ -831,13 +3350,43 @@ impl AppStateProxy {
         }
     }
 
-    // vertical modes
+    // autothrottle
 
-    pub async fn set_velocity_standby(&self, velocity: f64) -> anyhow::Result<()> {
+    pub async fn activate_autothrottle_standby_mode(&self) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetStandbyVelocity {
-                standby_velocity: velocity,
+            .send(StateSignal::SetAutoThrottleToStandbyMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn activate_autothrottle_speed_mode(&self) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetAutoThrottleToSpeedMode { result_sender })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn release_autothrottle_thr_hold(&self, throttle: f64) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::ReleaseAutoThrottleThrHold {
+                throttle,
                 result_sender,
             })
             .await?;
@@ -851,10 +3400,13 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_velocity_setpoint(&self) -> anyhow::Result<()> {
+    pub async fn set_thrust_limit(&self, thrust_limit: ThrustLimit) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::ActivateStandbyVelocity { result_sender })
+            .send(StateSignal::SetThrustLimit {
+                thrust_limit,
+                result_sender,
+            })
             .await?;
 
         match result_receiver
@@ -866,11 +3418,11 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn set_altitude_standby(&self, altitude: f64) -> anyhow::Result<()> {
+    pub async fn update_autothrottle(&self, throttle_command: f64) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::SetStandbyAltitude {
-                standby_altitude: altitude,
+            .send(StateSignal::UpdateAutoThrottle {
+                throttle_command,
                 result_sender,
             })
             .await?;
@@ -884,10 +3436,15 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_altitude_setpoint(&self) -> anyhow::Result<()> {
+    // stores the state of a loop after its update
+    pub async fn update_pid(&self, pid_loop: PidLoop, pid: Pid) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::ActivateStandbyAltitude { result_sender })
+            .send(StateSignal::UpdatePid {
+                pid_loop,
+                pid,
+                result_sender,
+            })
             .await?;
 
         match result_receiver
@@ -899,10 +3456,14 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn activate_vertical_standby_mode(&self) -> anyhow::Result<()> {
+    pub async fn update_vertical_profile(&self, profile: VerticalProfile) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
+
         self.state_sender
-            .send(StateSignal::SetVerticalGuidanceToStandbyMode { result_sender })
+            .send(StateSignal::UpdateVerticalProfile {
+                profile,
+                result_sender,
+            })
             .await?;
 
         match result_receiver
@@ -914,11 +3475,14 @@ impl AppStateProxy {
         }
     }
 
-    #[allow(non_snake_case)]
-    pub async fn activate_vertical_TECS_mode(&self) -> anyhow::Result<()> {
+    pub async fn set_tecs_priority(&self, priority: TecsPriority) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
+
         self.state_sender
-            .send(StateSignal::SetVerticalGuidanceToTECSMode { result_sender })
+            .send(StateSignal::SetTECSPriority {
+                priority,
+                result_sender,
+            })
             .await?;
 
         match result_receiver
@@ -930,12 +3494,15 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn add_to_energy_error_integral(&self, value: f64) -> anyhow::Result<()> {
+    pub async fn update_scheduled_gains(
+        &self,
+        scheduled_gains: BTreeMap<String, f64>,
+    ) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::AddToEnergyErrorIntegral {
-                value,
+            .send(StateSignal::UpdateScheduledGains {
+                scheduled_gains,
                 result_sender,
             })
             .await?;
@@ -949,12 +3516,34 @@ impl AppStateProxy {
         }
     }
 
-    pub async fn add_to_pitch_error_integral(&self, value: f64) -> anyhow::Result<()> {
+    pub async fn update_loop_timing(&self, timing: LoopTiming) -> anyhow::Result<()> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         self.state_sender
-            .send(StateSignal::AddToPitchErrorIntegral {
-                value,
+            .send(StateSignal::UpdateLoopTiming {
+                timing,
+                result_sender,
+            })
+            .await?;
+
+        match result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive result from auto pilot state"))
+        {
+            true => Ok(()),
+            _ => Err(anyhow!("Error with receiving result from autopilot state")),
+        }
+    }
+
+    pub async fn update_envelope_protection(
+        &self,
+        protection: EnvelopeProtection,
+    ) -> anyhow::Result<()> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        self.state_sender
+            .send(StateSignal::UpdateEnvelopeProtection {
+                protection,
                 result_sender,
             })
             .await?;
@@ -1018,6 +3607,7 @@ pub struct Command {
     pub value: f64,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum CommandType {
     Aileron,
     Elevator,
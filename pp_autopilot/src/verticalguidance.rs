@@ -1,8 +1,15 @@
 use crate::AutoPilotVerticalMetrics;
 
 use super::{
+    altitudecapture::{
+        self, AltitudeAlert, AltitudeCaptureState, AltitudeHoldCommand, AltitudeHoldInput,
+    },
+    autothrottle,
+    navigation::{Position, GRAVITATIONAL_ACCELERATION, KNOTS_TO_METERS_PER_SECOND},
+    pid::{Pid, PidGains, PidInput},
     send_command,
-    types::{CommandType, VerticalModes},
+    types::{AutoPilotConstants, CommandType, PidLoop, TecsPriority, VerticalModes},
+    verticalprofile::{self, VerticalProfile, VerticalProfileInput},
 };
 
 pub(super) async fn execute_vertical_guidance(
@@ -12,171 +19,856 @@ pub(super) async fn execute_vertical_guidance(
     auto_pilot_state: &super::types::AutoPilotState,
     plane_state_struct: &super::types::PlaneStateStruct,
 ) -> anyhow::Result<()> {
-    const KNOTS_TO_METERS_PER_SECOND: f64 = 0.514444;
-    const FEET_TO_METERS: f64 = 0.3048;
-    const GRAVITATIONAL_CONSTANT: f64 = 0.981;
+    update_altitude_alert(app_state_proxy, auto_pilot_state, plane_state_struct).await?;
 
     match auto_pilot_state.vertical_guidance.vertical_mode {
         VerticalModes::Standby => {}
         VerticalModes::TECS => {
-            let flight_path_commanded: f64 = 0.0;
-
-            let flight_path_error: f64 = flight_path_commanded - plane_state_struct.vpath;
-            let velocity_over_g: f64 = - plane_state_struct.gload_axial / GRAVITATIONAL_CONSTANT;
+            let vertical_guidance = &auto_pilot_state.vertical_guidance;
+            let constants = &auto_pilot_state.control_constants;
+
+            // climb or descend to the selected altitude, then capture and hold it
+            let hold: AltitudeHoldCommand = altitudecapture::altitude_hold(
+                &AltitudeHoldInput {
+                    altitude_error: vertical_guidance.altitude_setpoint
+                        - plane_state_struct.altitude_msl,
+                    vertical_speed: plane_state_struct.vertical_speed,
+                    capture_state: vertical_guidance.altitude_capture_state,
+                    vertical_speed_command: vertical_guidance.vertical_speed_command,
+                    capture_time: constants.altitude_capture_time,
+                    minimum_capture_distance: constants.altitude_capture_margin,
+                    climb_vertical_speed: constants.altitude_climb_vertical_speed,
+                    vertical_acceleration: constants.altitude_vertical_acceleration,
+                },
+                dt,
+            );
 
-            let energy_error = flight_path_error + velocity_over_g;
+            if hold.capture_state != vertical_guidance.altitude_capture_state {
+                tracing::event!(
+                    tracing::Level::INFO,
+                    "Altitude capture {:?} -> {:?} at {:.0} ft, selected altitude {} ft",
+                    vertical_guidance.altitude_capture_state,
+                    hold.capture_state,
+                    plane_state_struct.altitude_msl,
+                    vertical_guidance.altitude_setpoint
+                );
+            }
 
             app_state_proxy
-                .add_to_energy_error_integral(energy_error * dt)
+                .update_altitude_capture(hold.capture_state, hold.vertical_speed_command)
                 .await?;
 
-            // throttle
+            // far from the selected altitude TECS sets the climb or descent itself, the level off follows the capture law
+            let flight_path_commanded: Option<f64> = match hold.capture_state {
+                AltitudeCaptureState::Inactive => None,
+                AltitudeCaptureState::Capture | AltitudeCaptureState::Hold => {
+                    Some(flight_path_for_vertical_speed(
+                        hold.vertical_speed_command,
+                        plane_state_struct.v_ground,
+                    ))
+                }
+            };
+
+            let targets = TecsTargets {
+                altitude: vertical_guidance.altitude_setpoint,
+                velocity: vertical_guidance.velocity_setpoint,
+                flight_path_commanded,
+            };
 
-            let Kti: f64 = 0.10;
-            let Ktii = 0.10;
+            execute_tecs(
+                dt,
+                client,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                &targets,
+            )
+            .await?;
+        }
+        VerticalModes::VNAV => {
+            let constants = &auto_pilot_state.control_constants;
+
+            let profile: VerticalProfile = verticalprofile::compute_vertical_profile(
+                &auto_pilot_state.flight_plan,
+                &VerticalProfileInput {
+                    position: Position {
+                        latitude: plane_state_struct.latitude,
+                        longitude: plane_state_struct.longitude,
+                    },
+                    altitude: plane_state_struct.altitude_msl,
+                    selected_altitude: auto_pilot_state.vertical_guidance.altitude_setpoint,
+                    selected_velocity: auto_pilot_state.vertical_guidance.velocity_setpoint,
+                    climb_gradient: constants.vnav_climb_gradient,
+                    descent_angle: constants.vnav_descent_angle,
+                },
+            );
 
-            let throttle: f64 = ((Kti * (energy_error))
-                + (auto_pilot_state.vertical_guidance.energy_error_integral * Ktii))
-                .clamp(0.0, 1.0);
+            // on the descent path fly its path angle, corrected for the deviation from the path,
+            // otherwise TECS climbs or descends to the target altitude
+            let vertical_deviation: f64 =
+                profile.vnav_altitude_target - plane_state_struct.altitude_msl;
+            let flight_path_commanded: Option<f64> = match profile.vnav_flight_path_angle {
+                angle if angle != 0.0 => Some(angle + constants.vnav_altitude_p * vertical_deviation),
+                _ => None,
+            };
 
-            // elevator
+            tracing::event!(tracing::Level::TRACE,
+                "VNAV mode - altitude target [ft]: {:.4}, velocity target [kt]: {:.4}, path angle [deg]: {:.4}, deviation [ft]: {:.4}, toc [nm]: {:?}, tod [nm]: {:?}",
+                profile.vnav_altitude_target, profile.vnav_velocity_target, profile.vnav_flight_path_angle, vertical_deviation, profile.top_of_climb_distance, profile.top_of_descent_distance
+            );
 
-            let energy_distribution_error = flight_path_error - velocity_over_g;
+            let targets = TecsTargets {
+                altitude: profile.vnav_altitude_target,
+                velocity: profile.vnav_velocity_target,
+                flight_path_commanded,
+            };
 
-            app_state_proxy
-                .add_to_pitch_error_integral(energy_distribution_error * dt)
-                .await?;
+            app_state_proxy.update_vertical_profile(profile).await?;
+
+            execute_tecs(
+                dt,
+                client,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                &targets,
+            )
+            .await?;
+        }
+        VerticalModes::VS => {
+            let vertical_speed_target: f64 =
+                auto_pilot_state.vertical_guidance.vertical_speed_setpoint;
+
+            let flight_path_target: f64 =
+                flight_path_for_vertical_speed(vertical_speed_target, plane_state_struct.v_ground);
+
+            let target_pitch: f64 = vertical_speed_pitch(
+                plane_state_struct.pitch,
+                plane_state_struct.vpath,
+                flight_path_target,
+                auto_pilot_state.control_constants.max_pitch,
+            );
 
-            let Kei: f64 = 0.04;
-            let Keii = 0.02;
+            let pitch_command: PitchCommand = command_pitch_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_pitch,
+            )
+            .await?;
+
+            // speed on the throttle, unless the autothrottle holds it
+            let velocity_error: f64 =
+                auto_pilot_state.vertical_guidance.velocity_setpoint - plane_state_struct.v_ind;
+
+            let mut speed_pid: Pid = auto_pilot_state.vertical_guidance.speed_pid;
+            let throttle: f64 = speed_pid.update(
+                &PidGains {
+                    kp: auto_pilot_state.control_constants.speed_throttle_p,
+                    output_min: 0.0,
+                    output_max: 1.0,
+                    ..Default::default()
+                },
+                &PidInput {
+                    error: velocity_error,
+                    measurement: plane_state_struct.v_ind,
+                    feedforward: auto_pilot_state.control_constants.tecs_cruise_throttle_base,
+                    ..Default::default()
+                },
+                dt,
+            );
 
-            let elevator = ((Kei * (energy_distribution_error))
-                + (Keii * auto_pilot_state.vertical_guidance.pitch_error_integral))
-                .clamp(
-                    -auto_pilot_state.control_constants.max_elevator,
-                    auto_pilot_state.control_constants.max_elevator,
-                );
+            app_state_proxy.update_pid(PidLoop::Speed, speed_pid).await?;
 
-            println!(
-                "TEC mode - alitude [ft]: {:.4}, Vind [kt]: {:.4}, f_path_err: {:.4}, v_g: {:.4}, e_err: {:.4},e_err_int: {:.4}, thrust: {:.4}, energy distr error: {:.4}, elevator: {:.4}",
-                plane_state_struct.altitude_msl, plane_state_struct.v_ind, flight_path_error, velocity_over_g, energy_error, auto_pilot_state.vertical_guidance.energy_error_integral, throttle, energy_distribution_error, elevator
+            tracing::event!(tracing::Level::TRACE,
+                "VS mode - vertical speed [fpm]: {:.4}, target vertical speed [fpm]: {:.4}, target flight path [deg]: {:.4}, target pitch [deg]: {:.4}, elevator: {:.4}, throttle: {:.4}",
+                plane_state_struct.vertical_speed, vertical_speed_target, flight_path_target, target_pitch, pitch_command.elevator, throttle
             );
 
-            send_command(app_state_proxy, &client, CommandType::Throttle, throttle).await?;
-            send_command(app_state_proxy, &client, CommandType::Elevator, elevator).await?;
+            let vertical_metrics = AutoPilotVerticalMetrics {
+                vertical_speed_target,
+                vertical_speed_error: vertical_speed_target - plane_state_struct.vertical_speed,
+                velocity_target: auto_pilot_state.vertical_guidance.velocity_setpoint,
+                velocity_error,
+                throttle_setpoint: throttle,
+                ..pitch_metrics(auto_pilot_state, plane_state_struct, &pitch_command)
+            };
 
-            /*
+            app_state_proxy.update_vertical_control_metrics(vertical_metrics).await?;
+            if !autothrottle::is_engaged(auto_pilot_state) {
+                send_command(
+                    app_state_proxy,
+                    client,
+                    auto_pilot_state,
+                    CommandType::Throttle,
+                    throttle,
+                )
+                .await?;
+            }
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Elevator,
+                pitch_command.elevator,
+            )
+            .await?;
+
+            capture_selected_altitude(app_state_proxy, auto_pilot_state, plane_state_struct)
+                .await?;
+        }
+        VerticalModes::FLCH => {
+            let altitude_error: f64 =
+                auto_pilot_state.vertical_guidance.altitude_setpoint - plane_state_struct.altitude_msl;
+
+            // fixed thrust (not when the autothrottle is engaged)
+            let throttle: f64 = flch_throttle(
+                altitude_error,
+                auto_pilot_state.vertical_guidance.flch_climb_throttle,
+                auto_pilot_state.vertical_guidance.flch_descent_throttle,
+            );
 
+            let velocity_error: f64 =
+                auto_pilot_state.vertical_guidance.velocity_setpoint - plane_state_struct.v_ind;
+
+            let mut flch_pid: Pid = auto_pilot_state.vertical_guidance.flch_pid;
+            let target_pitch: f64 = flch_pitch(
+                &mut flch_pid,
+                &auto_pilot_state.control_constants,
+                velocity_error,
+                plane_state_struct.v_ind,
+                plane_state_struct.pitch,
+                dt,
+            );
 
-            // calculate specific (so no mass term) energy target
-            let target_kinetic: f64 = 0.5
-                * (auto_pilot_state.vertical_guidance.velocity_setpoint
-                    * KNOTS_TO_METERS_PER_SECOND)
-                * (auto_pilot_state.vertical_guidance.velocity_setpoint
-                    * KNOTS_TO_METERS_PER_SECOND); //speed to m/s
-            let target_potential: f64 = (auto_pilot_state.vertical_guidance.altitude_setpoint
-                * FEET_TO_METERS)
-                * GRAVITATIONAL_CONSTANT; // altitude to m
+            app_state_proxy.update_pid(PidLoop::FlightLevelChange, flch_pid).await?;
 
-            let target_energy: f64 = target_kinetic + target_potential;
+            let pitch_command: PitchCommand = command_pitch_angle(
+                dt,
+                app_state_proxy,
+                auto_pilot_state,
+                plane_state_struct,
+                target_pitch,
+            )
+            .await?;
 
-            let kinetic: f64 = 0.5
-                * (plane_state_struct.v_ind * KNOTS_TO_METERS_PER_SECOND)
-                * (plane_state_struct.v_ind * KNOTS_TO_METERS_PER_SECOND);
-            let potential: f64 =
-                (plane_state_struct.altitude_msl * FEET_TO_METERS) * GRAVITATIONAL_CONSTANT;
-            let energy: f64 = kinetic + potential;
+            tracing::event!(tracing::Level::TRACE,
+                "FLCH mode - Vind [kt]: {:.4}, velocity error [kt]: {:.4}, altitude error [ft]: {:.4}, target pitch [deg]: {:.4}, elevator: {:.4}, throttle: {:.4}",
+                plane_state_struct.v_ind, velocity_error, altitude_error, target_pitch, pitch_command.elevator, throttle
+            );
 
-            let energy_error: f64 = target_energy - energy;
+            let vertical_metrics = AutoPilotVerticalMetrics {
+                velocity_target: auto_pilot_state.vertical_guidance.velocity_setpoint,
+                velocity_error,
+                throttle_setpoint: throttle,
+                ..pitch_metrics(auto_pilot_state, plane_state_struct, &pitch_command)
+            };
 
-            app_state_proxy
-                .add_to_energy_error_integral(energy_error * dt)
+            app_state_proxy.update_vertical_control_metrics(vertical_metrics).await?;
+            if !autothrottle::is_engaged(auto_pilot_state) {
+                send_command(
+                    app_state_proxy,
+                    client,
+                    auto_pilot_state,
+                    CommandType::Throttle,
+                    throttle,
+                )
                 .await?;
+            }
+            send_command(
+                app_state_proxy,
+                client,
+                auto_pilot_state,
+                CommandType::Elevator,
+                pitch_command.elevator,
+            )
+            .await?;
+
+            capture_selected_altitude(app_state_proxy, auto_pilot_state, plane_state_struct)
+                .await?;
+        }
+    }
 
-            dbg!(energy_error * dt);
-
-            let ke: f64 = auto_pilot_state.control_constants.tecs_energy_p;
-            let ks = auto_pilot_state.control_constants.tecs_cruise_throttle_slope;
-            let thr_cruise = auto_pilot_state.control_constants.tecs_cruise_throttle_base + target_energy * ks;
+    Ok(())
+}
 
-            let ki = auto_pilot_state.control_constants.tecs_energy_i;
+const FEET_PER_MINUTE_PER_KNOT: f64 = 101.269;
 
-            let throttle = (ke * energy_error
-                + thr_cruise
-                + auto_pilot_state.vertical_guidance.energy_error_integral * ki)
-                .clamp(0.0, 1.0);
+// the flight path angle [deg] for a vertical speed [fpm] at a ground speed [kt]
+fn flight_path_for_vertical_speed(vertical_speed: f64, ground_speed: f64) -> f64 {
+    (vertical_speed / (ground_speed.max(30.0) * FEET_PER_MINUTE_PER_KNOT))
+        .atan()
+        .to_degrees()
+}
 
-            //todo
+// the acceleration along the flight path [g] from the axial g load. xplane's axial g load is the
+// specific force along the body, positive backwards, so it is -(dV/dt / g + sin(flight path)). the
+// flight path part is taken out, TECS has it in the flight path error already
+fn acceleration_along_path(gload_axial: f64, flight_path: f64) -> f64 {
+    -gload_axial - flight_path.sin()
+}
 
-            println!(
-                "TEC mode - alitude [ft]: {:.4}, Vind [kt]: {:.4}, energy_error: {:.4}, integral: {:.4}, throttle: {:.4}",
-                plane_state_struct.altitude_msl, plane_state_struct.v_ind, energy_error, auto_pilot_state.vertical_guidance.energy_error_integral, throttle
+// alert when approaching the selected altitude, or when deviating from it after the capture
+async fn update_altitude_alert(
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+) -> anyhow::Result<()> {
+    let altitude_alert: AltitudeAlert = match auto_pilot_state.vertical_guidance.vertical_mode {
+        VerticalModes::TECS | VerticalModes::VS | VerticalModes::FLCH => {
+            altitudecapture::altitude_alert(
+                auto_pilot_state.vertical_guidance.altitude_setpoint
+                    - plane_state_struct.altitude_msl,
+                auto_pilot_state.vertical_guidance.altitude_capture_state,
+            )
+        }
+        VerticalModes::Standby | VerticalModes::VNAV => AltitudeAlert::None,
+    };
+
+    if altitude_alert != auto_pilot_state.vertical_guidance.altitude_alert {
+        if altitude_alert != AltitudeAlert::None {
+            tracing::event!(
+                tracing::Level::WARN,
+                "Altitude alert {:?} at {:.0} ft, selected altitude {} ft",
+                altitude_alert,
+                plane_state_struct.altitude_msl,
+                auto_pilot_state.vertical_guidance.altitude_setpoint
             );
+        }
+
+        app_state_proxy.set_altitude_alert(altitude_alert).await?;
+    }
 
-            send_command(app_state_proxy, client, CommandType::Throttle, throttle).await?;
+    Ok(())
+}
 
-            // pitch
+// VS and FLCH hand over to TECS at the capture distance, TECS then levels off and holds the selected altitude
+async fn capture_selected_altitude(
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+) -> anyhow::Result<()> {
+    let altitude_error: f64 =
+        auto_pilot_state.vertical_guidance.altitude_setpoint - plane_state_struct.altitude_msl;
+
+    if selected_altitude_reached(
+        altitude_error,
+        plane_state_struct.vertical_speed,
+        &auto_pilot_state.control_constants,
+    ) {
+        tracing::event!(
+            tracing::Level::INFO,
+            "Selected altitude {} ft captured from {:?} mode",
+            auto_pilot_state.vertical_guidance.altitude_setpoint,
+            auto_pilot_state.vertical_guidance.vertical_mode
+        );
+        app_state_proxy.capture_selected_altitude().await?;
+    }
 
-            let kpitch: f64 = auto_pilot_state.control_constants.pitch_error_p;
+    Ok(())
+}
 
-            let target_pitch: f64 = ((auto_pilot_state.vertical_guidance.velocity_setpoint
-                - plane_state_struct.v_ind)
-                * kpitch)
-                .clamp(-auto_pilot_state.control_constants.max_pitch, auto_pilot_state.control_constants.max_pitch);
-            let pitch_error = target_pitch - plane_state_struct.pitch;
+// the capture starts when the selected altitude is within the capture distance for the vertical speed
+fn selected_altitude_reached(
+    altitude_error: f64,
+    vertical_speed: f64,
+    constants: &AutoPilotConstants,
+) -> bool {
+    let capture_distance: f64 = altitudecapture::capture_distance(
+        vertical_speed,
+        constants.altitude_capture_time,
+        constants.altitude_capture_margin,
+    );
+
+    altitude_error.abs() <= capture_distance
+}
 
-            app_state_proxy
-                .add_to_pitch_error_integral(pitch_error * dt)
-                .await?;
+// VS: pitch is angle of attack plus flight path angle [deg], keep the current angle of attack
+fn vertical_speed_pitch(
+    pitch: f64,
+    flight_path: f64,
+    flight_path_target: f64,
+    max_pitch: f64,
+) -> f64 {
+    (pitch - flight_path + flight_path_target).clamp(-max_pitch, max_pitch)
+}
 
-            let kpr = auto_pilot_state.control_constants.pitch_rate_error_p;
+// FLCH: climb or descent thrust depending on where the selected altitude is
+fn flch_throttle(altitude_error: f64, climb_throttle: f64, descent_throttle: f64) -> f64 {
+    if altitude_error >= 0.0 {
+        climb_throttle
+    } else {
+        descent_throttle
+    }
+}
 
-            let target_pitch_rate = (pitch_error * kpr).clamp(-auto_pilot_state.control_constants.max_pitch_rate, auto_pilot_state.control_constants.max_pitch_rate);
-            let pitch_rate_error = target_pitch_rate - plane_state_struct.pitch_rate;
+// FLCH: speed on the elevator, too slow means pitch down (the gain is negative). it has its own loop,
+// the speed loop on the throttle of VS does the opposite
+fn flch_pitch(
+    flch_pid: &mut Pid,
+    constants: &AutoPilotConstants,
+    velocity_error: f64,
+    v_ind: f64,
+    pitch: f64,
+    dt: f64,
+) -> f64 {
+    flch_pid.update(
+        &PidGains {
+            kp: constants.flch_pitch_p,
+            output_min: -constants.max_pitch,
+            output_max: constants.max_pitch,
+            ..Default::default()
+        },
+        &PidInput {
+            error: velocity_error,
+            measurement: v_ind,
+            feedforward: pitch,
+            ..Default::default()
+        },
+        dt,
+    )
+}
 
-            let kelevator = auto_pilot_state.control_constants.elevator_p;
-            let kdelevator = auto_pilot_state.control_constants.elevator_d;
-            let kielevator: f64 = auto_pilot_state.control_constants.elevator_i;
+struct PitchCommand {
+    pitch_error: f64,
+    target_pitch_rate: f64,
+    pitch_rate_error: f64,
+    elevator: f64,
+}
 
-            let elevator = (kelevator * pitch_error
-                + kdelevator * pitch_rate_error
-                + kielevator * auto_pilot_state.vertical_guidance.pitch_error_integral)
-                .clamp(-auto_pilot_state.control_constants.max_elevator, auto_pilot_state.control_constants.max_elevator);
+// inner loop shared by the vertical modes that fly a pitch attitude: fly a target pitch angle with the elevator
+async fn command_pitch_angle(
+    dt: f64,
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+    target_pitch: f64,
+) -> anyhow::Result<PitchCommand> {
+    let pitch_error: f64 = target_pitch - plane_state_struct.pitch;
+
+    let kpr: f64 = auto_pilot_state.control_constants.pitch_rate_error_p;
+
+    let target_pitch_rate: f64 = (pitch_error * kpr).clamp(
+        -auto_pilot_state.control_constants.max_pitch_rate,
+        auto_pilot_state.control_constants.max_pitch_rate,
+    );
+    let pitch_rate_error: f64 = target_pitch_rate - plane_state_struct.pitch_rate;
+
+    let constants = &auto_pilot_state.control_constants;
+
+    let gains: PidGains = PidGains {
+        kp: constants.elevator_p,
+        ki: constants.elevator_i,
+        kd: constants.elevator_d,
+        output_min: -constants.max_elevator,
+        output_max: constants.max_elevator,
+        derivative_filter_time: constants.derivative_filter_time,
+        ..Default::default()
+    };
+
+    // the damping acts on the pitch rate error, so the target pitch rate is fed forward
+    let mut pitch_pid: Pid = auto_pilot_state.vertical_guidance.pitch_pid;
+    let elevator: f64 = pitch_pid.update(
+        &gains,
+        &PidInput {
+            error: pitch_error,
+            measurement: plane_state_struct.pitch,
+            measurement_rate: Some(plane_state_struct.pitch_rate),
+            feedforward: constants.elevator_d * target_pitch_rate,
+        },
+        dt,
+    );
+
+    app_state_proxy.update_pid(PidLoop::Pitch, pitch_pid).await?;
+
+    Ok(PitchCommand {
+        pitch_error,
+        target_pitch_rate,
+        pitch_rate_error,
+        elevator,
+    })
+}
 
-            tracing::event!(tracing::Level::TRACE,
-                "TEC mode - pitch [deg]: {:.4}, target_pitch [deg]: {:.4}, pitch_error [deg]: {:.4}, pitch_rate: {:.4}, target_pitch_rate: {:.4}, pitch_rate_error: {:.4}, elevator {:.4}",
-                plane_state_struct.pitch, target_pitch, pitch_error, plane_state_struct.pitch_rate, target_pitch_rate, pitch_rate_error, elevator
-            );
+fn pitch_metrics(
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+    pitch_command: &PitchCommand,
+) -> AutoPilotVerticalMetrics {
+    AutoPilotVerticalMetrics {
+        altitude_msl: plane_state_struct.altitude_msl,
+        altitude_target: auto_pilot_state.vertical_guidance.altitude_setpoint,
+        altitude_error: auto_pilot_state.vertical_guidance.altitude_setpoint
+            - plane_state_struct.altitude_msl,
+        velocity: plane_state_struct.v_ind,
+        pitch: plane_state_struct.pitch,
+        pitch_target: plane_state_struct.pitch + pitch_command.pitch_error,
+        pitch_error: pitch_command.pitch_error,
+        pitch_rate: plane_state_struct.pitch_rate,
+        pitch_rate_target: pitch_command.target_pitch_rate,
+        pitch_rate_error: pitch_command.pitch_rate_error,
+        elevator_setpoint: pitch_command.elevator,
+        vertical_speed: plane_state_struct.vertical_speed,
+        ..Default::default()
+    }
+}
 
-            let vertical_metrics = AutoPilotVerticalMetrics {
-                altitude_msl: plane_state_struct.altitude_msl,
-                altitude_target: auto_pilot_state.vertical_guidance.altitude_setpoint,
-                altitude_error: auto_pilot_state.vertical_guidance.altitude_setpoint - plane_state_struct.altitude_msl,
-                velocity: plane_state_struct.v_ind,
-                velocity_target: auto_pilot_state.vertical_guidance.velocity_setpoint,
-                velocity_error: auto_pilot_state.vertical_guidance.velocity_setpoint - plane_state_struct.v_ind,
-                kinetic_energy: kinetic,
-                kinetic_energy_target: target_kinetic,
-                potential_energy: potential,
-                potential_energy_target: target_potential,
-                energy,
-                energy_target: target_energy,
-                energy_error,
-                pitch: plane_state_struct.pitch,
-                pitch_target: target_pitch,
-                pitch_error,
-                pitch_rate: plane_state_struct.pitch_rate,
-                pitch_rate_target: target_pitch_rate,
-                pitch_rate_error,
-                elevator_setpoint: elevator,
-            };
+// what TECS should fly, set by the active vertical mode
+struct TecsTargets {
+    altitude: f64,
+    velocity: f64,
+    // flight path angle [deg] when the mode flies a path itself, otherwise TECS derives it from the altitude error
+    flight_path_commanded: Option<f64>,
+}
 
-            app_state_proxy.update_vertical_control_metrics(vertical_metrics).await?;
-            send_command(app_state_proxy, &client, CommandType::Elevator, elevator).await?;
+// total energy control: the throttle controls the total energy rate (flight path plus acceleration),
+// the elevator controls the distribution between flight path and acceleration
+// the throttle controls the total energy: the flight path [rad] and acceleration [g] errors add up
+fn energy_rate_error(flight_path_error: f64, acceleration_error: f64) -> f64 {
+    flight_path_error + acceleration_error
+}
 
-            */
-        }
+// the elevator trades speed for altitude. with the autothrottle on speed the elevator only flies the
+// path, with the throttle at a limit it flies the priority that is selected
+fn distribution_priority(
+    autothrottle_engaged: bool,
+    throttle_saturated: bool,
+    tecs_priority: TecsPriority,
+) -> Option<TecsPriority> {
+    match (autothrottle_engaged, throttle_saturated) {
+        (true, _) => Some(TecsPriority::Altitude),
+        (false, true) => Some(tecs_priority),
+        (false, false) => None,
+    }
+}
+
+fn energy_distribution_error(
+    flight_path_error: f64,
+    acceleration_error: f64,
+    priority: Option<TecsPriority>,
+) -> f64 {
+    match priority {
+        None => flight_path_error - acceleration_error,
+        Some(TecsPriority::Speed) => -2.0 * acceleration_error,
+        Some(TecsPriority::Altitude) => 2.0 * flight_path_error,
+    }
+}
+
+async fn execute_tecs(
+    dt: f64,
+    client: &reqwest::Client,
+    app_state_proxy: &super::types::AppStateProxy,
+    auto_pilot_state: &super::types::AutoPilotState,
+    plane_state_struct: &super::types::PlaneStateStruct,
+    targets: &TecsTargets,
+) -> anyhow::Result<()> {
+    const FEET_TO_METERS: f64 = 0.3048;
+
+    let constants = &auto_pilot_state.control_constants;
+    let vertical_guidance = &auto_pilot_state.vertical_guidance;
+
+    let altitude_error: f64 = targets.altitude - plane_state_struct.altitude_msl;
+    let velocity_error: f64 = targets.velocity - plane_state_struct.v_ind;
+
+    // commanded flight path [rad] and acceleration [g]
+    let max_flight_path: f64 = constants.tecs_max_flight_path.to_radians();
+    let flight_path_target: f64 = match targets.flight_path_commanded {
+        Some(flight_path) => flight_path.to_radians(),
+        None => constants.tecs_altitude_p * altitude_error,
     }
+    .clamp(-max_flight_path, max_flight_path);
+
+    let acceleration_target: f64 = (constants.tecs_velocity_p * velocity_error)
+        .clamp(-constants.tecs_max_acceleration, constants.tecs_max_acceleration);
+
+    let flight_path: f64 = plane_state_struct.vpath.to_radians();
+    let acceleration: f64 = acceleration_along_path(plane_state_struct.gload_axial, flight_path);
+
+    let flight_path_error: f64 = flight_path_target - flight_path;
+    let acceleration_error: f64 = acceleration_target - acceleration;
+
+    // specific (so no mass term) energies, used for the throttle trim and the metrics
+    let velocity: f64 = plane_state_struct.v_ind * KNOTS_TO_METERS_PER_SECOND;
+    let velocity_target: f64 = targets.velocity * KNOTS_TO_METERS_PER_SECOND;
+
+    let kinetic: f64 = 0.5 * velocity * velocity;
+    let kinetic_target: f64 = 0.5 * velocity_target * velocity_target;
+    let potential: f64 = plane_state_struct.altitude_msl * FEET_TO_METERS * GRAVITATIONAL_ACCELERATION;
+    let potential_target: f64 = targets.altitude * FEET_TO_METERS * GRAVITATIONAL_ACCELERATION;
+
+    let energy: f64 = kinetic + potential;
+    let energy_target: f64 = kinetic_target + potential_target;
+
+    // throttle
+    let energy_rate_error: f64 = energy_rate_error(flight_path_error, acceleration_error);
+
+    let throttle_trim: f64 = constants.tecs_cruise_throttle_base
+        + constants.tecs_cruise_throttle_slope * energy_target;
+
+    // with the autothrottle holding speed the throttle is not ours, so the elevator only flies the path
+    let autothrottle_engaged: bool = autothrottle::is_engaged(auto_pilot_state);
+
+    let mut energy_pid: Pid = vertical_guidance.energy_pid;
+    let throttle: f64 = if autothrottle_engaged {
+        // follow the autothrottle, so TECS takes the throttle over without a bump
+        energy_pid.initialize(auto_pilot_state.autothrottle.throttle_command);
+        auto_pilot_state.autothrottle.throttle_command
+    } else {
+        energy_pid.update(
+            &PidGains {
+                kp: constants.tecs_energy_p,
+                ki: constants.tecs_energy_i,
+                output_min: 0.0,
+                output_max: 1.0,
+                ..Default::default()
+            },
+            &PidInput {
+                error: energy_rate_error,
+                measurement: flight_path + acceleration,
+                feedforward: throttle_trim,
+                ..Default::default()
+            },
+            dt,
+        )
+    };
+
+    let throttle_saturated: bool = energy_pid.saturated;
+
+    app_state_proxy.update_pid(PidLoop::Energy, energy_pid).await?;
+
+    // elevator, when the throttle cannot deliver the energy the elevator gives priority to speed or altitude
+    let priority: Option<TecsPriority> = distribution_priority(
+        autothrottle_engaged,
+        throttle_saturated,
+        vertical_guidance.tecs_priority,
+    );
+
+    let energy_distribution_error: f64 =
+        energy_distribution_error(flight_path_error, acceleration_error, priority);
+
+    let mut distribution_pid: Pid = vertical_guidance.distribution_pid;
+    let pitch_correction: f64 = distribution_pid.update(
+        &PidGains {
+            kp: constants.tecs_pitch_p,
+            ki: constants.tecs_pitch_i,
+            output_min: -constants.max_pitch.to_radians(),
+            output_max: constants.max_pitch.to_radians(),
+            ..Default::default()
+        },
+        &PidInput {
+            error: energy_distribution_error,
+            measurement: flight_path - acceleration,
+            ..Default::default()
+        },
+        dt,
+    );
+
+    app_state_proxy
+        .update_pid(PidLoop::Distribution, distribution_pid)
+        .await?;
+
+    // pitch is angle of attack plus flight path angle, the distribution loop corrects on top of that
+    let target_pitch: f64 = (plane_state_struct.pitch - plane_state_struct.vpath
+        + flight_path_target.to_degrees()
+        + pitch_correction.to_degrees())
+    .clamp(-constants.max_pitch, constants.max_pitch);
+
+    let pitch_command: PitchCommand = command_pitch_angle(
+        dt,
+        app_state_proxy,
+        auto_pilot_state,
+        plane_state_struct,
+        target_pitch,
+    )
+    .await?;
+
+    tracing::event!(tracing::Level::TRACE,
+        "TECS - altitude [ft]: {:.4}, altitude error [ft]: {:.4}, Vind [kt]: {:.4}, velocity error [kt]: {:.4}, flight path target [deg]: {:.4}, acceleration target [g]: {:.4}, energy rate error: {:.4}, distribution error: {:.4}, throttle: {:.4}, saturated: {}, target pitch [deg]: {:.4}, elevator: {:.4}",
+        plane_state_struct.altitude_msl, altitude_error, plane_state_struct.v_ind, velocity_error, flight_path_target.to_degrees(), acceleration_target, energy_rate_error, energy_distribution_error, throttle, throttle_saturated, target_pitch, pitch_command.elevator
+    );
+
+    let vertical_metrics = AutoPilotVerticalMetrics {
+        altitude_target: targets.altitude,
+        altitude_error,
+        velocity_target: targets.velocity,
+        velocity_error,
+        kinetic_energy: kinetic,
+        kinetic_energy_target: kinetic_target,
+        potential_energy: potential,
+        potential_energy_target: potential_target,
+        energy,
+        energy_target,
+        energy_error: energy_target - energy,
+        throttle_setpoint: throttle,
+        flight_path: plane_state_struct.vpath,
+        flight_path_target: flight_path_target.to_degrees(),
+        acceleration,
+        acceleration_target,
+        energy_rate_error,
+        energy_distribution_error,
+        throttle_saturated,
+        ..pitch_metrics(auto_pilot_state, plane_state_struct, &pitch_command)
+    };
+
+    app_state_proxy.update_vertical_control_metrics(vertical_metrics).await?;
+    if !autothrottle_engaged {
+        send_command(
+            app_state_proxy,
+            client,
+            auto_pilot_state,
+            CommandType::Throttle,
+            throttle,
+        )
+        .await?;
+    }
+    send_command(
+        app_state_proxy,
+        client,
+        auto_pilot_state,
+        CommandType::Elevator,
+        pitch_command.elevator,
+    )
+    .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acceleration_along_path() {
+        // a steady climb at a constant speed: the axial g load only holds the plane up the path,
+        // so with the speed on target there is no acceleration error and no energy rate error
+        let flight_path_target: f64 = 5.0_f64.to_radians();
+        let acceleration_target: f64 = 0.0;
+
+        let flight_path: f64 = flight_path_target;
+        let acceleration: f64 = acceleration_along_path(-flight_path.sin(), flight_path);
+
+        let flight_path_error: f64 = flight_path_target - flight_path;
+        let acceleration_error: f64 = acceleration_target - acceleration;
+        assert!(acceleration_error.abs() < 1e-12);
+        assert!((flight_path_error + acceleration_error).abs() < 1e-12);
+
+        // speeding up in level flight is a positive acceleration, so the throttle comes back
+        assert!((acceleration_along_path(-0.1, 0.0) - 0.1).abs() < 1e-12);
+        assert!(acceleration_along_path(0.05, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn test_energy_law() {
+        // below the altitude on speed: more energy, which goes into altitude, so throttle and pitch up
+        assert!(energy_rate_error(0.05, 0.0) > 0.0);
+        assert!(energy_distribution_error(0.05, 0.0, None) > 0.0);
+
+        // on altitude and slow: more energy, which goes into speed, so throttle up and pitch down
+        assert!(energy_rate_error(0.0, 0.05) > 0.0);
+        assert!(energy_distribution_error(0.0, 0.05, None) < 0.0);
+
+        // high and slow by the same energy: the total is right, so the throttle stays and the elevator
+        // trades the altitude for speed
+        assert_eq!(energy_rate_error(-0.05, 0.05), 0.0);
+        assert!(energy_distribution_error(-0.05, 0.05, None) < 0.0);
+
+        // fast and low the other way around
+        assert_eq!(energy_rate_error(0.05, -0.05), 0.0);
+        assert!(energy_distribution_error(0.05, -0.05, None) > 0.0);
+    }
+
+    #[test]
+    fn test_priority_on_throttle_saturation() {
+        // the throttle has authority, so it is no trade
+        assert_eq!(distribution_priority(false, false, TecsPriority::Speed), None);
+
+        // at full throttle and still slow while climbing: speed priority gives up the climb for the
+        // speed, altitude priority keeps climbing and lets the speed go
+        let (flight_path_error, acceleration_error) = (0.05, 0.03);
+
+        let priority = distribution_priority(false, true, TecsPriority::Speed);
+        assert_eq!(priority, Some(TecsPriority::Speed));
+        let speed_first = energy_distribution_error(flight_path_error, acceleration_error, priority);
+        assert!(speed_first < 0.0);
+        assert_eq!(speed_first, -2.0 * acceleration_error);
+
+        let priority = distribution_priority(false, true, TecsPriority::Altitude);
+        assert_eq!(priority, Some(TecsPriority::Altitude));
+        let altitude_first =
+            energy_distribution_error(flight_path_error, acceleration_error, priority);
+        assert!(altitude_first > 0.0);
+        assert_eq!(altitude_first, 2.0 * flight_path_error);
+
+        // with the autothrottle on speed the elevator always flies the path
+        assert_eq!(
+            distribution_priority(true, false, TecsPriority::Speed),
+            Some(TecsPriority::Altitude)
+        );
+        assert_eq!(
+            distribution_priority(true, true, TecsPriority::Speed),
+            Some(TecsPriority::Altitude)
+        );
+    }
+
+    #[test]
+    fn test_vertical_speed_hold() {
+        // 1000 fpm at 100 kt is a flight path of about 5.6 degrees
+        let flight_path_target: f64 = flight_path_for_vertical_speed(1000.0, 100.0);
+        assert!(
+            (flight_path_target - 5.64).abs() < 0.01,
+            "flight path was {}",
+            flight_path_target
+        );
+        assert!(flight_path_for_vertical_speed(-1000.0, 100.0) < 0.0);
+
+        // level at 3 degrees angle of attack: pitch up by the flight path
+        let pitch: f64 = vertical_speed_pitch(3.0, 0.0, flight_path_target, 15.0);
+        assert!((pitch - (3.0 + flight_path_target)).abs() < 1e-12);
+
+        // on the flight path the pitch is held
+        assert_eq!(vertical_speed_pitch(8.0, 5.0, 5.0, 15.0), 8.0);
+
+        // the pitch limit holds for a vertical speed the plane cannot fly
+        assert_eq!(vertical_speed_pitch(3.0, 0.0, 25.0, 15.0), 15.0);
+    }
+
+    #[test]
+    fn test_flch_pitch_direction() {
+        let constants = AutoPilotConstants::new();
+
+        // climb thrust below the selected altitude, descent thrust above it
+        assert_eq!(flch_throttle(2000.0, 0.9, 0.2), 0.9);
+        assert_eq!(flch_throttle(-2000.0, 0.9, 0.2), 0.2);
+
+        // too slow: pitch down to get the speed back
+        let mut flch_pid = Pid::default();
+        let slow: f64 = flch_pitch(&mut flch_pid, &constants, 10.0, 100.0, 5.0, 0.1);
+        assert!(slow < 5.0, "pitch was {}", slow);
+
+        // too fast: pitch up
+        let mut flch_pid = Pid::default();
+        let fast: f64 = flch_pitch(&mut flch_pid, &constants, -10.0, 120.0, 5.0, 0.1);
+        assert!(fast > 5.0, "pitch was {}", fast);
+    }
+
+    #[test]
+    fn test_capture_from_vs_and_flch() {
+        let constants = AutoPilotConstants {
+            altitude_capture_time: 10.0,
+            altitude_capture_margin: 50.0,
+            ..AutoPilotConstants::new()
+        };
+
+        // climbing at 1200 fpm the capture starts 200 ft below the selected altitude
+        assert!(!selected_altitude_reached(1000.0, 1200.0, &constants));
+        assert!(!selected_altitude_reached(201.0, 1200.0, &constants));
+        assert!(selected_altitude_reached(199.0, 1200.0, &constants));
+
+        // the same descending onto it
+        assert!(selected_altitude_reached(-150.0, -1200.0, &constants));
+
+        // nearly level it is captured within the margin
+        assert!(!selected_altitude_reached(80.0, 100.0, &constants));
+        assert!(selected_altitude_reached(40.0, 100.0, &constants));
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flightplan::{AltitudeConstraint, FlightPlan},
+    navigation::{self, Position},
+};
+
+pub(super) const FEET_PER_NAUTICAL_MILE: f64 = 6076.12;
+
+// the vertical path along the remaining route, as flown by VNAV
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct VerticalProfile {
+    pub vnav_altitude_target: f64,
+    pub vnav_velocity_target: f64,
+    pub vnav_flight_path_angle: f64,
+    pub top_of_climb_distance: Option<f64>,
+    pub top_of_descent_distance: Option<f64>,
+}
+
+pub(super) struct VerticalProfileInput {
+    pub position: Position,
+    pub altitude: f64,
+    pub selected_altitude: f64,
+    pub selected_velocity: f64,
+    pub climb_gradient: f64,
+    pub descent_angle: f64,
+}
+
+struct RouteConstraint {
+    distance: f64,
+    constraint: AltitudeConstraint,
+}
+
+// computes the altitude we should be at now, given the altitude constraints on the remaining waypoints
+//
+// the selected altitude acts as the cruise altitude and is never exceeded. descents are planned backwards
+// from every at or at-or-below constraint with a fixed path angle, which gives the top of descent.
+// an at-or-above constraint on the next waypoint is respected by not descending below it before it is passed.
+pub(super) fn compute_vertical_profile(
+    flight_plan: &FlightPlan,
+    input: &VerticalProfileInput,
+) -> VerticalProfile {
+    let descent_gradient: f64 =
+        input.descent_angle.abs().to_radians().tan() * FEET_PER_NAUTICAL_MILE;
+    let cruise_altitude: f64 = input.selected_altitude;
+
+    // along route distance from the plane to each remaining waypoint
+    let mut constraints: Vec<RouteConstraint> = Vec::new();
+    let mut distance: f64 = 0.0;
+    let mut previous: Position = input.position;
+
+    for waypoint in flight_plan.waypoints.iter().skip(flight_plan.active_waypoint) {
+        distance += navigation::distance_nm(previous, waypoint.position());
+        previous = waypoint.position();
+
+        if let Some(constraint) = waypoint.altitude_constraint {
+            constraints.push(RouteConstraint {
+                distance,
+                constraint,
+            });
+        }
+    }
+
+    // highest altitude allowed here, so the descent path still meets every upcoming upper limit
+    let (ceiling, on_descent_path): (f64, bool) = constraints
+        .iter()
+        .filter_map(|c| match c.constraint {
+            AltitudeConstraint::At(a) | AltitudeConstraint::AtOrBelow(a) => {
+                Some(a + c.distance * descent_gradient)
+            }
+            AltitudeConstraint::AtOrAbove(_) => None,
+        })
+        .fold((cruise_altitude, false), |(ceiling, on_path), limit| {
+            if limit < ceiling {
+                (limit, true)
+            } else {
+                (ceiling, on_path)
+            }
+        });
+
+    // lowest altitude allowed here, when the next constraint is an at or at-or-above constraint
+    let floor: Option<f64> = constraints.first().and_then(|c| match c.constraint {
+        AltitudeConstraint::At(a) | AltitudeConstraint::AtOrAbove(a) => Some(a),
+        AltitudeConstraint::AtOrBelow(_) => None,
+    });
+
+    let altitude_target: f64 = match floor {
+        Some(f) if f > ceiling => f.min(cruise_altitude),
+        _ => ceiling,
+    };
+
+    // follow the descent path once we are on it, otherwise fly level (TECS will climb or descend to the target)
+    let flight_path_angle: f64 = if on_descent_path && input.altitude > altitude_target - 100.0 {
+        -input.descent_angle.abs()
+    } else {
+        0.0
+    };
+
+    let top_of_climb_distance: Option<f64> = if input.altitude < altitude_target
+        && input.climb_gradient > 0.0
+    {
+        Some((altitude_target - input.altitude) / input.climb_gradient)
+    } else {
+        None
+    };
+
+    let top_of_descent_distance: Option<f64> = constraints
+        .iter()
+        .filter_map(|c| match c.constraint {
+            AltitudeConstraint::At(a) | AltitudeConstraint::AtOrBelow(a)
+                if a < cruise_altitude =>
+            {
+                Some(c.distance - (cruise_altitude - a) / descent_gradient)
+            }
+            _ => None,
+        })
+        .reduce(f64::min)
+        .map(|d| d.max(0.0));
+
+    // the speed constraint on the active waypoint applies until it is passed
+    let velocity_target: f64 = flight_plan
+        .active()
+        .and_then(|w| w.speed_constraint)
+        .unwrap_or(input.selected_velocity);
+
+    VerticalProfile {
+        vnav_altitude_target: altitude_target,
+        vnav_velocity_target: velocity_target,
+        vnav_flight_path_angle: flight_path_angle,
+        top_of_climb_distance,
+        top_of_descent_distance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flightplan::Waypoint;
+
+    fn waypoint_north(nm: f64, altitude_constraint: Option<AltitudeConstraint>) -> Waypoint {
+        Waypoint {
+            name: format!("N{}", nm),
+            latitude: nm / 60.0,
+            longitude: 0.0,
+            altitude_constraint,
+            speed_constraint: None,
+        }
+    }
+
+    fn input(altitude: f64) -> VerticalProfileInput {
+        VerticalProfileInput {
+            position: Position::default(),
+            altitude,
+            selected_altitude: 6000.0,
+            selected_velocity: 100.0,
+            climb_gradient: 500.0,
+            descent_angle: 3.0,
+        }
+    }
+
+    #[test]
+    fn test_top_of_descent() {
+        let mut flight_plan = FlightPlan::default();
+        flight_plan.load(
+            vec![
+                waypoint_north(10.0, None),
+                waypoint_north(40.0, Some(AltitudeConstraint::At(3000.0))),
+            ],
+            Position::default(),
+        );
+
+        // 3000 ft to lose at ~318 ft/nm is ~9.4 nm before the constraint
+        let profile = compute_vertical_profile(&flight_plan, &input(6000.0));
+        let top_of_descent = profile.top_of_descent_distance.unwrap();
+        assert!((top_of_descent - 30.6).abs() < 0.2, "tod was {}", top_of_descent);
+        assert_eq!(profile.vnav_altitude_target, 6000.0);
+        assert_eq!(profile.vnav_flight_path_angle, 0.0);
+
+        // past the top of descent we follow the path down
+        flight_plan.load(
+            vec![waypoint_north(5.0, Some(AltitudeConstraint::At(3000.0)))],
+            Position::default(),
+        );
+        let profile = compute_vertical_profile(&flight_plan, &input(4600.0));
+        assert!((profile.vnav_altitude_target - 4592.0).abs() < 5.0);
+        assert_eq!(profile.vnav_flight_path_angle, -3.0);
+    }
+
+    #[test]
+    fn test_climb_constraints() {
+        let mut flight_plan = FlightPlan::default();
+        flight_plan.load(
+            vec![
+                waypoint_north(5.0, Some(AltitudeConstraint::AtOrBelow(2000.0))),
+                waypoint_north(20.0, Some(AltitudeConstraint::AtOrAbove(4000.0))),
+            ],
+            Position::default(),
+        );
+
+        // the at-or-below constraint caps the climb until it is passed
+        let profile = compute_vertical_profile(&flight_plan, &input(1000.0));
+        assert!(profile.vnav_altitude_target < 3700.0);
+        assert!((profile.top_of_climb_distance.unwrap() - 5.4).abs() < 0.5);
+
+        flight_plan.sequence();
+        let profile = compute_vertical_profile(&flight_plan, &input(2000.0));
+        assert_eq!(profile.vnav_altitude_target, 6000.0);
+    }
+}
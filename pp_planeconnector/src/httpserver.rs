@@ -5,9 +5,12 @@ use serde::{Deserialize, Serialize};
 use axum::{
     extract::State,
     http::{Method, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
     routing::{get, post},
     Json, Router,
 };
+use futures::stream::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{event, Level};
@@ -32,6 +35,8 @@ pub(super) async fn run_server(app_state: AppStateProxy) {
     let app: Router = Router::new()
         .route("/", get(root))
         .route("/api/v1/state", get(get_state))
+        .route("/api/v1/state/filtered", get(get_filtered_state))
+        .route("/api/v1/state/stream", get(stream_state))
         .route("/api/v1/command", post(send_command))
         .layer(utils::return_trace_layer())
         .layer(cors)
@@ -114,10 +119,44 @@ async fn get_state(
         .await
         .expect("error getting the state");
 
-    let average_state: BTreeMap<String, serde_json::Value> = app_state_proxy
-        .get_average_state()
+    Ok(Json(state))
+}
+
+// get the low pass filtered state from the app and serve as a JSON
+async fn get_filtered_state(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let filtered_state: BTreeMap<String, serde_json::Value> = app_state_proxy
+        .get_filtered_state()
         .await
-        .expect("error getting the state");
+        .expect("error getting the filtered state");
+
+    Ok(Json(filtered_state))
+}
+
+// push every new sample of the state as a server sent event, so the autopilot does not have to poll
+async fn stream_state(
+    State(app_state_proxy): State<AppStateProxy>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let receiver: broadcast::Receiver<BTreeMap<String, serde_json::Value>> =
+        app_state_proxy.subscribe_to_state();
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(sample) => return Some((Event::default().json_data(&sample), receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    // a slow subscriber only gets the most recent samples
+                    event!(
+                        Level::DEBUG,
+                        "State stream subscriber lagging, skipped {} samples",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
 
-    Ok(Json(average_state))
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
@@ -2,7 +2,7 @@
 
 use std::time::Duration;
 
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 
 use self::types::{AppState, AppStateProxy};
 
@@ -17,9 +17,13 @@ pub async fn run_app(service_adresses: &(String, String, String)) -> anyhow::Res
     let (tx_command, rx_command) = mpsc::channel(32);
     let (tx_state, rx_state) = mpsc::channel(32);
 
+    // and a broadcast channel to push every new plane state sample to the subscribers
+    let (tx_sample, _) = broadcast::channel(16);
+
     // set up the app state and a proxy, that is linked through a channel. we can then clone and share the proxy with all the different procsesses
-    let app_state: AppState = AppState::new(rx_state);
-    let app_state_proxy: AppStateProxy = AppStateProxy::new(service_adresses, tx_state, tx_command);
+    let app_state: AppState = AppState::new(rx_state, tx_sample.clone());
+    let app_state_proxy: AppStateProxy =
+        AppStateProxy::new(service_adresses, tx_state, tx_command, tx_sample);
 
     tokio::select! {
 
@@ -29,6 +33,9 @@ pub async fn run_app(service_adresses: &(String, String, String)) -> anyhow::Res
         // process that listens to xplane udp packets, and updatates the state accordingly
         _ = xplaneudp::listen_to_xplane(app_state_proxy.clone()) => { }
 
+        // process that asks xplane which aircraft is loaded, it does not end on an error
+        _ = xplaneudp::listen_to_aircraft(app_state_proxy.clone()) => { }
+
         // process that listens to incomming commands (through the http server), and send them to xplane
         _ = xplaneudp::listen_to_send_commands(rx_command) => { }
 
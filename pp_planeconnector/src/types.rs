@@ -1,6 +1,6 @@
 use serde_json::Value;
 use std::collections::{BTreeMap, HashMap};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot};
 
 // Define the types of commands that can be sent to the AppState actor
 #[derive(Debug)]
@@ -15,6 +15,10 @@ pub(super) enum StateSignal {
         state: BTreeMap<String, serde_json::value::Value>,
         result_sender: oneshot::Sender<bool>,
     },
+    SetAircraft {
+        aircraft: String,
+        result_sender: oneshot::Sender<bool>,
+    },
 }
 
 // first-order low-pass filter
@@ -43,9 +47,9 @@ impl LowPassFilter {
         y
     }
 
-    pub fn get_latest(self) -> f64 {
-        self.y1.clone()
-    }    
+    pub fn get_latest(&self) -> f64 {
+        self.y1
+    }
 }
 
 
@@ -56,32 +60,38 @@ pub(super) struct AppState {
     plane_state: BTreeMap<String, Vec<Value>>,
     plane_state_filtered:  BTreeMap<String, LowPassFilter>, 
     receiver: mpsc::Receiver<StateSignal>,
+    // every update is pushed to the subscribers (e.g. the autopilot) as a numbered sample
+    sample_sender: broadcast::Sender<BTreeMap<String, Value>>,
+    sample_number: u64,
 }
 
 impl AppState {
-    pub fn new(receiver: mpsc::Receiver<StateSignal>) -> Self {
+    pub fn new(
+        receiver: mpsc::Receiver<StateSignal>,
+        sample_sender: broadcast::Sender<BTreeMap<String, Value>>,
+    ) -> Self {
         AppState {
             plane_state: BTreeMap::new(),
             plane_state_filtered: BTreeMap::new(),
             receiver,
+            sample_sender,
+            sample_number: 0,
         }
     }
 
+    fn latest_state(&self) -> BTreeMap<String, Value> {
+        self.plane_state
+            .iter()
+            .map(|(key, val)| (key.to_string(), val[0].clone()))
+            .collect()
+    }
+
     // Process incoming commands asynchronously
     pub async fn process(mut self) {
         while let Some(signal) = self.receiver.recv().await {
             match signal {
                 StateSignal::ReturnPlaneState { result_sender } => {
-                    let mut state: BTreeMap<String, Value> = BTreeMap::new();
-
-                    for (key, val) in self.plane_state.iter() {
-                            state.insert(
-                                key.to_string(),
-                                val[0].clone(),
-                            );
-                        }
-
-                    let _ = result_sender.send(state.clone());
+                    let _ = result_sender.send(self.latest_state());
                 }
                 StateSignal::ReturnFilteredPlaneState { result_sender } => {
                     let mut state: BTreeMap<String, Value> = BTreeMap::new();
@@ -127,16 +137,32 @@ impl AppState {
                             })
                             .or_insert(LowPassFilter::new(30.0, 0.1));
 
-                        // add the current update timestamp to plane_state
-                        // TODO
-                        self.plane_state.insert(
-                            "last_updated_timestamp".to_string(),
-                            vec![Value::Number(chrono::Utc::now().timestamp_millis().into())],
-                        );
+                    }
 
+                    // add the current update timestamp and the sample number to plane_state
+                    self.sample_number += 1;
+                    self.plane_state.insert(
+                        "last_updated_timestamp".to_string(),
+                        vec![Value::Number(chrono::Utc::now().timestamp_millis().into())],
+                    );
+                    self.plane_state.insert(
+                        "sample_number".to_string(),
+                        vec![Value::Number(self.sample_number.into())],
+                    );
 
+                    // an error only means nobody is subscribed at the moment
+                    let _ = self.sample_sender.send(self.latest_state());
+
+                    let _ = result_sender.send(true);
+                }
+                StateSignal::SetAircraft {
+                    aircraft,
+                    result_sender,
+                } => {
+                    // not a new sample, the aircraft goes out with the next one
+                    self.plane_state
+                        .insert("aircraft".to_string(), vec![Value::String(aircraft)]);
 
-                    }
                     let _ = result_sender.send(true);
                 }
             }
@@ -151,6 +177,7 @@ pub(super) struct AppStateProxy {
     pub service_adresses: (String, String, String),
     pub state_sender: mpsc::Sender<StateSignal>,
     pub command_sender: mpsc::Sender<Command>,
+    pub sample_sender: broadcast::Sender<BTreeMap<String, Value>>,
 }
 
 impl AppStateProxy {
@@ -158,14 +185,21 @@ impl AppStateProxy {
         service_adresses: &(String, String, String),
         state_sender: mpsc::Sender<StateSignal>,
         command_sender: mpsc::Sender<Command>,
+        sample_sender: broadcast::Sender<BTreeMap<String, Value>>,
     ) -> Self {
         AppStateProxy {
             service_adresses: service_adresses.clone(),
             state_sender,
             command_sender,
+            sample_sender,
         }
     }
 
+    // receive every new sample of the plane state, as soon as it is there
+    pub fn subscribe_to_state(&self) -> broadcast::Receiver<BTreeMap<String, Value>> {
+        self.sample_sender.subscribe()
+    }
+
     // send a command to xplane
     pub async fn send_command(&self, command: Command) -> anyhow::Result<()> {
         match self.command_sender.send(command).await {
@@ -188,16 +222,16 @@ impl AppStateProxy {
     }
 
     // send and return state signal and await the result
-    pub async fn get_average_state(
+    pub async fn get_filtered_state(
         &self,
     ) -> anyhow::Result<BTreeMap<String, serde_json::value::Value>> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.state_sender
-            .send(StateSignal::ReturnAveragePlaneState { result_sender })
+            .send(StateSignal::ReturnFilteredPlaneState { result_sender })
             .await?;
         Ok(result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive average state result from state")))
+            .unwrap_or_else(|_| panic!("Failed to receive filtered state result from state")))
     }
 
     // Send a value to be added to the state
@@ -218,6 +252,22 @@ impl AppStateProxy {
 
         Ok(result)
     }
+
+    // the ICAO type of the aircraft loaded in the sim, e.g. C172
+    pub async fn set_aircraft(&self, aircraft: &str) -> anyhow::Result<bool> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.state_sender
+            .send(StateSignal::SetAircraft {
+                aircraft: aircraft.to_string(),
+                result_sender,
+            })
+            .await?;
+        let result = result_receiver
+            .await
+            .unwrap_or_else(|_| panic!("Failed to receive message from state"));
+
+        Ok(result)
+    }
 }
 
 // define possible UDP packet types, to be send to xplane
@@ -18,6 +18,23 @@ pub struct DataIndex {
 
 pub fn data_map() -> Vec<DataIndex> {
     vec![
+        // the times: real time since xplane started, then the flight time, which is simulator time, it
+        // stops when the sim is paused and runs faster with time acceleration [s]
+        DataIndex {
+            index: 1_u8,
+            data: vec![
+                DataStructure {
+                    name: "real_time".to_string(),
+                    data_type: DataType::Empty,
+                    transformation: None,
+                },
+                DataStructure {
+                    name: "sim_time".to_string(),
+                    data_type: DataType::Float,
+                    transformation: None,
+                },
+            ],
+        },
         DataIndex {
             index: 3_u8,
             data: vec![
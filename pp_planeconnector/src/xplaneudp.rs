@@ -15,6 +15,19 @@ const FLOAT_LEN: usize = 4;
 const IP_ADRR: &str = "127.0.0.1";
 const LISTENING_PORT: &str = "49101";
 const SENDING_PORT: &str = "49000";
+const AIRCRAFT_PORT: &str = "49102";
+
+// the ICAO type of the loaded aircraft, a byte array that xplane sends as one float per byte.
+// type designators are at most 4 characters, the rest is padding
+const AIRCRAFT_DATAREF: &str = "sim/aircraft/view/acf_ICAO";
+const AIRCRAFT_LEN: usize = 8;
+// [Hz]
+const AIRCRAFT_FREQUENCY: i32 = 1;
+// xplane forgets the subscription on a restart, so it is renewed when nothing comes in
+const AIRCRAFT_TIMEOUT: Duration = Duration::from_secs(5);
+// after an error the socket is bound again after this, e.g. on windows a request to an xplane
+// that is not running makes the next recv fail
+const AIRCRAFT_RETRY: Duration = Duration::from_secs(5);
 
 // Listens to mpsc channel if commands are received, and turn them into an UDP packet to send to xplane
 
@@ -88,6 +101,9 @@ pub(super) async fn listen_to_xplane(app_state_proxy: AppStateProxy) -> anyhow::
 
         // check if we get a DATA packet
         if &buf[0..4] == b"DATA" {
+            // all sentences in one packet are one sample of the plane state
+            let mut sample: BTreeMap<String, Value> = BTreeMap::new();
+
             for sentence in buf[5..len].chunks(36) {
                 // there is a 0 after DATA, and only take part of the buffer that actually contains the udp packet [5..len]
                 // take the data and translate the bytes to floats
@@ -115,13 +131,117 @@ pub(super) async fn listen_to_xplane(app_state_proxy: AppStateProxy) -> anyhow::
                     );
                 });
 
-                //send a signal to the app state - via the proxy - to update the state
-                app_state_proxy.add_value_to_state(values.unwrap()).await?;
+                if let Ok(values) = values {
+                    sample.extend(values);
+                }
+            }
+
+            //send a signal to the app state - via the proxy - to update the state, once per packet
+            if !sample.is_empty() {
+                app_state_proxy.add_value_to_state(sample).await?;
             }
         }
     }
 }
 
+// Asks xplane for the loaded aircraft, and puts it in the state when it changes. the aircraft is only
+// nice to know, so this never ends: on an error it waits and starts over with a new socket
+pub(super) async fn listen_to_aircraft(app_state_proxy: AppStateProxy) {
+    let mut aircraft: String = String::new();
+    // the same error comes every few seconds while xplane is not there, so it is a warning only
+    // once until xplane answers again
+    let mut warned: bool = false;
+
+    loop {
+        if let Err(e) = ask_for_aircraft(&app_state_proxy, &mut aircraft, &mut warned).await {
+            match std::mem::replace(&mut warned, true) {
+                false => event!(
+                    Level::WARN,
+                    "Cannot get the aircraft from xplane, trying again every {:?}: {:?}",
+                    AIRCRAFT_RETRY,
+                    e
+                ),
+                true => event!(Level::DEBUG, "Cannot get the aircraft from xplane: {:?}", e),
+            }
+        }
+
+        tokio::time::sleep(AIRCRAFT_RETRY).await;
+    }
+}
+
+// one socket that asks for the aircraft until something fails
+async fn ask_for_aircraft(
+    app_state_proxy: &AppStateProxy,
+    aircraft: &mut String,
+    warned: &mut bool,
+) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(IP_ADRR.to_owned() + ":" + AIRCRAFT_PORT).await?;
+    let mut buf: [u8; 1024] = [0_u8; 1024];
+
+    let mut aircraft_bytes: [u8; AIRCRAFT_LEN] = [0_u8; AIRCRAFT_LEN];
+
+    loop {
+        // xplane answers to the port the request came from
+        for index in 0..AIRCRAFT_LEN {
+            let dataref: String = format!("{}[{}]", AIRCRAFT_DATAREF, index);
+            let packet: Vec<u8> = create_rref_packet(AIRCRAFT_FREQUENCY, index as i32, &dataref)?;
+            if let Err(e) = socket
+                .send_to(&packet, IP_ADRR.to_owned() + ":" + SENDING_PORT)
+                .await
+            {
+                event!(Level::DEBUG, "Cannot ask xplane for the aircraft: {:?}", e);
+            }
+        }
+
+        while let Ok(received) =
+            tokio::time::timeout(AIRCRAFT_TIMEOUT, socket.recv_from(&mut buf)).await
+        {
+            let (len, _src) = received?;
+
+            if len < 5 || &buf[0..4] != b"RREF" {
+                continue;
+            }
+            *warned = false;
+
+            for (index, value) in parse_rref(&buf[5..len]) {
+                if let Some(byte) = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| aircraft_bytes.get_mut(i))
+                {
+                    *byte = value as u8;
+                }
+            }
+
+            let reported: String = aircraft_from_bytes(&aircraft_bytes);
+            if reported != *aircraft {
+                event!(Level::INFO, "Aircraft in the sim: {}", reported);
+
+                app_state_proxy.set_aircraft(&reported).await?;
+                *aircraft = reported;
+            }
+        }
+    }
+}
+
+// the values of an RREF packet, as pairs of the index that was asked for and the value
+fn parse_rref(data: &[u8]) -> Vec<(i32, f32)> {
+    data.chunks_exact(8)
+        .map(|pair| {
+            (
+                i32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]),
+                f32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]),
+            )
+        })
+        .collect()
+}
+
+// the bytes up to the first 0, without the spaces some aircraft pad with
+fn aircraft_from_bytes(bytes: &[u8]) -> String {
+    let end: usize = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
 // Translates 32 bytes to 8 floats
 
 fn translate_bytes_to_floats(data_bytes: &[u8; 8 * 4]) -> anyhow::Result<Vec<f32>> {
@@ -153,6 +273,33 @@ mod tests {
 
         assert_eq!(translate_bytes_to_floats(&bytes).unwrap(), vec);
     }
+
+    #[test]
+    fn test_aircraft_from_rref() {
+        let mut data: Vec<u8> = Vec::new();
+        for (index, byte) in b"C172\0\0\0\0".iter().enumerate() {
+            data.extend_from_slice(&(index as i32).to_le_bytes());
+            data.extend_from_slice(&(*byte as f32).to_le_bytes());
+        }
+
+        let mut bytes: [u8; AIRCRAFT_LEN] = [0_u8; AIRCRAFT_LEN];
+        for (index, value) in parse_rref(&data) {
+            bytes[index as usize] = value as u8;
+        }
+
+        assert_eq!(aircraft_from_bytes(&bytes), "C172");
+        assert_eq!(aircraft_from_bytes(b"B350 \0\0\0"), "B350");
+    }
+
+    #[test]
+    fn test_simulator_time() {
+        // the times row: real time, then the flight time the autopilot takes as simulator time
+        let values: Vec<f32> = vec![5400.0, 1234.5, 1234.5, 0.0, 0.0, 14.2, 15.2, 320.0];
+        let plane_state = map_values(1, values, &data_map()).unwrap();
+
+        assert_eq!(plane_state.get("sim_time").and_then(|v| v.as_f64()), Some(1234.5));
+        assert!(!plane_state.contains_key("real_time"));
+    }
 }
 
 // Maps values into the plane_state, based on the data map index
@@ -277,3 +424,31 @@ fn create_packet(
         }
     }
 }
+
+// an RREF packet asks xplane to send a dataref at a frequency, a frequency of 0 stops it
+fn create_rref_packet(frequency: i32, index: i32, dataref: &str) -> anyhow::Result<Vec<u8>> {
+    /*
+    RREF + \0 upfront (5 bytes)
+
+    struct dref_struct_in
+    {
+        xint dref_freq; 4 bytes
+        xint dref_sender_index; 4
+        xchr dref_string[400]; 400
+    };
+
+    */
+
+    if dataref.len() >= 400 {
+        return Err(anyhow!("Dataref too long for an RREF packet: {}", dataref));
+    }
+
+    let mut packet: Vec<u8> = vec![0; 413]; // an RREF packet is 413 bytes
+
+    packet[0..4].copy_from_slice(b"RREF");
+    packet[5..9].copy_from_slice(&frequency.to_le_bytes());
+    packet[9..13].copy_from_slice(&index.to_le_bytes());
+    packet[13..13 + dataref.len()].copy_from_slice(dataref.as_bytes());
+
+    Ok(packet)
+}
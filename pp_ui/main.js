@@ -4,16 +4,24 @@ const autopilotAddress = "http://localhost:3200/api/v1";
 
 //let activate_wings_level_button = document.querySelector("#activate_horizontal_wings_level_button");
 
-async function getAutoPilotState() {
-  try {
-    const response = await fetch(autopilotAddress.concat("/autopilot_state"));
-    const responseData = await response.json();
+// the autopilot pushes its state when it changes, first the whole state and then only what changed
+let autoPilotState = {};
 
-    return responseData;
+function streamAutoPilotState() {
+  const stream = new EventSource(autopilotAddress.concat("/autopilot_state/stream?diff=true"));
 
-  } catch (error) {
-    console.error("Error:", error);
-  }
+  stream.addEventListener("state", (event) => {
+    autoPilotState = JSON.parse(event.data);
+    updateUI(autoPilotState);
+  });
+
+  stream.addEventListener("diff", (event) => {
+    Object.assign(autoPilotState, JSON.parse(event.data));
+    updateUI(autoPilotState);
+  });
+
+  // the browser reconnects by itself, and the stream starts over with the whole state
+  stream.onerror = (error) => console.error("Error:", error);
 }
 
 async function getPlaneState() {
@@ -28,18 +36,22 @@ async function getPlaneState() {
   }
 }
 
-async function updateUI() {
-	
-	let state = await getAutoPilotState();
-
-	console.log(state);
+function updateUI(state) {
 
 	activate_horizontal_standby_button.classList.remove("btn-outline-success", "btn-success");
 	activate_horizontal_wings_level_button.classList.remove("btn-outline-success", "btn-success");
 	activate_horizontal_heading_button.classList.remove("btn-outline-success", "btn-success");
+	activate_horizontal_track_button.classList.remove("btn-outline-success", "btn-success");
+	activate_horizontal_lnav_button.classList.remove("btn-outline-success", "btn-success");
 
 	activate_vertical_standby_button.classList.remove("btn-outline-success", "btn-success");
 	activate_vertical_TECS_button.classList.remove("btn-outline-success", "btn-success");
+	activate_vertical_vnav_button.classList.remove("btn-outline-success", "btn-success");
+	activate_vertical_vs_button.classList.remove("btn-outline-success", "btn-success");
+	activate_vertical_flch_button.classList.remove("btn-outline-success", "btn-success");
+
+	activate_autothrottle_standby_button.classList.remove("btn-outline-success", "btn-success");
+	activate_autothrottle_speed_button.classList.remove("btn-outline-success", "btn-success");
 
 	//horizontal
 
@@ -48,54 +60,177 @@ async function updateUI() {
 		activate_horizontal_standby_button.classList.add("btn-success");
 		activate_horizontal_wings_level_button.classList.add("btn-outline-success")
 		activate_horizontal_heading_button.classList.add("btn-outline-success")
+		activate_horizontal_track_button.classList.add("btn-outline-success")
+		activate_horizontal_lnav_button.classList.add("btn-outline-success")
 		break;
 	case "WingsLevel":
 		activate_horizontal_standby_button.classList.add("btn-outline-success");
 		activate_horizontal_wings_level_button.classList.add("btn-success")
 		activate_horizontal_heading_button.classList.add("btn-outline-success")
+		activate_horizontal_track_button.classList.add("btn-outline-success")
+		activate_horizontal_lnav_button.classList.add("btn-outline-success")
 		break;
 	case "Heading":
 		activate_horizontal_standby_button.classList.add("btn-outline-success");
 		activate_horizontal_wings_level_button.classList.add("btn-outline-success")
-		activate_horizontal_heading_button.classList.add("btn-success")		
+		activate_horizontal_heading_button.classList.add("btn-success")
+		activate_horizontal_track_button.classList.add("btn-outline-success")
+		activate_horizontal_lnav_button.classList.add("btn-outline-success")
+		break;
+	case "Track":
+		activate_horizontal_standby_button.classList.add("btn-outline-success");
+		activate_horizontal_wings_level_button.classList.add("btn-outline-success")
+		activate_horizontal_heading_button.classList.add("btn-outline-success")
+		activate_horizontal_track_button.classList.add("btn-success")
+		activate_horizontal_lnav_button.classList.add("btn-outline-success")
+		break;
+	case "LNAV":
+		activate_horizontal_standby_button.classList.add("btn-outline-success");
+		activate_horizontal_wings_level_button.classList.add("btn-outline-success")
+		activate_horizontal_heading_button.classList.add("btn-outline-success")
+		activate_horizontal_track_button.classList.add("btn-outline-success")
+		activate_horizontal_lnav_button.classList.add("btn-success")
+		break;
+	default:
+		// HOLD and ORBIT have no button, they are set up and engaged through the api and shown in the FMA
+		activate_horizontal_standby_button.classList.add("btn-outline-success");
+		activate_horizontal_wings_level_button.classList.add("btn-outline-success")
+		activate_horizontal_heading_button.classList.add("btn-outline-success")
+		activate_horizontal_track_button.classList.add("btn-outline-success")
+		activate_horizontal_lnav_button.classList.add("btn-outline-success")
+	}
+
+	//flight mode annunciations
+
+	fma_autothrottle.innerHTML = state.fma.autothrottle;
+	fma_lateral_active.innerHTML = state.fma.lateral_active;
+	fma_lateral_armed.innerHTML = state.fma.lateral_armed;
+	fma_vertical_active.innerHTML = state.fma.vertical_active;
+	fma_vertical_armed.innerHTML = state.fma.vertical_armed;
+
+	// the autopilot can be uncoupled to hand fly the modes on the flight director
+	autopilot_coupled_button.classList.toggle("btn-success", state.autopilot_coupled);
+	autopilot_coupled_button.classList.toggle("btn-outline-secondary", !state.autopilot_coupled);
+	flight_director_button.classList.toggle("btn-success", state.flight_director_on);
+	flight_director_button.classList.toggle("btn-outline-secondary", !state.flight_director_on);
+
+	let cue = (value, digits) => value === null || value === undefined ? "-" : value.toFixed(digits);
+	fd_cues.innerHTML = "BANK ".concat(cue(state.fd_bank_cue, 1), " PITCH ", cue(state.fd_pitch_cue, 1), " THR ", cue(state.fd_throttle_cue, 2));
+
+	// the last channel the pilot took over
+	if (state.override_events.length > 0) {
+		let last_override = state.override_events[state.override_events.length - 1];
+		override_last.innerHTML = last_override.channel.toUpperCase().concat(" ", new Date(last_override.timestamp).toLocaleTimeString());
+	}
+
+	// the aircraft profile in effect, and the aircraft the sim reports
+	aircraft_profile.innerHTML = state.aircraft_profile || "-";
+	aircraft_reported.innerHTML = state.aircraft_reported;
+
+	// an automatic disconnect stays on until it is clicked away
+	if (state.disconnect_alert) {
+		disconnect_alert_button.classList.remove("d-none");
+		disconnect_alert_button.title = state.disconnect_reason;
+	} else {
+		disconnect_alert_button.classList.add("d-none");
 	}
 
 	heading_active.innerHTML = state.heading_setpoint;
+	wind_active.innerHTML = Math.round(state.wind_direction).toString().concat("/", Math.round(state.wind_speed));
 
 	if (document.activeElement !== heading_standby) {
 		heading_standby.value = state.heading_standby;
 	}
 
+	if (document.activeElement !== turn_direction) {
+		turn_direction.value = state.turn_direction;
+	}
+
+	if (document.activeElement !== heading_reference) {
+		heading_reference.value = state.heading_reference;
+	}
+
+	if (document.activeElement !== bank_limit) {
+		bank_limit.value = state.bank_limit;
+	}
+
 	//vertical
 
 	switch (state.vertical_mode) {
 	case "Standby":
 		activate_vertical_standby_button.classList.add("btn-success");
 		activate_vertical_TECS_button.classList.add("btn-outline-success");
+		activate_vertical_vnav_button.classList.add("btn-outline-success");
+		activate_vertical_vs_button.classList.add("btn-outline-success");
+		activate_vertical_flch_button.classList.add("btn-outline-success");
 		break;
 	case "TECS":
 		activate_vertical_standby_button.classList.add("btn-outline-success");
 		activate_vertical_TECS_button.classList.add("btn-success");
-		break;	
+		activate_vertical_vnav_button.classList.add("btn-outline-success");
+		activate_vertical_vs_button.classList.add("btn-outline-success");
+		activate_vertical_flch_button.classList.add("btn-outline-success");
+		break;
+	case "VNAV":
+		activate_vertical_standby_button.classList.add("btn-outline-success");
+		activate_vertical_TECS_button.classList.add("btn-outline-success");
+		activate_vertical_vnav_button.classList.add("btn-success");
+		activate_vertical_vs_button.classList.add("btn-outline-success");
+		activate_vertical_flch_button.classList.add("btn-outline-success");
+		break;
+	case "VS":
+		activate_vertical_standby_button.classList.add("btn-outline-success");
+		activate_vertical_TECS_button.classList.add("btn-outline-success");
+		activate_vertical_vnav_button.classList.add("btn-outline-success");
+		activate_vertical_vs_button.classList.add("btn-success");
+		activate_vertical_flch_button.classList.add("btn-outline-success");
+		break;
+	case "FLCH":
+		activate_vertical_standby_button.classList.add("btn-outline-success");
+		activate_vertical_TECS_button.classList.add("btn-outline-success");
+		activate_vertical_vnav_button.classList.add("btn-outline-success");
+		activate_vertical_vs_button.classList.add("btn-outline-success");
+		activate_vertical_flch_button.classList.add("btn-success");
+		break;
 	}
 
+	//autothrottle
+
+	switch (state.autothrottle_mode) {
+	case "Standby":
+		activate_autothrottle_standby_button.classList.add("btn-success");
+		activate_autothrottle_speed_button.classList.add("btn-outline-success");
+		activate_autothrottle_speed_button.innerHTML = "SPEED";
+		break;
+	default:
+		// SPEED or THR HOLD
+		activate_autothrottle_standby_button.classList.add("btn-outline-success");
+		activate_autothrottle_speed_button.classList.add("btn-success");
+		activate_autothrottle_speed_button.innerHTML = state.autothrottle_mode;
+	}
+
+}
+
+async function updatePlaneState() {
+
 	let plane_state = await getPlaneState();
 
 	let plane_state_div = document.querySelector("#plane_state");
 	plane_state_div.innerHTML = JSON.stringify(plane_state, null, 2);
 
-    setTimeout(updateUI, 500);
+    setTimeout(updatePlaneState, 500);
 
 }
 
 async function activateHorizontalStandby() {
   try {
-    const response = await fetch(autopilotAddress.concat("/activate/horizontal/standby"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "standby"})
     });
 
     if (response.ok) {
@@ -113,12 +248,13 @@ async function activateHorizontalStandby() {
 
 async function activateWingsLevel() {
   try {
-    const response = await fetch(autopilotAddress.concat("/activate/horizontal/wingslevel"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "wingslevel"})
     });
 
     if (response.ok) {
@@ -136,12 +272,13 @@ async function activateWingsLevel() {
 
 async function activateHeading() {
   try {
-    const response = await fetch(autopilotAddress.concat("/activate/horizontal/heading"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "heading"})
     });
 
     if (response.ok) {
@@ -157,14 +294,63 @@ async function activateHeading() {
   //updateUI();
 }
 
+async function activateTrack() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "track"})
+    });
+
+    if (response.ok) {
+    	console.log("Track activated");
+    } else {
+    	console.error("Track not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function activateLNAV() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "lnav"})
+    });
+
+    if (response.ok) {
+    	console.log("LNAV activated");
+    } else {
+    	console.error("LNAV not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
 async function activateVerticalStandby() {
   try {
-    const response = await fetch(autopilotAddress.concat("/activate/vertical/standby"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "standby"})
     });
 
     if (response.ok) {
@@ -182,12 +368,13 @@ async function activateVerticalStandby() {
 
 async function activateVerticalTECS() {
   try {
-    const response = await fetch(autopilotAddress.concat("/activate/vertical/tecs"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "tecs"})
     });
 
     if (response.ok) {
@@ -203,17 +390,138 @@ async function activateVerticalTECS() {
   //updateUI();
 }
 
+async function activateVerticalVNAV() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "vnav"})
+    });
+
+    if (response.ok) {
+    	console.log("VNAV activated");
+    } else {
+    	console.error("VNAV not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function activateVerticalVS() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "vs"})
+    });
+
+    if (response.ok) {
+    	console.log("VS activated");
+    } else {
+    	console.error("VS not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function activateVerticalFLCH() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "flch"})
+    });
+
+    if (response.ok) {
+    	console.log("FLCH activated");
+    } else {
+    	console.error("FLCH not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function activateAutothrottleStandby() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "autothrottle", mode: "standby"})
+    });
+
+    if (response.ok) {
+    	console.log("Autothrottle standby activated");
+    } else {
+    	console.error("Autothrottle standby not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function activateAutothrottleSpeed() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "autothrottle", mode: "speed"})
+    });
+
+    if (response.ok) {
+    	console.log("Autothrottle SPEED activated");
+    } else {
+    	console.error("Autothrottle SPEED not activated");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
 async function setHeadingStandby() {
 
   let heading = heading_standby.value;
 
   try {
-    const response = await fetch(autopilotAddress.concat("/set/heading/").concat(heading), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/standby"), {
+      method: "PUT",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
-      }
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({heading: parseFloat(heading)})
     });
 
     if (response.ok) {
@@ -229,14 +537,94 @@ async function setHeadingStandby() {
   //updateUI();
 }
 
-async function switchHeading() {
+async function setTurnDirection() {
+
+  let direction = turn_direction.value;
 
   try {
-    const response = await fetch(autopilotAddress.concat("/switch/heading"), {
-      method: "GET",
+    const response = await fetch(autopilotAddress.concat("/settings"), {
+      method: "PUT",
       headers: {
       	"Accept":"*/*",
-      	"Accept-Encoding": "gzip, deflate, br"
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({turn_direction: direction})
+    });
+
+    if (response.ok) {
+    	console.log("Turn direction set to ", direction);
+    } else {
+    	console.error("Turn direction not set");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function setBankLimit() {
+
+  let limit = bank_limit.value;
+
+  try {
+    const response = await fetch(autopilotAddress.concat("/settings"), {
+      method: "PUT",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({bank_limit: limit})
+    });
+
+    if (response.ok) {
+    	console.log("Bank limit set to ", limit);
+    } else {
+    	console.error("Bank limit not set");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function setHeadingReference() {
+
+  let reference = heading_reference.value;
+
+  try {
+    const response = await fetch(autopilotAddress.concat("/settings"), {
+      method: "PUT",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({heading_reference: reference})
+    });
+
+    if (response.ok) {
+    	console.log("Heading reference set to ", reference);
+    } else {
+    	console.error("Heading reference not set");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+
+  //updateUI();
+}
+
+async function switchHeading() {
+
+  try {
+    const response = await fetch(autopilotAddress.concat("/standby/heading/activate"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*"
       }
     });
 
@@ -253,18 +641,141 @@ async function switchHeading() {
   //updateUI();
 }
 
+async function armLNAV() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/arm"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "horizontal", mode: "lnav"})
+    });
+
+    if (response.ok) {
+    	console.log("LNAV armed");
+    } else {
+    	console.error("LNAV not armed");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+}
+
+async function armVNAV() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/arm"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type": "application/json"
+      },
+      body: JSON.stringify({channel: "vertical", mode: "vnav"})
+    });
+
+    if (response.ok) {
+    	console.log("VNAV armed");
+    } else {
+    	console.error("VNAV not armed");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+}
+
+async function disarmModes() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/modes/disarm"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*"
+      }
+    });
+
+    if (response.ok) {
+    	console.log("Modes disarmed");
+    } else {
+    	console.error("Modes not disarmed");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+}
+
+async function setFlightDirector(settings) {
+  try {
+    const response = await fetch(autopilotAddress.concat("/flight_director"), {
+      method: "PUT",
+      headers: {
+      	"Accept":"*/*",
+      	"Content-Type":"application/json"
+      },
+      body: JSON.stringify(settings)
+    });
+
+    if (response.ok) {
+    	console.log("Flight director set");
+    } else {
+    	console.error("Flight director not set");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+}
+
+async function acknowledgeDisconnect() {
+  try {
+    const response = await fetch(autopilotAddress.concat("/disconnect/acknowledge"), {
+      method: "POST",
+      headers: {
+      	"Accept":"*/*"
+      }
+    });
+
+    if (response.ok) {
+    	console.log("Disconnect acknowledged");
+    } else {
+    	console.error("Disconnect not acknowledged");
+    }
+
+  } catch (error) {
+    console.error("Error:", error);
+  }
+}
+
 
 activate_horizontal_standby_button.addEventListener("click", () => activateHorizontalStandby());
 activate_horizontal_wings_level_button.addEventListener("click", () => activateWingsLevel());
 activate_horizontal_heading_button.addEventListener("click", () => activateHeading());
+activate_horizontal_track_button.addEventListener("click", () => activateTrack());
+activate_horizontal_lnav_button.addEventListener("click", () => activateLNAV());
 activate_vertical_standby_button.addEventListener("click", () => activateVerticalStandby());
 activate_vertical_TECS_button.addEventListener("click", () => activateVerticalTECS());
+activate_vertical_vnav_button.addEventListener("click", () => activateVerticalVNAV());
+activate_vertical_vs_button.addEventListener("click", () => activateVerticalVS());
+activate_vertical_flch_button.addEventListener("click", () => activateVerticalFLCH());
+activate_autothrottle_standby_button.addEventListener("click", () => activateAutothrottleStandby());
+activate_autothrottle_speed_button.addEventListener("click", () => activateAutothrottleSpeed());
+arm_lnav_button.addEventListener("click", () => armLNAV());
+arm_vnav_button.addEventListener("click", () => armVNAV());
+disarm_button.addEventListener("click", () => disarmModes());
+disconnect_alert_button.addEventListener("click", () => acknowledgeDisconnect());
+autopilot_coupled_button.addEventListener("click", () => setFlightDirector({ autopilot_coupled: !autoPilotState.autopilot_coupled }));
+flight_director_button.addEventListener("click", () => setFlightDirector({ flight_director_on: !autoPilotState.flight_director_on }));
 
 heading_standby.addEventListener("change", () => setHeadingStandby());
 switch_heading.addEventListener("click", () => switchHeading());
+turn_direction.addEventListener("change", () => setTurnDirection());
+heading_reference.addEventListener("change", () => setHeadingReference());
+bank_limit.addEventListener("change", () => setBankLimit());
 
 var map = L.map('map').setView([51.505, -0.09], 13);
 
-updateUI();
+streamAutoPilotState();
+updatePlaneState();
 
 console.log("Hello world - planepilot UI is active");
@@ -27,6 +27,14 @@ async fn main() {
         std::env::set_var("MIGRATION_PATH", "./pp_dataserver/migrations");
     }
 
+    if std::env::var("CONSTANTS_PATH").is_err() {
+        std::env::set_var("CONSTANTS_PATH", "./constants.json");
+    }
+
+    if std::env::var("PROFILES_PATH").is_err() {
+        std::env::set_var("PROFILES_PATH", "./profiles");
+    }
+
     tracing_subscriber::fmt::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();